@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Controller enable/disable/reset state machine built on top of the
+//! generated [`Nvme`] register block.
+//!
+//! This only sequences register reads, writes, and polls; it knows nothing
+//! about the PCI BAR mapping, queue DMA allocation, or interrupts, so it can
+//! be driven by any caller that has already mapped the controller's
+//! registers.
+
+use super::{regs, vals, Nvme};
+use kernel::error::code::{EIO, ETIME};
+use kernel::error::Result;
+
+/// One unit of [`regs::Cap::to`] is 500ms of real time.
+const CAP_TO_UNIT_MILLIS: u64 = 500;
+
+/// Admin submission/completion queue addresses and sizes, as programmed
+/// into `AQA`/`ASQ`/`ACQ` by [`Nvme::enable`].
+pub struct AdminQueueAddrs {
+    /// DMA address of the admin submission queue.
+    pub sq_dma_addr: u64,
+    /// DMA address of the admin completion queue.
+    pub cq_dma_addr: u64,
+    /// Admin submission queue size, 0's based.
+    pub sq_depth: u16,
+    /// Admin completion queue size, 0's based.
+    pub cq_depth: u16,
+}
+
+impl Nvme {
+    /// Polls `CSTS` until `cond` is satisfied, aborting early on `CSTS.CFS`.
+    ///
+    /// Busy-waits in 500ms increments for up to `cap().to()` units (the
+    /// timeout the controller itself advertises in `CAP`), matching the
+    /// granularity the spec defines that field in.
+    fn poll_csts(self, mut cond: impl FnMut(regs::Csts) -> bool) -> Result {
+        let timeout_millis = u64::from(self.cap().read().to().max(1)) * CAP_TO_UNIT_MILLIS;
+        let mut waited_millis = 0;
+        loop {
+            let csts = self.csts().read();
+            if csts.cfs() {
+                return Err(EIO);
+            }
+            if cond(csts) {
+                return Ok(());
+            }
+            if waited_millis >= timeout_millis {
+                return Err(ETIME);
+            }
+            // SAFETY: `mdelay` just busy-waits; it has no safety requirements
+            // beyond not being called with an absurdly large argument.
+            unsafe { kernel::bindings::mdelay(CAP_TO_UNIT_MILLIS as u32) };
+            waited_millis += CAP_TO_UNIT_MILLIS;
+        }
+    }
+
+    /// Brings the controller up: programs the admin queue registers, sets
+    /// `CC.EN`, and waits for `CSTS.RDY` to flip to 1.
+    ///
+    /// `admin` must describe admin queues that are already allocated and
+    /// ready to receive doorbell writes; this does not set up interrupts or
+    /// submit any commands.
+    ///
+    /// Fails with `ETIME` if the controller does not become ready within
+    /// the CAP-derived timeout, or with `EIO` if `CSTS.CFS` is observed.
+    ///
+    /// Sizing `admin` and the doorbell stride to use when ringing it is the
+    /// caller's responsibility, based on `cap().read().mqes()` and
+    /// `cap().read().dstrd()` respectively -- this only programs the
+    /// registers and brings the controller up.
+    pub fn enable(self, admin: &AdminQueueAddrs) -> Result {
+        let mut aqa = regs::Aqa::default();
+        aqa.set_asqs(admin.sq_depth);
+        aqa.set_acqs(admin.cq_depth);
+        self.aqa().write(aqa);
+        self.asq().write(admin.sq_dma_addr);
+        self.acq().write(admin.cq_dma_addr);
+
+        let mut cc = regs::Cc::default();
+        cc.set_css(0); // NVM command set
+        cc.set_mps((kernel::bindings::PAGE_SHIFT - 12) as u8);
+        cc.set_ams(0); // Round-robin arbitration
+        cc.set_shn(vals::Shn::NONE);
+        cc.set_iosqes(6); // 64 bytes, 2^6
+        cc.set_iocqes(4); // 16 bytes, 2^4
+        cc.set_en(true);
+        self.cc().write(cc);
+
+        self.poll_csts(|csts| csts.rdy())
+    }
+
+    /// Requests a normal shutdown and waits for `CSTS.SHST` to reach
+    /// [`vals::Shst::SHUTDOWNCOMPLETE`].
+    pub fn shutdown(self) -> Result {
+        let mut cc = self.cc().read();
+        cc.set_shn(vals::Shn::NORMAL);
+        self.cc().write(cc);
+
+        self.poll_csts(|csts| csts.shst() == vals::Shst::SHUTDOWNCOMPLETE)
+    }
+
+    /// Clears `CC.EN` and waits for `CSTS.RDY` to drop back to 0, without
+    /// touching the admin queue registers. Used to reset a controller that
+    /// is about to have its admin/I/O queues re-established from scratch.
+    pub fn disable(self) -> Result {
+        let mut cc = self.cc().read();
+        cc.set_en(false);
+        self.cc().write(cc);
+
+        self.poll_csts(|csts| !csts.rdy())
+    }
+}
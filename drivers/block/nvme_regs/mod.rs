@@ -1,5 +1,9 @@
 pub mod regs_rt;
 
+mod ctrl;
+
+pub use ctrl::AdminQueueAddrs;
+
 #[doc = "NVMe controller"]
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub struct Nvme {
@@ -26,6 +30,26 @@ impl Nvme {
     pub const fn csts(self) -> regs_rt::Reg<regs::Csts, regs_rt::R> {
         unsafe { regs_rt::Reg::from_ptr(self.ptr.add(28usize) as _) }
     }
+    #[doc = "Controller Configuration"]
+    #[inline(always)]
+    pub const fn cc(self) -> regs_rt::Reg<regs::Cc, regs_rt::RW> {
+        unsafe { regs_rt::Reg::from_ptr(self.ptr.add(20usize) as _) }
+    }
+    #[doc = "Admin Queue Attributes"]
+    #[inline(always)]
+    pub const fn aqa(self) -> regs_rt::Reg<regs::Aqa, regs_rt::RW> {
+        unsafe { regs_rt::Reg::from_ptr(self.ptr.add(36usize) as _) }
+    }
+    #[doc = "Admin Submission Queue Base Address"]
+    #[inline(always)]
+    pub const fn asq(self) -> regs_rt::Reg<u64, regs_rt::RW> {
+        unsafe { regs_rt::Reg::from_ptr(self.ptr.add(40usize) as _) }
+    }
+    #[doc = "Admin Completion Queue Base Address"]
+    #[inline(always)]
+    pub const fn acq(self) -> regs_rt::Reg<u64, regs_rt::RW> {
+        unsafe { regs_rt::Reg::from_ptr(self.ptr.add(48usize) as _) }
+    }
 }
 pub mod regs {
     #[doc = "Controller Capabilities"]
@@ -66,6 +90,17 @@ pub mod regs {
         pub fn set_dstrd(&mut self, val: u8) {
             self.0 = (self.0 & !(0x0f << 32usize)) | (((val as u64) & 0x0f) << 32usize);
         }
+        #[doc = "Timeout, in 500ms units"]
+        #[inline(always)]
+        pub const fn to(&self) -> u8 {
+            let val = (self.0 >> 24usize) & 0xff;
+            val as u8
+        }
+        #[doc = "Timeout, in 500ms units"]
+        #[inline(always)]
+        pub fn set_to(&mut self, val: u8) {
+            self.0 = (self.0 & !(0xff << 24usize)) | (((val as u64) & 0xff) << 24usize);
+        }
     }
     impl Default for Cap {
         #[inline(always)]
@@ -151,6 +186,129 @@ pub mod regs {
             Csts(0)
         }
     }
+    #[doc = "Controller Configuration"]
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Eq, PartialEq)]
+    pub struct Cc(pub u32);
+    impl Cc {
+        #[doc = "Enable"]
+        #[inline(always)]
+        pub const fn en(&self) -> bool {
+            let val = (self.0 >> 0usize) & 0x01;
+            val != 0
+        }
+        #[doc = "Enable"]
+        #[inline(always)]
+        pub fn set_en(&mut self, val: bool) {
+            self.0 = (self.0 & !(0x01 << 0usize)) | (((val as u32) & 0x01) << 0usize);
+        }
+        #[doc = "I/O Command Set Selected"]
+        #[inline(always)]
+        pub const fn css(&self) -> u8 {
+            let val = (self.0 >> 4usize) & 0x07;
+            val as u8
+        }
+        #[doc = "I/O Command Set Selected"]
+        #[inline(always)]
+        pub fn set_css(&mut self, val: u8) {
+            self.0 = (self.0 & !(0x07 << 4usize)) | (((val as u32) & 0x07) << 4usize);
+        }
+        #[doc = "Memory Page Size, as (4096 << mps) bytes"]
+        #[inline(always)]
+        pub const fn mps(&self) -> u8 {
+            let val = (self.0 >> 7usize) & 0x0f;
+            val as u8
+        }
+        #[doc = "Memory Page Size, as (4096 << mps) bytes"]
+        #[inline(always)]
+        pub fn set_mps(&mut self, val: u8) {
+            self.0 = (self.0 & !(0x0f << 7usize)) | (((val as u32) & 0x0f) << 7usize);
+        }
+        #[doc = "Arbitration Mechanism Selected"]
+        #[inline(always)]
+        pub const fn ams(&self) -> u8 {
+            let val = (self.0 >> 11usize) & 0x07;
+            val as u8
+        }
+        #[doc = "Arbitration Mechanism Selected"]
+        #[inline(always)]
+        pub fn set_ams(&mut self, val: u8) {
+            self.0 = (self.0 & !(0x07 << 11usize)) | (((val as u32) & 0x07) << 11usize);
+        }
+        #[doc = "Shutdown Notification"]
+        #[inline(always)]
+        pub const fn shn(&self) -> super::vals::Shn {
+            let val = (self.0 >> 14usize) & 0x03;
+            super::vals::Shn::from_bits(val as u8)
+        }
+        #[doc = "Shutdown Notification"]
+        #[inline(always)]
+        pub fn set_shn(&mut self, val: super::vals::Shn) {
+            self.0 = (self.0 & !(0x03 << 14usize)) | (((val.to_bits() as u32) & 0x03) << 14usize);
+        }
+        #[doc = "I/O Submission Queue Entry Size, as 2^iosqes bytes"]
+        #[inline(always)]
+        pub const fn iosqes(&self) -> u8 {
+            let val = (self.0 >> 16usize) & 0x0f;
+            val as u8
+        }
+        #[doc = "I/O Submission Queue Entry Size, as 2^iosqes bytes"]
+        #[inline(always)]
+        pub fn set_iosqes(&mut self, val: u8) {
+            self.0 = (self.0 & !(0x0f << 16usize)) | (((val as u32) & 0x0f) << 16usize);
+        }
+        #[doc = "I/O Completion Queue Entry Size, as 2^iocqes bytes"]
+        #[inline(always)]
+        pub const fn iocqes(&self) -> u8 {
+            let val = (self.0 >> 20usize) & 0x0f;
+            val as u8
+        }
+        #[doc = "I/O Completion Queue Entry Size, as 2^iocqes bytes"]
+        #[inline(always)]
+        pub fn set_iocqes(&mut self, val: u8) {
+            self.0 = (self.0 & !(0x0f << 20usize)) | (((val as u32) & 0x0f) << 20usize);
+        }
+    }
+    impl Default for Cc {
+        #[inline(always)]
+        fn default() -> Cc {
+            Cc(0)
+        }
+    }
+    #[doc = "Admin Queue Attributes"]
+    #[repr(transparent)]
+    #[derive(Copy, Clone, Eq, PartialEq)]
+    pub struct Aqa(pub u32);
+    impl Aqa {
+        #[doc = "Admin Submission Queue Size (0's based)"]
+        #[inline(always)]
+        pub const fn asqs(&self) -> u16 {
+            let val = (self.0 >> 0usize) & 0x0fff;
+            val as u16
+        }
+        #[doc = "Admin Submission Queue Size (0's based)"]
+        #[inline(always)]
+        pub fn set_asqs(&mut self, val: u16) {
+            self.0 = (self.0 & !(0x0fff << 0usize)) | (((val as u32) & 0x0fff) << 0usize);
+        }
+        #[doc = "Admin Completion Queue Size (0's based)"]
+        #[inline(always)]
+        pub const fn acqs(&self) -> u16 {
+            let val = (self.0 >> 16usize) & 0x0fff;
+            val as u16
+        }
+        #[doc = "Admin Completion Queue Size (0's based)"]
+        #[inline(always)]
+        pub fn set_acqs(&mut self, val: u16) {
+            self.0 = (self.0 & !(0x0fff << 16usize)) | (((val as u32) & 0x0fff) << 16usize);
+        }
+    }
+    impl Default for Aqa {
+        #[inline(always)]
+        fn default() -> Aqa {
+            Aqa(0)
+        }
+    }
 }
 pub mod vals {
     #[repr(u8)]
@@ -186,4 +344,37 @@ pub mod vals {
             Shst::to_bits(val)
         }
     }
+    #[repr(u8)]
+    #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+    pub enum Shn {
+        #[doc = "No notification; no shutdown in progress"]
+        NONE = 0,
+        #[doc = "Normal shutdown notification"]
+        NORMAL = 0x01,
+        #[doc = "Abrupt shutdown notification"]
+        ABRUPT = 0x02,
+        _RESERVED_3 = 0x03,
+    }
+    impl Shn {
+        #[inline(always)]
+        pub const fn from_bits(val: u8) -> Shn {
+            unsafe { core::mem::transmute(val & 0x03) }
+        }
+        #[inline(always)]
+        pub const fn to_bits(self) -> u8 {
+            unsafe { core::mem::transmute(self) }
+        }
+    }
+    impl From<u8> for Shn {
+        #[inline(always)]
+        fn from(val: u8) -> Shn {
+            Shn::from_bits(val)
+        }
+    }
+    impl From<Shn> for u8 {
+        #[inline(always)]
+        fn from(val: Shn) -> u8 {
+            Shn::to_bits(val)
+        }
+    }
 }
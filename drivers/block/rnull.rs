@@ -6,13 +6,18 @@
 //!
 //! - blk-mq interface
 //! - direct completion
-//! - block size 4k
+//! - configurable capacity, block size, queue count and rotational flag
+//! - optional memory-backed mode, retaining written data like `brd`
 //!
-//! The driver is not configurable.
+//! Configuration happens through configfs, under `/config/rnull/<name>`, and
+//! may only be changed while the device is powered off.
 
+use alloc::boxed::Box;
 use core::fmt::Write;
+use core::pin::Pin;
 use kernel::{
     alloc::flags,
+    bindings,
     block::mq::{
         self,
         gen_disk::{self, GenDisk},
@@ -24,11 +29,13 @@ use kernel::{
     error::Result,
     new_mutex,
     page::PAGE_SIZE,
+    pages::Pages,
     pr_info,
     prelude::*,
     str::CString,
-    sync::{Arc, Mutex},
+    sync::{Arc, ArcBorrow, Mutex},
     types::ARef,
+    xarray::XArray,
 };
 
 module! {
@@ -82,6 +89,12 @@ impl
             container: DeviceConfig,
             attributes: [
                 powered: 0,
+                capacity_mb: 1,
+                logical_block_size: 2,
+                physical_block_size: 3,
+                rotational: 4,
+                submit_queues: 5,
+                memory_backed: 6,
             ],
         };
 
@@ -94,6 +107,12 @@ impl
                     powered: false,
                     disk: None,
                     name: name.try_into()?,
+                    capacity_mb: DEFAULT_CAPACITY_MB,
+                    logical_block_size: 4096,
+                    physical_block_size: 4096,
+                    rotational: false,
+                    submit_queues: 1,
+                    memory_backed: false,
                 }),
             }),
         ))
@@ -111,8 +130,21 @@ struct DeviceConfigInner {
     powered: bool,
     name: CString,
     disk: Option<GenDisk<NullBlkDevice>>,
+    capacity_mb: u64,
+    logical_block_size: u32,
+    physical_block_size: u32,
+    rotational: bool,
+    // Real null_blk calls this module parameter `submit_queues`; it sets the
+    // number of blk-mq hardware queues (and hence `TagSet`'s `nr_hw_queues`).
+    submit_queues: u32,
+    memory_backed: bool,
 }
 
+/// Number of bytes in one sector, the block layer's fixed addressing unit.
+const SECTOR_SIZE: u64 = 512;
+
+const DEFAULT_CAPACITY_MB: u64 = 4096;
+
 #[vtable]
 impl configfs::AttributeOperations<0> for DeviceConfig {
     type Data = DeviceConfig;
@@ -140,14 +172,24 @@ impl configfs::AttributeOperations<0> for DeviceConfig {
         let mut guard = this.data.lock();
 
         if !guard.powered && power_op {
-            let tagset = Arc::pin_init(TagSet::new(1, 256, 1), flags::GFP_KERNEL)?;
+            let tagset = Arc::pin_init(
+                TagSet::new(guard.submit_queues, 256, 1),
+                flags::GFP_KERNEL,
+            )?;
+
+            let memory = if guard.memory_backed {
+                Some(MemoryBacking::new())
+            } else {
+                None
+            };
+            let queue_data = Arc::new(NullBlkQueueData { memory }, flags::GFP_KERNEL)?;
 
             let disk = gen_disk::GenDiskBuilder::new()
-                .capacity_sectors(4096 << 11)
-                .logical_block_size(4096)?
-                .physical_block_size(4096)?
-                .rotational(false)
-                .build(fmt!("{}", guard.name.to_str()?), tagset)?;
+                .capacity_sectors(guard.capacity_mb * (1024 * 1024 / SECTOR_SIZE))
+                .logical_block_size(guard.logical_block_size)?
+                .physical_block_size(guard.physical_block_size)?
+                .rotational(guard.rotational)
+                .build(fmt!("{}", guard.name.to_str()?), tagset, queue_data)?;
 
             guard.disk = Some(disk);
             guard.powered = true;
@@ -160,21 +202,166 @@ impl configfs::AttributeOperations<0> for DeviceConfig {
     }
 }
 
+/// Parses `page` as a value of type `T`, rejecting the write with `EINVAL` if
+/// it is not a valid value of that type once trimmed.
+fn parse_attr<T: core::str::FromStr>(page: &[u8]) -> Result<T> {
+    core::str::from_utf8(page)?
+        .trim()
+        .parse()
+        .map_err(|_| kernel::error::code::EINVAL)
+}
+
+macro_rules! numeric_attr {
+    ($index:literal, $field:ident, $ty:ty) => {
+        #[vtable]
+        impl configfs::AttributeOperations<$index> for DeviceConfig {
+            type Data = DeviceConfig;
+
+            fn show(this: &DeviceConfig, page: &mut [u8; PAGE_SIZE]) -> Result<usize> {
+                let mut writer = kernel::str::BufferWriter::new(page)?;
+                writer.write_fmt(fmt!("{}\n", this.data.lock().$field))?;
+                Ok(writer.pos())
+            }
+
+            fn store(this: &DeviceConfig, page: &[u8]) -> Result {
+                let value: $ty = parse_attr(page)?;
+                let mut guard = this.data.lock();
+                if guard.powered {
+                    return Err(kernel::error::code::EBUSY);
+                }
+                guard.$field = value;
+                Ok(())
+            }
+        }
+    };
+}
+
+numeric_attr!(1, capacity_mb, u64);
+numeric_attr!(2, logical_block_size, u32);
+numeric_attr!(3, physical_block_size, u32);
+numeric_attr!(5, submit_queues, u32);
+
+macro_rules! bool_attr {
+    ($index:literal, $field:ident) => {
+        #[vtable]
+        impl configfs::AttributeOperations<$index> for DeviceConfig {
+            type Data = DeviceConfig;
+
+            fn show(this: &DeviceConfig, page: &mut [u8; PAGE_SIZE]) -> Result<usize> {
+                let mut writer = kernel::str::BufferWriter::new(page)?;
+                writer.write_fmt(fmt!("{}\n", this.data.lock().$field as u8))?;
+                Ok(writer.pos())
+            }
+
+            fn store(this: &DeviceConfig, page: &[u8]) -> Result {
+                let value = parse_attr::<u8>(page)? != 0;
+                let mut guard = this.data.lock();
+                if guard.powered {
+                    return Err(kernel::error::code::EBUSY);
+                }
+                guard.$field = value;
+                Ok(())
+            }
+        }
+    };
+}
+
+bool_attr!(4, rotational);
+bool_attr!(6, memory_backed);
+
+/// A sparse, page-granular backing store for a memory-backed device.
+///
+/// Pages are indexed by the page-aligned slot they cover (byte offset into
+/// the device, divided by [`PAGE_SIZE`]), allocated lazily on first write and
+/// zero-filled on read until then -- the same sparse scheme `brd` uses, just
+/// keyed by an `XArray` instead of a radix tree.
+struct MemoryBacking {
+    pages: Pin<Box<XArray<Box<Pages<0>>>>>,
+}
+
+impl MemoryBacking {
+    fn new() -> Self {
+        Self {
+            pages: Box::pin(XArray::new(0)),
+        }
+    }
+
+    /// Returns the page backing `slot`, allocating and zero-filling it if
+    /// this is the first write to that slot.
+    fn get_or_insert(&self, slot: usize) -> Result<kernel::xarray::Guard<'_, Box<Pages<0>>>> {
+        let pages = self.pages.as_ref();
+        if pages.get(slot).is_none() {
+            pages.replace(slot, Box::try_new(Pages::new()?)?)?;
+        }
+        pages.get(slot).ok_or(kernel::error::code::ENOMEM)
+    }
+}
+
+/// Per-disk state handed to [`NullBlkDevice::queue_rq`] as `queue_data`, set
+/// up when the device is powered on.
+struct NullBlkQueueData {
+    memory: Option<MemoryBacking>,
+}
+
+/// Copies `rq`'s payload into or out of `memory`'s sparse page map, depending
+/// on whether it is a write or a read.
+///
+/// The block layer clamps every [`Segment`](kernel::block::bio::Segment) to a
+/// single page, so this assumes (as the C null_blk driver does) that each
+/// segment lines up with exactly one of `memory`'s `PAGE_SIZE`-sized slots.
+fn copy_request(rq: &mq::Request<NullBlkDevice>, memory: &MemoryBacking) -> Result {
+    let is_write = rq.command() == bindings::req_op_REQ_OP_WRITE;
+    let mut byte_pos = rq.sector() as u64 * SECTOR_SIZE;
+
+    for bio in rq.bio_iter() {
+        for mut segment in bio.segment_iter() {
+            let slot = (byte_pos / PAGE_SIZE as u64) as usize;
+
+            if is_write {
+                let mut page = memory.get_or_insert(slot)?;
+                segment.copy_to_page_atomic(page.borrow_mut())?;
+            } else if let Some(page) = memory.pages.as_ref().get(slot) {
+                segment.copy_from_page_atomic(page.borrow())?;
+            } else {
+                // Never written: sparse regions read back as zero.
+                segment.map_local().fill(0);
+            }
+
+            byte_pos += segment.len() as u64;
+        }
+    }
+
+    Ok(())
+}
+
 struct NullBlkDevice;
 
 #[vtable]
 impl Operations for NullBlkDevice {
+    type QueueData = Arc<NullBlkQueueData>;
+
     #[inline(always)]
-    fn queue_rq(rq: ARef<mq::Request<Self>>, _is_last: bool) -> Result {
-        mq::Request::end_ok(rq)
-            .map_err(|_e| kernel::error::code::EIO)
-            // We take no refcounts on the request, so we expect to be able to
-            // end the request. The request reference must be unique at this
-            // point, and so `end_ok` cannot fail.
-            .expect("Fatal error - expected to be able to end request");
+    fn queue_rq(
+        queue_data: ArcBorrow<'_, NullBlkQueueData>,
+        rq: ARef<mq::Request<Self>>,
+        _is_last: bool,
+    ) -> Result {
+        let result = match &queue_data.memory {
+            Some(memory) => copy_request(&rq, memory),
+            None => Ok(()),
+        };
+
+        match result {
+            Ok(()) => mq::Request::end_ok(rq).map_err(|_| ()),
+            Err(e) => mq::Request::end_err(rq, e).map_err(|_| ()),
+        }
+        // We take no refcounts on the request, so we expect to be able to
+        // end the request. The request reference must be unique at this
+        // point, and so `end_ok`/`end_err` cannot fail.
+        .expect("Fatal error - expected to be able to end request");
 
         Ok(())
     }
 
-    fn commit_rqs() {}
+    fn commit_rqs(_queue_data: ArcBorrow<'_, NullBlkQueueData>) {}
 }
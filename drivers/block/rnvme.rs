@@ -10,7 +10,7 @@ use core::{
     convert::TryInto,
     format_args,
     pin::Pin,
-    sync::atomic::{AtomicU16, AtomicU32, AtomicU64, Ordering},
+    sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, Ordering},
 };
 use kernel::{
     bindings,
@@ -42,14 +42,32 @@ use nvme_driver_defs::*;
 struct NvmeData {
     db_stride: usize,
     dev: Device,
-    pci_dev: pci::Device,
+    /// Our own handle to the PCI device, kept around (behind a lock, since
+    /// the methods that use it need `&mut`) so that [`NvmeDevice::remove`]
+    /// can free IRQ vectors after `probe`'s own `&mut pci::Device` parameter
+    /// has gone out of scope.
+    #[pin]
+    pci_dev: SpinLock<pci::Device>,
     instance: u32,
-    shadow: Option<NvmeShadow>,
+    #[pin]
+    shadow: SpinLock<Option<NvmeShadow>>,
+    /// The Host Memory Buffer handed to the controller in
+    /// [`NvmeDevice::setup_host_mem_buf`], if any. `None` both when the
+    /// controller didn't ask for one and when `nvme_max_host_mem_size_mb`
+    /// disables the feature.
+    #[pin]
+    hmb: SpinLock<Option<NvmeHmb>>,
     #[pin]
     queues: SpinLock<NvmeQueues>,
     dma_pool: Arc<dma::Pool<le<u64>>>,
     poll_queue_count: u32,
     irq_queue_count: u32,
+    /// Whether the controller advertises SGL support (Identify Controller
+    /// SGLS bit 0), discovered during [`NvmeDevice::dev_add`]. Meant to be
+    /// consulted by the request mapping path in `nvme_mq` (not part of this
+    /// tree) to decide whether a request's data transfer should be mapped
+    /// with [`build_sgl`] instead of falling back to PRPs.
+    sgl_supported: AtomicBool,
 }
 
 struct NvmeResources {
@@ -58,14 +76,115 @@ struct NvmeResources {
 
 struct NvmeQueues {
     admin: Option<Arc<nvme_queue::NvmeQueue<nvme_mq::AdminQueueOperations>>>,
+    /// The admin request queue, kept around so that [`NvmeDevice::remove`]
+    /// can submit Delete SQ/CQ admin commands during teardown.
+    admin_mq: Option<mq::RequestQueue<nvme_mq::AdminQueueOperations>>,
     io: Vec<Arc<nvme_queue::NvmeQueue<nvme_mq::IoQueueOperations>>>,
+    /// Namespaces added in [`NvmeDevice::dev_add`]. Previously leaked with
+    /// `core::mem::forget`; tracked here so [`NvmeDevice::remove`] can remove
+    /// them from the VFS again.
+    disks: Vec<mq::GenDisk<nvme_mq::IoQueueOperations>>,
 }
 
+/// The shadow doorbell buffers used to avoid MMIO doorbell writes on the
+/// common path.
+///
+/// Once the controller has acknowledged a `dbbuf_set` admin command, the SQ
+/// tail and CQ head doorbells for every queue can instead be updated in
+/// `dbs`, with the controller polling them itself. The driver only needs to
+/// fall back to a real MMIO doorbell write when the controller's last
+/// observed event index (`eis`) has fallen behind, which [`NvmeShadow::update`]
+/// determines.
+///
+/// [`NvmeDevice::ring_sq_doorbell`] and [`NvmeDevice::ring_cq_doorbell`] are the entry
+/// points that thread [`NvmeShadow::update_sq_tail`]/[`NvmeShadow::update_cq_head`]
+/// together with the MMIO fallback; `nvme_queue` (not part of this tree) is expected to
+/// call them instead of writing the doorbell registers directly.
 struct NvmeShadow {
     dbs: dma::CoherentAllocation<u32, dma::CoherentAllocator>,
     eis: dma::CoherentAllocation<u32, dma::CoherentAllocator>,
 }
 
+impl NvmeShadow {
+    /// Returns whether a real MMIO doorbell write is still required after the
+    /// doorbell value for `qid`'s submission queue tail was shadow-updated to
+    /// `new_tail`.
+    ///
+    /// The admin queue (`qid == 0`) never uses the shadow buffers and must
+    /// keep ringing the real doorbell until `dbbuf_set` has completed.
+    fn update_sq_tail(&self, qid: u16, db_stride: usize, new_tail: u16) -> bool {
+        self.update(Self::sq_tail_index(qid, db_stride), new_tail)
+    }
+
+    /// Returns whether a real MMIO doorbell write is still required after the
+    /// doorbell value for `qid`'s completion queue head was shadow-updated to
+    /// `new_head`.
+    fn update_cq_head(&self, qid: u16, db_stride: usize, new_head: u16) -> bool {
+        self.update(Self::cq_head_index(qid, db_stride), new_head)
+    }
+
+    /// Index, in `u32` elements, of the SQ tail doorbell slot for `qid`.
+    fn sq_tail_index(qid: u16, db_stride: usize) -> usize {
+        qid as usize * db_stride * 2 / core::mem::size_of::<u32>()
+    }
+
+    /// Index, in `u32` elements, of the CQ head doorbell slot for `qid`.
+    fn cq_head_index(qid: u16, db_stride: usize) -> usize {
+        Self::sq_tail_index(qid, db_stride) + db_stride / core::mem::size_of::<u32>()
+    }
+
+    /// Updates the shadow doorbell slot at `index` to `new_value` and decides
+    /// whether the controller still needs a real doorbell write to notice it.
+    fn update(&self, index: usize, new_value: u16) -> bool {
+        let old_value = self.dbs.read(index) as u16;
+        self.dbs.write(index, &u32::from(new_value));
+
+        // Pairs with the controller's read of the shadow doorbell: the new
+        // value must be visible before we consult the event index it may
+        // have been written in response to.
+        core::sync::atomic::fence(Ordering::SeqCst);
+
+        let event_idx = self.eis.read(index) as u16;
+        Self::need_event(event_idx, new_value, old_value)
+    }
+
+    /// Whether the controller needs an explicit doorbell write to notice that
+    /// the shadow doorbell moved from `old` to `new`, given that it last told
+    /// us (via the event index buffer) it was watching for `event_idx`.
+    ///
+    /// This is the wrapping-`u16` comparison used by the NVMe shadow
+    /// doorbell protocol: a doorbell write is needed only if `event_idx`
+    /// lies in the range `(old, new]` modulo 2^16, i.e. the doorbell has just
+    /// moved onto or past the value the controller asked to be told about.
+    fn need_event(event_idx: u16, new: u16, old: u16) -> bool {
+        new.wrapping_sub(event_idx) < new.wrapping_sub(old)
+    }
+}
+
+/// One entry of a Host Memory Buffer descriptor list, as laid out by the
+/// NVMe spec: a DMA address (in bytes) and a size in units of the
+/// controller's memory page size.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct NvmeHmbDesc {
+    addr: le<u64>,
+    size: le<u32>,
+    reserved: u32,
+}
+
+/// The Host Memory Buffer allocated for a DRAM-less controller in
+/// [`NvmeDevice::setup_host_mem_buf`].
+///
+/// `chunk` is the host memory actually handed to the controller; `descs` is
+/// the descriptor list pointing at it, which the controller reads once when
+/// the buffer is enabled via Set Features. Both must outlive the buffer
+/// being enabled, so they are kept here rather than dropped at the end of
+/// `dev_add`.
+struct NvmeHmb {
+    descs: dma::CoherentAllocation<NvmeHmbDesc, dma::CoherentAllocator>,
+    chunk: dma::CoherentAllocation<u8, dma::CoherentAllocator>,
+}
+
 type DeviceData = device::Data<(), NvmeResources, NvmeData>;
 
 #[pin_data]
@@ -89,6 +208,21 @@ struct NvmeNamespace {
     lba_shift: u32,
 }
 
+// TODO: `nvme_mq::{AdminQueueOperations, IoQueueOperations}::ioctl`/`compat_ioctl`
+// (block::mq::Operations now has both, wired through to the gendisk's
+// `block_device_operations` by `block::mq::gen_disk`) should implement
+// NVME_IOCTL_ADMIN_CMD/NVME_IOCTL_IO_CMD-style passthrough: copy the fixed
+// 64-byte command plus data buffer pointer/length/direction in from `arg`,
+// reject the call unless the caller is capable (`CAP_SYS_ADMIN` for admin
+// passthrough), clamp the transfer length to the controller's `max_sectors`
+// (computed from MDTS in `dev_add`), map the user buffer the same way
+// `MappingData` maps request data, build an `NvmeCommand` from the copied-in
+// fields, and dispatch it through `NvmeDevice::submit_sync_command` for
+// admin passthrough or through the namespace's own I/O queue for I/O
+// passthrough, copying the completion's result and status dword back out.
+// That dispatch logic is implemented in `nvme_mq`, which is not part of
+// this tree.
+
 const fn div_round_up(a: usize, b: usize) -> usize {
     (a + (b - 1)) / b
 }
@@ -101,9 +235,99 @@ const fn npages_prp() -> usize {
     div_round_up(8 * nprps, nvme_driver_defs::NVME_CTRL_PAGE_SIZE - 8)
 }
 
+/// PRP/SGL Data Transfer (PSDT) bits of `NvmeCommon::flags` (bits 6:7): `00` (the default)
+/// selects the PRP fields; `01`, set by [`build_sgl`], selects a single SGL segment
+/// referenced by `prp1` instead.
+const NVME_CMD_PSDT_SGL_METABUF: u8 = 1 << 6;
+
+/// SGL Descriptor Type nibble (upper nibble of an [`NvmeSglDesc`]'s `kind` byte) for a
+/// plain data block descriptor.
+const NVME_SGL_FMT_DATA_DESC: u8 = 0x00;
+
+/// SGL Descriptor Type nibble for a "last segment" descriptor: one that itself points at
+/// the final (here, only) segment of further descriptors, rather than at data.
+const NVME_SGL_FMT_LAST_SEG_DESC: u8 = 0x03 << 4;
+
+/// One entry of an NVMe SGL segment, as laid out by the spec: a DMA address, a byte
+/// length, 3 reserved bytes, and a type byte (`kind`, upper nibble the SGL Descriptor
+/// Type, lower nibble the SGL Descriptor Sub Type, always 0 here).
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct NvmeSglDesc {
+    addr: le<u64>,
+    len: le<u32>,
+    reserved: [u8; 3],
+    kind: u8,
+}
+
+/// Builds an NVMe SGL segment in `mapping.sgl` for `sg[..sg_count]`, sets the PSDT bits
+/// in `cmd`'s common flags, and returns the DMA address to place in `cmd`'s `prp1` field.
+///
+/// Meant to be called by the request mapping path in `nvme_mq` (not part of this tree) in
+/// place of the PRP list path below, whenever [`NvmeData::sgl_supported`] is set.
+/// [`teardown_sgl`] undoes it; it does not touch the PRP list.
+///
+/// For a single segment, `prp1` points at the lone data block descriptor in
+/// `mapping.sgl[0]`, so it is simply `sgl_dma_addr`. For more than one, the descriptors are chained
+/// through a "last segment" descriptor as the spec requires: `mapping.sgl[..sg_count]`
+/// holds the data block descriptors and `mapping.sgl[sg_count]` the last-segment
+/// descriptor wrapping them, whose own DMA address is what `prp1` must point to instead.
+/// `sgl_dma_addr` is the DMA address of `mapping.sgl` itself.
+fn build_sgl(
+    sg: &[bindings::scatterlist],
+    sg_count: usize,
+    mapping: &mut MappingData,
+    sgl_dma_addr: u64,
+    cmd: &mut NvmeCommand,
+) -> Result<u64> {
+    if sg_count == 0 || sg_count >= mapping.sgl.len() {
+        return Err(EINVAL);
+    }
+
+    for (i, entry) in sg.iter().take(sg_count).enumerate() {
+        mapping.sgl[i] = NvmeSglDesc {
+            addr: u64::from(entry.dma_address).into(),
+            len: entry.length.into(),
+            reserved: [0; 3],
+            kind: NVME_SGL_FMT_DATA_DESC,
+        };
+    }
+
+    cmd.common.flags |= NVME_CMD_PSDT_SGL_METABUF;
+
+    if sg_count == 1 {
+        return Ok(sgl_dma_addr);
+    }
+
+    let desc_size = core::mem::size_of::<NvmeSglDesc>() as u64;
+    let last_seg_addr = sgl_dma_addr + desc_size * sg_count as u64;
+    mapping.sgl[sg_count] = NvmeSglDesc {
+        addr: sgl_dma_addr.into(),
+        len: (desc_size as u32 * sg_count as u32).into(),
+        reserved: [0; 3],
+        kind: NVME_SGL_FMT_LAST_SEG_DESC,
+    };
+    Ok(last_seg_addr)
+}
+
+/// Undoes [`build_sgl`] once the command it was built for has completed.
+///
+/// The descriptors themselves need no DMA teardown of their own: they live in the
+/// pre-allocated, DMA-coherent `mapping.sgl` buffer that is reused by the next request.
+/// Clearing them avoids leaking a stale descriptor list into a future PRP-path request
+/// that reuses the same `MappingData` without repopulating `sgl`.
+fn teardown_sgl(mapping: &mut MappingData, sg_count: usize) {
+    for entry in mapping.sgl.iter_mut().take(sg_count + 1) {
+        *entry = NvmeSglDesc::default();
+    }
+}
+
 struct MappingData {
     sg: [bindings::scatterlist; nvme_driver_defs::NVME_MAX_SEGS],
     pages: [usize; npages_prp()],
+    /// SGL segment built by [`build_sgl`] when [`NvmeData::sgl_supported`] is set; unused
+    /// (and left zeroed by [`teardown_sgl`]) on the PRP path.
+    sgl: [NvmeSglDesc; nvme_driver_defs::NVME_MAX_SEGS],
 }
 
 impl Default for MappingData {
@@ -111,6 +335,7 @@ impl Default for MappingData {
         Self {
             sg: [bindings::scatterlist::default(); nvme_driver_defs::NVME_MAX_SEGS],
             pages: [0; npages_prp()],
+            sgl: [NvmeSglDesc::default(); nvme_driver_defs::NVME_MAX_SEGS],
         }
     }
 }
@@ -146,13 +371,14 @@ impl NvmeDevice {
             id: nsid,
             lba_shift,
         })?;
-        let mut disk = mq::GenDisk::try_new(tagset, ns)?;
+        let limits = mq::QueueLimits::new()
+            .logical_block_size(1 << lba_shift)?
+            .max_hw_sectors(max_sectors)?
+            .max_segments(nvme_driver_defs::NVME_MAX_SEGS as u16)?
+            .virt_boundary_mask(u64::from(nvme_driver_defs::NVME_CTRL_PAGE_SIZE - 1));
+        let mut disk = mq::GenDisk::try_new(tagset, ns, Some(limits))?;
         disk.set_name(format_args!("nvme{}n{}", instance, nsid))?;
         disk.set_capacity_sectors(id.nsze.into() << (lba_shift - bindings::SECTOR_SHIFT));
-        disk.set_queue_logical_block_size(1 << lba_shift);
-        disk.set_queue_max_hw_sectors(max_sectors);
-        disk.set_queue_max_segments(nvme_driver_defs::NVME_MAX_SEGS as _);
-        disk.set_queue_virt_boundary(nvme_driver_defs::NVME_CTRL_PAGE_SIZE - 1);
         Ok(disk)
     }
 
@@ -193,7 +419,15 @@ impl NvmeDevice {
 
         pr_info!("HW queue depth: {}\n", q_depth);
         pr_info!("HW queue count: {}\n", nr_io_queues);
-        let tagset = Arc::pin_init(mq::TagSet::try_new(nr_io_queues, dev.clone(), q_depth, 3))?; //TODO: 1 or 3 on demand, depending on polling enabled
+        let io_timeout_jiffies = (*nvme_io_timeout.read() as u32).saturating_mul(bindings::HZ);
+        //TODO: 1 or 3 on demand, depending on polling enabled
+        let tagset = Arc::pin_init(mq::TagSet::try_new(
+            nr_io_queues,
+            dev.clone(),
+            q_depth,
+            3,
+            mq::TagSetConfig::new().timeout_jiffies(io_timeout_jiffies),
+        ))?;
 
         dev.queues.lock().io.try_reserve(nr_io_queues as _)?;
         for i in 1..=nr_io_queues {
@@ -201,7 +435,17 @@ impl NvmeDevice {
 
             let polled: bool = i > dev.irq_queue_count;
 
-            let vector = if !polled { qid % (irqs as u16) } else { 0 };
+            // IRQ vector 0 belongs to the admin queue (registered in
+            // `configure_admin_queue`); IO queue `qid` was allocated one
+            // vector each, in order, by `alloc_irq_vectors_affinity` above,
+            // so `qid` is also its vector number. `map_io_queues` relies on
+            // this 1:1 numbering to route each CPU to the IO queue whose
+            // interrupt it will service.
+            let vector = if !polled {
+                core::cmp::min(qid, irqs as u16 - 1)
+            } else {
+                0
+            };
 
             pr_info!(
                 "Setting up queue {}, vector: {}, polled: {}\n",
@@ -236,6 +480,46 @@ impl NvmeDevice {
         Ok(tagset)
     }
 
+    /// Builds the IO tagset's CPU-to-hardware-queue map so that each CPU
+    /// submits on the queue whose completion interrupt it will service,
+    /// instead of leaving the block layer's IRQ-affinity-unaware default
+    /// mapping in place.
+    ///
+    /// Should be called from [`nvme_mq::IoQueueOperations::map_queues`]
+    /// (not part of this tree), which the block layer invokes whenever it
+    /// (re)builds the tagset's queue maps.
+    ///
+    /// Mirrors `blk_mq_pci_map_queues`: for the default (and, since no
+    /// dedicated read queues are configured, read) hardware context type,
+    /// each possible CPU is routed to the IO queue whose IRQ vector's
+    /// affinity mask includes it. `vector` and hardware context index are
+    /// related by the 1:1 numbering `setup_io_queues` assigns: IO queue
+    /// `qid` uses vector `qid` and hardware context `qid - 1`. Polled
+    /// queues aren't interrupt-driven, so the poll hardware context type is
+    /// just evenly distributed with the kernel's default mapping.
+    fn map_io_queues(tagset: &mut mq::QueueMapSet<'_>, pci_dev: &pci::Device, irq_queue_count: u32) {
+        for ty in [mq::HctxType::Default, mq::HctxType::Read] {
+            let Some(mut map) = tagset.get(ty) else {
+                continue;
+            };
+
+            for cpu in 0..kernel::num_possible_cpus() {
+                let mut hctx_idx = 0;
+                for vector in 1..=irq_queue_count {
+                    if pci_dev.irq_vector_has_cpu(vector, cpu) {
+                        hctx_idx = vector - 1;
+                        break;
+                    }
+                }
+                map.set(cpu, hctx_idx);
+            }
+        }
+
+        if let Some(mut map) = tagset.get(mq::HctxType::Poll) {
+            map.map_queues();
+        }
+    }
+
     fn dev_add(
         cap: u64,
         dev: &Arc<DeviceData>,
@@ -254,10 +538,25 @@ impl NvmeDevice {
 
         let number_of_namespaces;
         let mdts;
+        let hmpre;
+        let hmmin;
         {
             let ctrl_id = unsafe { &*(id.first_ptr() as *const NvmeIdCtrl) };
             number_of_namespaces = ctrl_id.nn.into();
             mdts = ctrl_id.mdts;
+            hmpre = ctrl_id.hmpre.into();
+            hmmin = ctrl_id.hmmin.into();
+
+            let sgls: u32 = ctrl_id.sgls.into();
+            dev.sgl_supported
+                .store(sgls & NVME_CTRL_SGLS_SUPPORTED != 0, Ordering::Relaxed);
+        }
+
+        // A DRAM-less controller advertises HMPRE != 0 to ask for a chunk of
+        // host memory it can use as its own DRAM would be; this is optional
+        // and best-effort; a failure here must not fail `probe`.
+        if let Err(e) = Self::setup_host_mem_buf(dev, pci_dev, mq, hmpre, hmmin) {
+            pr_info!("Failed to set up host memory buffer: {:?}\n", e);
         }
 
         let max_sectors = if let Some(blocks) = calculate_max_blocks(cap, mdts) {
@@ -285,18 +584,87 @@ impl NvmeDevice {
             if let Ok(disk) =
                 Self::alloc_ns(max_sectors, dev.instance, i, id_ns, tagset.clone(), rt)
             {
-                // TODO: Add disk to list.
                 pr_info!("about to add disk\n");
-                disk.add();
-                pr_info!("disk added\n");
-
-                core::mem::forget(disk);
+                match disk.add() {
+                    Ok(disk) => {
+                        // Tracked here (instead of leaked with
+                        // `core::mem::forget`) so `remove` can unregister it
+                        // again on module unload/device removal.
+                        if let Err(e) = dev.queues.lock().disks.try_push(disk) {
+                            pr_info!("Failed to track added disk: {:?}\n", e);
+                        } else {
+                            pr_info!("disk added\n");
+                        }
+                    }
+                    Err(e) => pr_info!("Failed to add disk for namespace {}: {:?}\n", i, e),
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Sets up a Host Memory Buffer for a DRAM-less controller that asked
+    /// for one (`hmpre != 0`), bounded by `nvme_max_host_mem_size_mb`.
+    ///
+    /// `hmpre` and `hmmin` are the controller's preferred and minimum HMB
+    /// sizes from Identify Controller, in units of the memory page size. If
+    /// the module param leaves us unable to meet `hmmin`, the controller is
+    /// left without a host memory buffer, exactly as if it had not asked
+    /// for one.
+    fn setup_host_mem_buf(
+        dev: &Arc<DeviceData>,
+        pci_dev: &pci::Device,
+        mq: &mq::RequestQueue<nvme_mq::AdminQueueOperations>,
+        hmpre: u32,
+        hmmin: u32,
+    ) -> Result {
+        if hmpre == 0 {
+            return Ok(());
+        }
+
+        let max_mb = *nvme_max_host_mem_size_mb.read();
+        if max_mb <= 0 {
+            return Ok(());
+        }
+
+        let page_size = u64::from(NVME_CTRL_PAGE_SIZE);
+        let wanted = core::cmp::min(u64::from(hmpre) * page_size, (max_mb as u64) * 1024 * 1024);
+        let minimum = u64::from(hmmin) * page_size;
+        if wanted < minimum {
+            pr_info!(
+                "Not enough host memory budgeted for HMB (wanted {}, minimum {})\n",
+                wanted,
+                minimum
+            );
+            return Ok(());
+        }
+
+        let chunk_size: usize = wanted.try_into()?;
+        let chunk = dma::try_alloc_coherent::<u8>(pci_dev, chunk_size, false)?;
+        let descs = dma::try_alloc_coherent::<NvmeHmbDesc>(pci_dev, 1, false)?;
+        descs.write(
+            0,
+            &NvmeHmbDesc {
+                addr: chunk.dma_handle.into(),
+                size: (chunk_size as u32 / NVME_CTRL_PAGE_SIZE).into(),
+                ..NvmeHmbDesc::default()
+            },
+        );
+
+        Self::set_host_mem_buf(
+            mq,
+            true,
+            chunk_size as u32 / NVME_CTRL_PAGE_SIZE,
+            descs.dma_handle,
+            1,
+        )?;
+
+        *dev.hmb.lock() = Some(NvmeHmb { descs, chunk });
+        pr_info!("Host memory buffer enabled: {} bytes\n", chunk_size);
+        Ok(())
+    }
+
     fn wait_ready(dev: &Arc<DeviceData>) {
         pr_info!("Waiting for controller ready\n");
         {
@@ -346,8 +714,17 @@ impl NvmeDevice {
 
         //TODO: Depth?
         let queue_depth = 64;
-        let admin_tagset: Arc<mq::TagSet<nvme_mq::AdminQueueOperations>> =
-            Arc::pin_init(mq::TagSet::try_new(1, dev.clone(), queue_depth, 1))?;
+        let admin_timeout_jiffies =
+            (*nvme_admin_timeout.read() as u32).saturating_mul(bindings::HZ);
+        let admin_tagset: Arc<mq::TagSet<nvme_mq::AdminQueueOperations>> = Arc::pin_init(
+            mq::TagSet::try_new(
+                1,
+                dev.clone(),
+                queue_depth,
+                1,
+                mq::TagSetConfig::new().timeout_jiffies(admin_timeout_jiffies),
+            ),
+        )?;
         let admin_queue: Arc<nvme_queue::NvmeQueue<nvme_mq::AdminQueueOperations>> =
             nvme_queue::NvmeQueue::try_new(
                 dev.clone(),
@@ -364,6 +741,7 @@ impl NvmeDevice {
             lba_shift: 9,
         })?;
         let admin_mq = mq::RequestQueue::try_new(admin_tagset, ns)?;
+        dev.queues.lock().admin_mq = Some(admin_mq.clone());
 
         let mut aqa = (queue_depth - 1) as u32;
         aqa |= aqa << 16;
@@ -393,6 +771,53 @@ impl NvmeDevice {
         Ok((admin_queue, admin_mq))
     }
 
+    /// Performs a full controller reset: disables the controller, waits for
+    /// it to go idle, then re-establishes the admin and I/O queues exactly
+    /// as [`Self::configure_admin_queue`] and [`Self::setup_io_queues`] do on
+    /// first attach.
+    ///
+    /// Called by the blk-mq timeout handler (in `nvme_mq`, not part of this
+    /// tree) once an Abort command has itself timed out, or once `CSTS.CFS`
+    /// is observed. Requeuing the commands that were outstanding on the old
+    /// queues is the caller's responsibility, since that requires iterating
+    /// the old tag sets before they are dropped here.
+    fn reset_controller(
+        dev: &Arc<DeviceData>,
+        pci_dev: &mut pci::Device,
+    ) -> Result<(
+        Arc<nvme_queue::NvmeQueue<nvme_mq::AdminQueueOperations>>,
+        mq::RequestQueue<nvme_mq::AdminQueueOperations>,
+    )> {
+        pr_info!("Resetting controller\n");
+
+        // The controller forgets about the host memory buffer across a
+        // reset anyway, but give it a chance to flush any state tied to it
+        // with a clean Set Features disable first.
+        if dev.hmb.lock().is_some() {
+            if let Some(admin_mq) = dev.queues.lock().admin_mq.clone() {
+                if let Err(e) = Self::set_host_mem_buf(&admin_mq, false, 0, 0, 0) {
+                    pr_info!("Failed to disable host memory buffer: {:?}\n", e);
+                }
+            }
+        }
+
+        {
+            dev.resources().unwrap().bar.writel(0, OFFSET_CC);
+        }
+        Self::wait_idle(dev);
+
+        let mut queues = dev.queues.lock();
+        queues.admin = None;
+        queues.io.clear();
+        drop(queues);
+
+        let (admin_queue, admin_mq) = Self::configure_admin_queue(dev, pci_dev)?;
+        Self::setup_io_queues(dev, pci_dev, &admin_queue, &admin_mq)?;
+
+        pr_info!("Controller reset done\n");
+        Ok((admin_queue, admin_mq))
+    }
+
     fn submit_sync_command(
         mq: &mq::RequestQueue<nvme_mq::AdminQueueOperations>,
         mut cmd: NvmeCommand,
@@ -470,6 +895,85 @@ impl NvmeDevice {
         )
     }
 
+    /// Rings the submission queue tail doorbell for `qid` after `new_tail` slots have
+    /// been filled in, using the shadow doorbell buffer (see [`NvmeShadow`]) instead of
+    /// a real MMIO write whenever the controller has acknowledged `dbbuf_set` for it.
+    ///
+    /// Meant to be called by `nvme_queue` (not part of this tree) every time a command
+    /// is queued for submission on `qid`. The admin queue (`qid == 0`) always falls back
+    /// to a real MMIO write: it is itself the queue `dbbuf_set` is sent on, so the shadow
+    /// buffers can't be trusted for it until after that command has already completed.
+    fn ring_sq_doorbell(dev: &Arc<DeviceData>, qid: u16, new_tail: u16) {
+        if qid != 0 {
+            if let Some(shadow) = &*dev.shadow.lock() {
+                if !shadow.update_sq_tail(qid, dev.db_stride, new_tail) {
+                    return;
+                }
+            }
+        }
+
+        dev.resources().unwrap().bar.writel(
+            u32::from(new_tail),
+            OFFSET_DBS + 2 * qid as usize * dev.db_stride,
+        );
+    }
+
+    /// Rings the completion queue head doorbell for `qid` after `new_head` entries have
+    /// been consumed, using the shadow doorbell buffer instead of a real MMIO write
+    /// whenever available. See [`Self::ring_sq_doorbell`] for why the admin queue is
+    /// excluded from the shadow path.
+    fn ring_cq_doorbell(dev: &Arc<DeviceData>, qid: u16, new_head: u16) {
+        if qid != 0 {
+            if let Some(shadow) = &*dev.shadow.lock() {
+                if !shadow.update_cq_head(qid, dev.db_stride, new_head) {
+                    return;
+                }
+            }
+        }
+
+        dev.resources().unwrap().bar.writel(
+            u32::from(new_head),
+            OFFSET_DBS + (2 * qid as usize + 1) * dev.db_stride,
+        );
+    }
+
+    /// Deletes the submission queue half of `queue`.
+    ///
+    /// Per spec, the submission queue of a queue pair must be deleted before
+    /// its completion queue.
+    fn delete_submission_queue<T: mq::Operations<RequestData = NvmeRequest>>(
+        mq: &mq::RequestQueue<nvme_mq::AdminQueueOperations>,
+        queue: &nvme_queue::NvmeQueue<T>,
+    ) -> Result<u32> {
+        Self::submit_sync_command(
+            mq,
+            NvmeCommand {
+                delete_queue: NvmeDeleteQueue {
+                    opcode: NvmeAdminOpcode::delete_sq as _,
+                    qid: queue.qid.into(),
+                    ..NvmeDeleteQueue::default()
+                },
+            },
+        )
+    }
+
+    /// Deletes the completion queue half of `queue`.
+    fn delete_completion_queue<T: mq::Operations<RequestData = NvmeRequest>>(
+        mq: &mq::RequestQueue<nvme_mq::AdminQueueOperations>,
+        queue: &nvme_queue::NvmeQueue<T>,
+    ) -> Result<u32> {
+        Self::submit_sync_command(
+            mq,
+            NvmeCommand {
+                delete_queue: NvmeDeleteQueue {
+                    opcode: NvmeAdminOpcode::delete_cq as _,
+                    qid: queue.qid.into(),
+                    ..NvmeDeleteQueue::default()
+                },
+            },
+        )
+    }
+
     fn identify(
         mq: &mq::RequestQueue<nvme_mq::AdminQueueOperations>,
         nsid: u32,
@@ -533,6 +1037,36 @@ impl NvmeDevice {
         ret
     }
 
+    /// Enables or disables the Host Memory Buffer via Set Features (feature
+    /// ID `0x0D`), pointing the controller at `nr_descs` descriptors
+    /// starting at `descs_dma`, covering `hsize` memory pages in total.
+    ///
+    /// To disable, pass `enable: false`; the remaining arguments are then
+    /// ignored by the controller and may be zero.
+    fn set_host_mem_buf(
+        mq: &mq::RequestQueue<nvme_mq::AdminQueueOperations>,
+        enable: bool,
+        hsize: u32,
+        descs_dma: u64,
+        nr_descs: u32,
+    ) -> Result<u32> {
+        Self::submit_sync_command(
+            mq,
+            NvmeCommand {
+                features: NvmeFeatures {
+                    opcode: NvmeAdminOpcode::set_features as _,
+                    fid: NVME_FEAT_HOST_MEM_BUF.into(),
+                    dword11: (enable as u32).into(),
+                    dword12: hsize.into(),
+                    dword13: (descs_dma as u32).into(),
+                    dword14: ((descs_dma >> 32) as u32).into(),
+                    dword15: nr_descs.into(),
+                    ..NvmeFeatures::default()
+                },
+            },
+        )
+    }
+
     fn dbbuf_set(
         mq: &mq::RequestQueue<nvme_mq::AdminQueueOperations>,
         dbs_dma_addr: u64,
@@ -550,6 +1084,30 @@ impl NvmeDevice {
             },
         )
     }
+
+    /// Submits an Abort admin command targeting command id `cid` on
+    /// submission queue `sqid`.
+    ///
+    /// Used by the blk-mq timeout handler (in `nvme_mq`, not part of this
+    /// tree) when a request's `io_timeout`/`admin_timeout` expires, before
+    /// escalating to [`Self::reset_controller`].
+    fn abort(
+        mq: &mq::RequestQueue<nvme_mq::AdminQueueOperations>,
+        sqid: u16,
+        cid: u16,
+    ) -> Result<u32> {
+        Self::submit_sync_command(
+            mq,
+            NvmeCommand {
+                abort: NvmeAbort {
+                    opcode: NvmeAdminOpcode::abort as _,
+                    sqid: sqid.into(),
+                    cid: cid.into(),
+                    ..NvmeAbort::default()
+                },
+            },
+        )
+    }
 }
 
 impl pci::Driver for NvmeDevice {
@@ -626,17 +1184,21 @@ impl pci::Driver for NvmeDevice {
                 // TODO: Use typed register access
                 db_stride: 1 << (((cap >> 32) & 0xf) + 2),
                 dev: device,
-                pci_dev: pci_device,
+                pci_dev <- new_spinlock!(pci_device),
                 instance: id,
-                shadow: None,
+                shadow <- new_spinlock!(None),
+                hmb <- new_spinlock!(None),
                 dma_pool: dma_pool,
                 queues <- new_spinlock!(
                     NvmeQueues {
                         admin: None,
+                        admin_mq: None,
                         io: Vec::new(),
+                        disks: Vec::new(),
                     }),
                 poll_queue_count: poll_queue_count,
                 irq_queue_count: irq_queue_count,
+                sgl_supported: AtomicBool::new(false),
             }),
             "Nvme::Data"
         )?
@@ -646,24 +1208,21 @@ impl pci::Driver for NvmeDevice {
         pr_info!("Setting up admin queue");
         let (admin_nvme_queue, admin_mq) = Self::configure_admin_queue(&data, dev)?;
         pr_info!("Created admin queue\n");
-        // TODO: Move this to a function. We should not fail `probe` if this fails.
-        // if false {
-        //     let dbs = dma::try_alloc_coherent::<u32>(dev, NVME_CTRL_PAGE_SIZE / 4, false)?;
-        //     let eis = dma::try_alloc_coherent::<u32>(dev, NVME_CTRL_PAGE_SIZE / 4, false)?;
-
-        //     for i in 0..NVME_CTRL_PAGE_SIZE / 4 {
-        //         dbs.write(i, &0);
-        //         eis.write(i, &0);
-        //     }
-
-        //     if Self::nvme_dbbuf_set(&admin_mq, dbs.dma_handle, eis.dma_handle).is_ok() {
-        //         // TODO: Fix this.
-        //         let x = unsafe { &mut *(&(**data) as *const NvmeData as *mut NvmeData) };
-        //         x.shadow = Some(NvmeShadow { dbs, eis });
-        //     } else {
-        //         return Err(kernel::error::code::EIO);
-        //     }
-        // }
+
+        // Shadow doorbell buffers are an optional fast path: if the
+        // controller doesn't support `dbbuf_set`, we simply keep ringing
+        // real MMIO doorbells, so a failure here must not fail `probe`.
+        let dbs = dma::try_alloc_coherent::<u32>(dev, NVME_CTRL_PAGE_SIZE / 4, false)?;
+        let eis = dma::try_alloc_coherent::<u32>(dev, NVME_CTRL_PAGE_SIZE / 4, false)?;
+
+        for i in 0..NVME_CTRL_PAGE_SIZE / 4 {
+            dbs.write(i, &0u32);
+            eis.write(i, &0u32);
+        }
+
+        if Self::dbbuf_set(&admin_mq, dbs.dma_handle, eis.dma_handle).is_ok() {
+            *data.shadow.lock() = Some(NvmeShadow { dbs, eis });
+        }
 
         if let Err(e) = Self::dev_add(cap, &data, dev, &admin_nvme_queue, &admin_mq) {
             pr_info!("Probe failed: {:?}\n", e);
@@ -674,8 +1233,84 @@ impl pci::Driver for NvmeDevice {
         Ok(data)
     }
 
-    fn remove(_data: &Self::Data) {
-        todo!()
+    fn remove(data: &Self::Data) {
+        pr_info!("Removing nvme device\n");
+
+        let mut queues = data.queues.lock();
+        let disks = core::mem::take(&mut queues.disks);
+        let io_queues = core::mem::take(&mut queues.io);
+        let admin_queue = queues.admin.take();
+        let admin_mq = queues.admin_mq.take();
+        drop(queues);
+
+        // Unregister the namespaces first so no new IO can be submitted
+        // while the queues below are quiesced and torn down.
+        drop(disks);
+
+        let hmb = data.hmb.lock().take();
+        if hmb.is_some() {
+            if let Some(admin_mq) = &admin_mq {
+                if let Err(e) = Self::set_host_mem_buf(admin_mq, false, 0, 0, 0) {
+                    pr_info!("Failed to disable host memory buffer: {:?}\n", e);
+                }
+            }
+        }
+        drop(hmb);
+
+        if let Some(admin_mq) = &admin_mq {
+            // A submission queue must be deleted before its completion
+            // queue, and queues are torn down in the reverse of the order
+            // `setup_io_queues` created them in.
+            for io_queue in io_queues.iter().rev() {
+                if let Err(e) = Self::delete_submission_queue(admin_mq, io_queue.as_ref()) {
+                    pr_info!("Failed to delete submission queue: {:?}\n", e);
+                }
+                if let Err(e) = Self::delete_completion_queue(admin_mq, io_queue.as_ref()) {
+                    pr_info!("Failed to delete completion queue: {:?}\n", e);
+                }
+            }
+        } else {
+            pr_info!("No admin queue available, skipping I/O queue teardown\n");
+        }
+
+        for io_queue in io_queues.iter() {
+            io_queue.unregister_irq();
+        }
+        drop(io_queues);
+        drop(admin_mq);
+
+        // Ask the controller to shut down cleanly, then wait for it to
+        // report completion, up to `nvme_shutdown_timeout` seconds.
+        {
+            let bar = &data.resources().unwrap().bar;
+            let cc =
+                (u32::from_le(bar.readl(OFFSET_CC)) & !NVME_CC_SHN_MASK) | NVME_CC_SHN_NORMAL;
+            bar.writel(cc, OFFSET_CC);
+
+            let shutdown_timeout_ms = (*nvme_shutdown_timeout.read() as u32) * 1000;
+            let mut waited_ms = 0;
+            while u32::from_le(bar.readl(OFFSET_CSTS)) & NVME_CSTS_SHST_MASK
+                != NVME_CSTS_SHST_CMPLT
+            {
+                if waited_ms >= shutdown_timeout_ms {
+                    pr_info!("Controller shutdown timed out\n");
+                    break;
+                }
+                unsafe { bindings::mdelay(100) };
+                waited_ms += 100;
+            }
+        }
+
+        if let Some(admin_queue) = &admin_queue {
+            admin_queue.unregister_irq();
+        }
+        drop(admin_queue);
+
+        data.pci_dev.lock().free_irq_vectors();
+
+        // The tag sets, `dma::Pool` and the rest of `NvmeData` are dropped
+        // along with the containing `Arc<DeviceData>` once this returns.
+        pr_info!("Nvme device removed\n");
     }
 }
 
@@ -715,5 +1350,30 @@ module! {
             permissions: 0,
             description: "Number of polled queues (-1 means num_cpu)",
         },
+        nvme_io_timeout: i64 {
+            default: 30,
+            permissions: 0,
+            description: "I/O queue command timeout, in seconds",
+        },
+        nvme_admin_timeout: i64 {
+            default: 60,
+            permissions: 0,
+            description: "Admin queue command timeout, in seconds",
+        },
+        nvme_max_retries: i64 {
+            default: 5,
+            permissions: 0,
+            description: "Maximum number of times to retry a timed out command before failing it with EIO",
+        },
+        nvme_shutdown_timeout: i64 {
+            default: 10,
+            permissions: 0,
+            description: "Time to wait for the controller to report a clean shutdown, in seconds",
+        },
+        nvme_max_host_mem_size_mb: i64 {
+            default: 0,
+            permissions: 0,
+            description: "Maximum Host Memory Buffer size to allocate for controllers that request one, in MiB (0 disables HMB)",
+        },
     },
 }
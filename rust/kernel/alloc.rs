@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Extensions to the alloc crate.
+
+use core::alloc::{AllocError, Layout};
+use core::ptr::NonNull;
+
+use crate::bindings;
+
+/// Typed allocation flags, wrapping the raw `gfp_t` value the C allocators expect.
+///
+/// Using this instead of a bare `gfp_t` keeps callers from accidentally passing an unrelated
+/// `u32` into an allocation call, while still lowering to the exact same value at the binding
+/// boundary. Values are built from the named constants in [`flags`] and combined with `|`, e.g.
+/// `flags::GFP_KERNEL | flags::__GFP_ZERO`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Flags(bindings::gfp_t);
+
+impl Flags {
+    /// Returns the raw `gfp_t` this wraps, for passing to a C API.
+    pub(crate) fn as_raw(self) -> bindings::gfp_t {
+        self.0
+    }
+}
+
+impl core::ops::BitOr for Flags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl core::ops::BitOrAssign for Flags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Named [`Flags`] values, mirroring the kernel's `GFP_*`/`__GFP_*` constants.
+pub mod flags {
+    use super::{bindings, Flags};
+
+    /// Allocate from a context that may sleep and may perform I/O to reclaim memory.
+    pub const GFP_KERNEL: Flags = Flags(bindings::GFP_KERNEL);
+    /// Allocate from a context that cannot sleep, e.g. interrupt context.
+    pub const GFP_ATOMIC: Flags = Flags(bindings::GFP_ATOMIC);
+    /// Like [`GFP_ATOMIC`], but fail rather than dip into the emergency memory reserves.
+    pub const GFP_NOWAIT: Flags = Flags(bindings::GFP_NOWAIT);
+    /// Zero the returned memory.
+    pub const __GFP_ZERO: Flags = Flags(bindings::__GFP_ZERO);
+    /// Do not print a warning when the allocation fails.
+    pub const __GFP_NOWARN: Flags = Flags(bindings::__GFP_NOWARN);
+}
+
+/// A kernel allocation backend, implemented by the zero-sized marker types [`Kmalloc`],
+/// [`Vmalloc`], and [`KVmalloc`].
+///
+/// [`crate::allocator::KernelAllocator`] (the `#[global_allocator]` backing ordinary
+/// `Box`/`Vec` usage) always goes through [`Kmalloc`]. Callers who need a large buffer that
+/// [`Kmalloc`] may fail to satisfy (because the slab allocator needs a physically contiguous
+/// run of pages) can name [`Vmalloc`] or [`KVmalloc`] explicitly instead.
+///
+/// # Safety
+///
+/// Implementers must ensure that `realloc` returns either an error, or a pointer to a live
+/// allocation of at least `layout.size()` bytes aligned to `layout.align()`, obtained in a way
+/// that `free` on the same type knows how to release.
+pub unsafe trait Allocator {
+    /// Reallocates `old` (or allocates, if `None`) to satisfy `layout`.
+    ///
+    /// # Safety
+    ///
+    /// `old`, if given, must have been returned by a previous call to `Self::realloc` and must
+    /// not have been passed to `Self::free` yet.
+    unsafe fn realloc(
+        old: Option<NonNull<[u8]>>,
+        layout: Layout,
+        flags: Flags,
+    ) -> Result<NonNull<[u8]>, AllocError>;
+
+    /// Frees a block previously returned by `realloc`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by a previous call to `Self::realloc` with a `layout` of
+    /// the same size and alignment, and must not be used again afterwards.
+    unsafe fn free(ptr: NonNull<u8>, layout: Layout);
+}
+
+/// Allocation backend, slab allocator (`krealloc`/`kfree`).
+///
+/// This is what [`crate::allocator::KernelAllocator`] uses for ordinary `Box`/`Vec`
+/// allocations. Like `kmalloc`, it needs a physically contiguous run of pages, so requests of
+/// more than a few pages frequently fail; [`Vmalloc`] or [`KVmalloc`] should be used instead for
+/// such sizes.
+pub struct Kmalloc;
+
+// SAFETY: `realloc`/`free` forward to `krealloc_aligned`/`kfree_aligned`, which satisfy the
+// safety requirements of `Allocator` by construction.
+unsafe impl Allocator for Kmalloc {
+    unsafe fn realloc(
+        old: Option<NonNull<[u8]>>,
+        layout: Layout,
+        flags: Flags,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        let ptr = old.map_or(core::ptr::null_mut(), |old| old.as_ptr().cast::<u8>());
+
+        // SAFETY: `ptr` is either null, or was returned by a previous call to
+        // `krealloc_aligned` with the same alignment, per the safety requirements of this
+        // function.
+        let mem = unsafe { crate::allocator::krealloc_aligned(ptr, layout, flags.as_raw()) };
+        let mem = NonNull::new(mem).ok_or(AllocError)?;
+
+        Ok(NonNull::slice_from_raw_parts(mem, layout.size()))
+    }
+
+    unsafe fn free(ptr: NonNull<u8>, layout: Layout) {
+        // SAFETY: `ptr` was returned by a previous call to `Self::realloc`, per the safety
+        // requirements of this function.
+        unsafe { crate::allocator::kfree_aligned(ptr.as_ptr(), layout) };
+    }
+}
+
+/// Allocation backend, `vmalloc`/`vfree`.
+///
+/// Suited to large buffers that do not need to be physically contiguous, since `vmalloc` only
+/// needs virtually contiguous address space, not a matching run of physical pages.
+pub struct Vmalloc;
+
+// SAFETY: `realloc`/`free` wrap `vmalloc`/`vfree`, which satisfy the safety requirements of
+// `Allocator` by construction.
+unsafe impl Allocator for Vmalloc {
+    unsafe fn realloc(
+        old: Option<NonNull<[u8]>>,
+        layout: Layout,
+        _flags: Flags,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // `vmalloc` has no realloc primitive of its own, so growing or shrinking means
+        // allocating fresh and copying over what fits.
+        //
+        // SAFETY: FFI call with no special requirements.
+        let mem = unsafe { bindings::vmalloc(layout.size()) }.cast::<u8>();
+        let mem = NonNull::new(mem).ok_or(AllocError)?;
+
+        if let Some(old) = old {
+            let len = core::cmp::min(old.len(), layout.size());
+
+            // SAFETY: `old` is a live `vmalloc` allocation of at least `len` bytes, per the
+            // safety requirements of this function; `mem` was just allocated with at least
+            // `len` bytes and cannot overlap it.
+            unsafe {
+                core::ptr::copy_nonoverlapping(old.as_ptr().cast::<u8>(), mem.as_ptr(), len);
+            }
+
+            // SAFETY: `old` was returned by a previous call to this same backend's `realloc`.
+            unsafe { Self::free(old.cast(), layout) };
+        }
+
+        Ok(NonNull::slice_from_raw_parts(mem, layout.size()))
+    }
+
+    unsafe fn free(ptr: NonNull<u8>, _layout: Layout) {
+        // SAFETY: `ptr` was returned by a previous call to `Self::realloc`, per the safety
+        // requirements of this function.
+        unsafe { bindings::vfree(ptr.as_ptr().cast()) };
+    }
+}
+
+/// Allocation backend, `kvmalloc`/`kvfree`.
+///
+/// Tries the slab allocator first and transparently falls back to a `vmalloc` allocation when
+/// the requested size cannot be satisfied as a contiguous physical allocation. The right default
+/// for buffers whose size is only known at runtime and may or may not turn out large.
+pub struct KVmalloc;
+
+// SAFETY: `realloc`/`free` wrap `kvmalloc`/`kvfree`, which satisfy the safety requirements of
+// `Allocator` by construction.
+unsafe impl Allocator for KVmalloc {
+    unsafe fn realloc(
+        old: Option<NonNull<[u8]>>,
+        layout: Layout,
+        flags: Flags,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // Like `Vmalloc`, `kvmalloc` has no realloc primitive of its own.
+        //
+        // SAFETY: FFI call with no special requirements.
+        let mem = unsafe { bindings::kvmalloc(layout.size(), flags.as_raw()) }.cast::<u8>();
+        let mem = NonNull::new(mem).ok_or(AllocError)?;
+
+        if let Some(old) = old {
+            let len = core::cmp::min(old.len(), layout.size());
+
+            // SAFETY: `old` is a live `kvmalloc` allocation of at least `len` bytes, per the
+            // safety requirements of this function; `mem` was just allocated with at least
+            // `len` bytes and cannot overlap it.
+            unsafe {
+                core::ptr::copy_nonoverlapping(old.as_ptr().cast::<u8>(), mem.as_ptr(), len);
+            }
+
+            // SAFETY: `old` was returned by a previous call to this same backend's `realloc`.
+            unsafe { Self::free(old.cast(), layout) };
+        }
+
+        Ok(NonNull::slice_from_raw_parts(mem, layout.size()))
+    }
+
+    unsafe fn free(ptr: NonNull<u8>, _layout: Layout) {
+        // SAFETY: `ptr` was returned by a previous call to `Self::realloc`, per the safety
+        // requirements of this function.
+        unsafe { bindings::kvfree(ptr.as_ptr().cast()) };
+    }
+}
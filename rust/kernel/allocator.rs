@@ -4,28 +4,157 @@
 
 use core::alloc::AllocError;
 use core::alloc::{GlobalAlloc, Layout};
+use core::mem::size_of;
 use core::ptr;
 use core::ptr::NonNull;
 
+use crate::alloc::flags::GFP_KERNEL;
+use crate::alloc::{flags, Allocator, Flags, Kmalloc};
 use crate::bindings;
 
 pub(crate) struct KernelAllocator;
 
+/// The largest alignment `krealloc()` is guaranteed to honor on its own.
+///
+/// `kmalloc()`/`krealloc()` align allocations to this value (or to the requested size itself,
+/// for power-of-two sizes smaller than it). Layouts that ask for more than this have to be
+/// handled by [`krealloc_aligned`] instead.
+const KMALLOC_ALIGNMENT: usize = bindings::ARCH_KMALLOC_MINALIGN as usize;
+
+/// Reallocates `ptr` (or allocates, if `ptr` is null) to satisfy `layout`, honoring
+/// `layout.align()` even when it exceeds [`KMALLOC_ALIGNMENT`].
+///
+/// For `layout.align() <= KMALLOC_ALIGNMENT`, this is a direct, zero-overhead `krealloc()` call.
+/// Otherwise, it over-allocates by `size_of::<*mut u8>() + layout.align()` bytes, rounds the
+/// returned pointer up to `layout.align()`, and stashes the real, `krealloc()`-returned pointer
+/// in the `size_of::<*mut u8>()` bytes immediately before the one it hands back, so
+/// [`kfree_aligned`] can recover it later.
+///
+/// Because the rounded-up offset depends on the base pointer's own address, it can differ
+/// between successive calls for the same logical allocation (the next `krealloc()` may hand
+/// back a base at a different offset into its alignment). So on the over-aligned path, growing
+/// or shrinking an existing allocation always allocates a fresh block and copies the payload
+/// across manually instead of reallocating the old base in place: relying on `krealloc()`'s own
+/// block-relative copy would preserve bytes at the old offset while this function returns a
+/// pointer at a freshly (and possibly different) computed offset, silently corrupting the
+/// caller's data.
+///
+/// # Safety
+///
+/// `ptr` must be null, or have been returned by a previous call to [`krealloc_aligned`] with a
+/// `layout` of the same alignment, and not yet passed to [`kfree_aligned`].
+pub(crate) unsafe fn krealloc_aligned(ptr: *mut u8, layout: Layout, flags: bindings::gfp_t) -> *mut u8 {
+    if layout.align() <= KMALLOC_ALIGNMENT {
+        // `krealloc()` is used instead of `kmalloc()` because the latter is
+        // an inline function and cannot be bound to as a result.
+        //
+        // SAFETY: `ptr` is either null, or was returned by a `krealloc()` call on this same
+        // fast path, per the safety requirements of this function.
+        return unsafe { bindings::krealloc(ptr.cast(), layout.size(), flags) }.cast();
+    }
+
+    let Some(size) = layout
+        .size()
+        .checked_add(layout.align())
+        .and_then(|size| size.checked_add(size_of::<*mut u8>()))
+    else {
+        return ptr::null_mut();
+    };
+
+    // Always allocate a fresh block on the over-aligned path; see this function's doc comment
+    // for why reallocating the old base in place would silently corrupt the payload.
+    //
+    // SAFETY: `size` is nonzero (it is at least `size_of::<*mut u8>()`) and `flags` is a valid
+    // `gfp_t`.
+    let new_base = unsafe { bindings::krealloc(ptr::null_mut(), size, flags) }.cast::<u8>();
+    if new_base.is_null() {
+        return ptr::null_mut();
+    }
+
+    // Round up to `layout.align()`, leaving room before the result for the base pointer we
+    // stash there below.
+    let new_aligned =
+        (new_base as usize + size_of::<*mut u8>() + layout.align() - 1) & !(layout.align() - 1);
+    let new_aligned = new_aligned as *mut u8;
+
+    if !ptr.is_null() {
+        // SAFETY: By the safety requirements of this function, `ptr` was returned by a previous
+        // over-aligned call to this function, in which case the `size_of::<*mut u8>()` bytes
+        // immediately before it hold the real, `krealloc()`-returned base pointer that this
+        // over-aligned pointer was carved out of.
+        let old_base = unsafe { ptr.cast::<*mut u8>().sub(1).read() };
+
+        // SAFETY: `old_base` was returned by a previous call to `krealloc()`.
+        let old_usable = unsafe { bindings::ksize(old_base.cast()) };
+        let old_offset = ptr as usize - old_base as usize;
+        let copy_len = core::cmp::min(old_usable.saturating_sub(old_offset), layout.size());
+
+        // SAFETY: `ptr` is valid for reads of `copy_len` bytes, carved out of the
+        // `old_usable`-byte `old_base` allocation; `new_aligned` is valid for writes of
+        // `copy_len` bytes, carved out of the freshly allocated `size`-byte `new_base`
+        // allocation; the two allocations cannot overlap.
+        unsafe { ptr::copy_nonoverlapping(ptr, new_aligned, copy_len) };
+
+        // SAFETY: `old_base` was returned by a previous call to `krealloc()` and is not
+        // accessed again after this.
+        unsafe { bindings::kfree(old_base.cast()) };
+    }
+
+    // SAFETY: `new_aligned` lies within the `size`-byte allocation above, with at least
+    // `size_of::<*mut u8>()` bytes free immediately before it for this write.
+    unsafe { new_aligned.cast::<*mut u8>().sub(1).write(new_base) };
+
+    new_aligned
+}
+
+/// Frees a pointer previously returned by [`krealloc_aligned`] for the same `layout`.
+///
+/// # Safety
+///
+/// `ptr` must be null, or have been returned by a previous call to [`krealloc_aligned`] with a
+/// `layout` of the same alignment.
+pub(crate) unsafe fn kfree_aligned(ptr: *mut u8, layout: Layout) {
+    if layout.align() <= KMALLOC_ALIGNMENT {
+        // SAFETY: `ptr` is either null, or was returned by a `krealloc()` call on this same
+        // fast path, per the safety requirements of this function.
+        unsafe { bindings::kfree(ptr.cast()) };
+        return;
+    }
+
+    if ptr.is_null() {
+        return;
+    }
+
+    // SAFETY: `ptr` was returned by a previous over-aligned call to `krealloc_aligned`, so the
+    // `size_of::<*mut u8>()` bytes immediately before it hold the real base pointer.
+    let base = unsafe { ptr.cast::<*mut u8>().sub(1).read() };
+
+    // SAFETY: `base` was returned by a previous call to `krealloc()`.
+    unsafe { bindings::kfree(base.cast()) };
+}
+
 impl KernelAllocator {
     #[cfg(not(test))]
     #[cfg(not(testlib))]
     pub(crate) fn allocate_with_flags(
         &self,
         layout: Layout,
-        flags: bindings::gfp_t,
+        flags: Flags,
     ) -> Result<NonNull<[u8]>, AllocError> {
-        // `krealloc()` is used instead of `kmalloc()` because the latter is
-        // an inline function and cannot be bound to as a result.
-        let mem = unsafe { bindings::krealloc(ptr::null(), layout.size(), flags) as *mut u8 };
-        if mem.is_null() {
-            return Err(AllocError);
-        }
-        let mem = unsafe { core::slice::from_raw_parts_mut(mem, bindings::ksize(mem as _)) };
+        // SAFETY: `None` is always a valid `old` to pass to `Kmalloc::realloc`.
+        let mem = unsafe { Kmalloc::realloc(None, layout, flags) }?;
+
+        // `ksize()` reports the true usable size of the block `krealloc()` returned, which is
+        // only the block we hand back to the caller on the fast path; on the over-aligned path
+        // our pointer is offset into a larger block, so `ksize()` would report the wrong slack.
+        let size = if layout.align() <= KMALLOC_ALIGNMENT {
+            // SAFETY: `mem` came from a successful `Kmalloc::realloc`, i.e. `krealloc()`.
+            unsafe { bindings::ksize(mem.as_ptr().cast()) }
+        } else {
+            layout.size()
+        };
+
+        let mem = unsafe { core::slice::from_raw_parts_mut(mem.as_ptr().cast::<u8>(), size) };
         // Safety: checked for non null above
         Ok(unsafe { NonNull::new_unchecked(mem) })
     }
@@ -35,7 +164,7 @@ impl KernelAllocator {
     pub(crate) fn allocate_with_flags(
         &self,
         layout: Layout,
-        _flags: bindings::gfp_t,
+        _flags: Flags,
     ) -> Result<NonNull<[u8]>, AllocError> {
         self.allocate(layout)
     }
@@ -43,15 +172,14 @@ impl KernelAllocator {
 
 unsafe impl GlobalAlloc for KernelAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        // `krealloc()` is used instead of `kmalloc()` because the latter is
-        // an inline function and cannot be bound to as a result.
-        unsafe { bindings::krealloc(ptr::null(), layout.size(), bindings::GFP_KERNEL) as *mut u8 }
+        // SAFETY: `None` is always a valid `old` to pass to `Kmalloc::realloc`.
+        unsafe { Kmalloc::realloc(None, layout, GFP_KERNEL) }
+            .map_or(ptr::null_mut(), |mem| mem.as_ptr().cast())
     }
 
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
-        unsafe {
-            bindings::kfree(ptr as *const core::ffi::c_void);
-        }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: `ptr` was returned by a matching call to `alloc`/`realloc` above.
+        unsafe { Kmalloc::free(NonNull::new_unchecked(ptr), layout) };
     }
 }
 
@@ -64,33 +192,46 @@ pub(crate) static ALLOCATOR: KernelAllocator = KernelAllocator;
 //
 // Note that `#[no_mangle]` implies exported too, nowadays.
 #[no_mangle]
-fn __rust_alloc(size: usize, _align: usize) -> *mut u8 {
-    unsafe { bindings::krealloc(core::ptr::null(), size, bindings::GFP_KERNEL) as *mut u8 }
+fn __rust_alloc(size: usize, align: usize) -> *mut u8 {
+    // SAFETY: `size`/`align` come from the `GlobalAlloc` shim contract, which guarantees they
+    // describe a valid `Layout`.
+    let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
+
+    // SAFETY: `None` is always a valid `old` to pass to `Kmalloc::realloc`.
+    unsafe { Kmalloc::realloc(None, layout, GFP_KERNEL) }
+        .map_or(ptr::null_mut(), |mem| mem.as_ptr().cast())
 }
 
 #[no_mangle]
-fn __rust_dealloc(ptr: *mut u8, _size: usize, _align: usize) {
-    unsafe { bindings::kfree(ptr as *const core::ffi::c_void) };
+fn __rust_dealloc(ptr: *mut u8, size: usize, align: usize) {
+    // SAFETY: see `__rust_alloc`.
+    let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
+
+    // SAFETY: `ptr` was returned by a matching `__rust_alloc`/`__rust_realloc` above.
+    unsafe { Kmalloc::free(NonNull::new_unchecked(ptr), layout) };
 }
 
 #[no_mangle]
-fn __rust_realloc(ptr: *mut u8, _old_size: usize, _align: usize, new_size: usize) -> *mut u8 {
-    unsafe {
-        bindings::krealloc(
-            ptr as *const core::ffi::c_void,
-            new_size,
-            bindings::GFP_KERNEL,
-        ) as *mut u8
-    }
+fn __rust_realloc(ptr: *mut u8, old_size: usize, align: usize, new_size: usize) -> *mut u8 {
+    // SAFETY: see `__rust_alloc`.
+    let layout = unsafe { Layout::from_size_align_unchecked(new_size, align) };
+    // SAFETY: `old_size`/`align` describe the `Layout` `ptr` was allocated with, per the
+    // `GlobalAlloc` shim contract.
+    let old_layout = unsafe { Layout::from_size_align_unchecked(old_size, align) };
+    let old = NonNull::new(ptr)
+        .map(|ptr| NonNull::slice_from_raw_parts(ptr, old_layout.size()));
+
+    // SAFETY: `old`, if any, was returned by a matching `__rust_alloc`/`__rust_realloc` above.
+    unsafe { Kmalloc::realloc(old, layout, GFP_KERNEL) }
+        .map_or(ptr::null_mut(), |mem| mem.as_ptr().cast())
 }
 
 #[no_mangle]
-fn __rust_alloc_zeroed(size: usize, _align: usize) -> *mut u8 {
-    unsafe {
-        bindings::krealloc(
-            core::ptr::null(),
-            size,
-            bindings::GFP_KERNEL | bindings::__GFP_ZERO,
-        ) as *mut u8
-    }
+fn __rust_alloc_zeroed(size: usize, align: usize) -> *mut u8 {
+    // SAFETY: see `__rust_alloc`.
+    let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
+
+    // SAFETY: `None` is always a valid `old` to pass to `Kmalloc::realloc`.
+    unsafe { Kmalloc::realloc(None, layout, GFP_KERNEL | flags::__GFP_ZERO) }
+        .map_or(ptr::null_mut(), |mem| mem.as_ptr().cast())
 }
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Types for working with the block layer.
+
+pub mod bio;
+pub mod mq;
+pub mod operations;
+
+use crate::{
+    bindings,
+    error::code::{EIO, ENOMEM},
+    error::Result,
+    folio::{Folio, UniqueFolio},
+    types::ScopeGuard,
+};
+use core::ptr::{self, NonNull};
+
+/// Synchronously reads `2^order` pages worth of data, starting at byte offset `pos` on `bdev`,
+/// into a freshly allocated folio.
+///
+/// This bridges the [`folio`](crate::folio) module with the block layer so file systems can
+/// fetch superblocks and other metadata without mounting a full address space. I/O errors are
+/// surfaced as `EIO`.
+///
+/// # Safety
+///
+/// `bdev` must be a valid, opened `struct block_device` for the duration of this call.
+pub unsafe fn read_folio_sync(
+    bdev: *mut bindings::block_device,
+    pos: i64,
+    order: u32,
+) -> Result<UniqueFolio> {
+    let folio = Folio::try_new(order)?;
+
+    // SAFETY: `bdev` is valid by the safety requirements of this function. We request room for a
+    // single bio_vec, which is all `bio_add_folio` below needs to cover the whole folio.
+    let bio = unsafe {
+        bindings::bio_alloc_bioset(
+            bdev,
+            1,
+            bindings::req_op_REQ_OP_READ,
+            bindings::GFP_KERNEL,
+            ptr::null_mut(),
+        )
+    };
+    let bio = NonNull::new(bio).ok_or(ENOMEM)?;
+
+    // SAFETY: `bio` was just allocated above by us, and is only ever freed once, by this guard.
+    let _guard = ScopeGuard::new(|| unsafe { bindings::bio_put(bio.as_ptr()) });
+
+    // SAFETY: `bio` was just allocated and has not been submitted yet.
+    unsafe { (*bio.as_ptr()).bi_iter.bi_sector = (pos as u64) >> 9 };
+
+    // SAFETY: `folio` is valid for the duration of this call, and `bio` was allocated with room
+    // for at least one bio_vec.
+    let added =
+        unsafe { bindings::bio_add_folio(bio.as_ptr(), folio.0 .0.get(), folio.0.size(), 0) };
+    if !added {
+        return Err(EIO);
+    }
+
+    // SAFETY: `bio` is fully initialized and holds a reference to `folio`'s pages for the
+    // duration of the read.
+    let status = unsafe { bindings::submit_bio_wait(bio.as_ptr()) };
+    if status != 0 {
+        return Err(EIO);
+    }
+
+    folio.0.flush_dcache();
+    // SAFETY: `submit_bio_wait` returned success above, so `folio`'s contents are now valid.
+    unsafe { bindings::folio_mark_uptodate(folio.0 .0.get()) };
+
+    Ok(folio)
+}
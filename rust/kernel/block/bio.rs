@@ -4,13 +4,22 @@
 //!
 //! C header: [`include/linux/blk_types.h`](../../include/linux/blk_types.h)
 
+use crate::error::code::EINVAL;
+use crate::error::Result;
 use core::fmt;
 use core::ptr::NonNull;
 
+mod integrity;
 mod vec;
 
+pub use integrity::BioIntegrityIterator;
+pub use integrity::IntegritySegment;
+pub use vec::BioMultiPageSegmentIterator;
 pub use vec::BioSegmentIterator;
+pub use vec::MappedSegment;
+pub use vec::MultiPageSegment;
 pub use vec::Segment;
+pub(crate) use vec::{bvec_iter_len, bvec_iter_offset, bvec_iter_page};
 
 /// A wrapper around a `struct bio` pointer
 ///
@@ -30,9 +39,53 @@ impl<'a> Bio<'a> {
         BioSegmentIterator::new(self)
     }
 
+    /// Returns an iterator over the integrity (T10 PI / DIX) segments
+    /// attached to this `Bio`. Yields nothing if the bio has no integrity
+    /// payload.
+    #[inline(always)]
+    pub fn integrity_iter(&'a self) -> BioIntegrityIterator<'a> {
+        BioIntegrityIterator::new(self)
+    }
+
+    /// Returns an iterator over segments in this `Bio`, without splitting
+    /// segments that span more than one physical page. Does not consider
+    /// segments of other bios in this bio chain.
+    #[inline(always)]
+    pub fn multipage_segment_iter(&'a self) -> BioMultiPageSegmentIterator<'a> {
+        BioMultiPageSegmentIterator::new(self)
+    }
+
+    /// Copies the entirety of this bio's data into `dst`, handling
+    /// cross-page segments and per-segment mapping internally.
+    ///
+    /// Fails with `EINVAL` if `dst` is smaller than the bio's total size.
+    pub fn copy_to_slice(&'a self, dst: &mut [u8]) -> Result {
+        let mut done = 0;
+        for segment in self.multipage_segment_iter() {
+            let end = done + segment.len();
+            segment.copy_to_slice(dst.get_mut(done..end).ok_or(EINVAL)?)?;
+            done = end;
+        }
+        Ok(())
+    }
+
+    /// Copies `src` into the entirety of this bio's data, handling
+    /// cross-page segments and per-segment mapping internally.
+    ///
+    /// Fails with `EINVAL` if `src` is smaller than the bio's total size.
+    pub fn copy_from_slice(&'a self, src: &[u8]) -> Result {
+        let mut done = 0;
+        for mut segment in self.multipage_segment_iter() {
+            let end = done + segment.len();
+            segment.copy_from_slice(src.get(done..end).ok_or(EINVAL)?)?;
+            done = end;
+        }
+        Ok(())
+    }
+
     /// Get a pointer to the `bio_vec` array off this bio
     #[inline(always)]
-    fn io_vec(&self) -> *const bindings::bio_vec {
+    pub(crate) fn io_vec(&self) -> *const bindings::bio_vec {
         // SAFETY: By type invariant, get_raw() returns a valid pointer to a
         // valid `struct bio`
         unsafe { (*self.get_raw()).bi_io_vec }
@@ -56,7 +109,7 @@ impl<'a> Bio<'a> {
 
     /// Return the raw pointer of the wrapped `struct bio`
     #[inline(always)]
-    fn get_raw(&self) -> *const bindings::bio {
+    pub(crate) fn get_raw(&self) -> *const bindings::bio {
         self.0.as_ptr()
     }
 
@@ -0,0 +1,150 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Types for working with per-bio integrity metadata (T10 PI / DIX).
+//!
+//! C header: [`include/linux/bio-integrity.h`](../../include/linux/bio-integrity.h)
+
+use super::Bio;
+use crate::error::Result;
+use crate::pages::Pages;
+use core::fmt;
+use core::mem::ManuallyDrop;
+
+/// A wrapper around a `struct bio_vec` taken from a bio's integrity payload
+/// (`bio->bi_integrity->bip_vec`), holding one segment of protection
+/// information tuples rather than data.
+///
+/// # Invariants
+///
+/// `bio_vec` must always be initialized and valid.
+pub struct IntegritySegment<'a> {
+    bio_vec: bindings::bio_vec,
+    _marker: core::marker::PhantomData<&'a ()>,
+}
+
+impl IntegritySegment<'_> {
+    /// Get the length of the segment in bytes.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.bio_vec.bv_len as usize
+    }
+
+    /// Returns true if the length of the segment is 0.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the offset field of the `bio_vec`.
+    #[inline(always)]
+    pub fn offset(&self) -> usize {
+        self.bio_vec.bv_offset as usize
+    }
+
+    /// Copy the integrity metadata of this segment into `page`.
+    #[inline(always)]
+    pub fn copy_to_page_atomic(&self, page: &mut Pages<0>) -> Result {
+        // SAFETY: self.bio_vec is valid and thus bv_page must be a valid
+        // pointer to a `struct page`. We do not own the page, but we prevent
+        // drop by wrapping the `Pages` in `ManuallyDrop`.
+        let our_page = ManuallyDrop::new(unsafe { Pages::<0>::from_raw(self.bio_vec.bv_page) });
+        let our_map = our_page.kmap_atomic();
+
+        let ptr = unsafe { (our_map.get_ptr() as *const u8).add(self.offset()) };
+
+        unsafe { page.write_atomic(ptr, self.offset(), self.len()) }
+    }
+
+    /// Copy integrity metadata from `page` into this segment.
+    #[inline(always)]
+    pub fn copy_from_page_atomic(&mut self, page: &Pages<0>) -> Result {
+        // SAFETY: self.bio_vec is valid and thus bv_page must be a valid
+        // pointer to a `struct page`. We do not own the page, but we prevent
+        // drop by wrapping the `Pages` in `ManuallyDrop`.
+        let our_page = ManuallyDrop::new(unsafe { Pages::<0>::from_raw(self.bio_vec.bv_page) });
+        let our_map = our_page.kmap_atomic();
+
+        let ptr = unsafe { (our_map.get_ptr() as *mut u8).add(self.offset()) };
+
+        unsafe { page.read_atomic(ptr, self.offset(), self.len()) }
+    }
+}
+
+impl core::fmt::Display for IntegritySegment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "IntegritySegment {:?} len: {}",
+            self.bio_vec.bv_page, self.bio_vec.bv_len
+        )
+    }
+}
+
+/// An iterator over the [`IntegritySegment`]s of a [`Bio`]'s integrity
+/// payload.
+///
+/// Walks `bio->bi_integrity->bip_vec` with the bio's integrity iterator,
+/// yielding whole `bio_vec` entries; unlike [`super::BioSegmentIterator`],
+/// it does not split them at page boundaries. Yields nothing for a bio with
+/// no integrity payload attached.
+pub struct BioIntegrityIterator<'a> {
+    bio: &'a Bio<'a>,
+    bip_vec: *const bindings::bio_vec,
+    iter: bindings::bvec_iter,
+}
+
+impl<'a> BioIntegrityIterator<'a> {
+    #[inline(always)]
+    pub(crate) fn new(bio: &'a Bio<'a>) -> Self {
+        // SAFETY: `bio.get_raw()` is a valid pointer to a valid `struct bio`.
+        let bip = unsafe { (*bio.get_raw()).bi_integrity };
+
+        // SAFETY: `bip`, if non-null, is a valid `struct
+        // bio_integrity_payload` for the lifetime of `bio`.
+        let (bip_vec, iter) = match unsafe { bip.as_ref() } {
+            Some(bip) => (bip.bip_vec.as_ptr(), bip.bip_iter),
+            // SAFETY: a zeroed `bvec_iter` has `bi_size == 0`, which `next()`
+            // treats as exhausted without ever reading `bip_vec`.
+            None => (core::ptr::null(), unsafe { core::mem::zeroed() }),
+        };
+
+        Self { bio, bip_vec, iter }
+    }
+}
+
+impl<'a> core::iter::Iterator for BioIntegrityIterator<'a> {
+    type Item = IntegritySegment<'a>;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.iter.bi_size == 0 {
+            return None;
+        }
+
+        // SAFETY: `self.bip_vec` is a valid array covering `self.iter` as
+        // long as `self.iter.bi_size != 0`, which was just checked above.
+        let cur = unsafe { *self.bip_vec.add(self.iter.bi_idx as usize) };
+
+        let offset = cur.bv_offset + self.iter.bi_bvec_done;
+        let len = self.iter.bi_size.min(cur.bv_len - self.iter.bi_bvec_done);
+
+        // SAFETY: `self.bio` is the bio `self.iter` was derived from, and
+        // `self.iter` has not been exhausted, as checked above.
+        unsafe {
+            bindings::bio_advance_iter_single(
+                self.bio.get_raw(),
+                &mut self.iter as *mut bindings::bvec_iter,
+                len,
+            )
+        };
+
+        Some(IntegritySegment {
+            bio_vec: bindings::bio_vec {
+                bv_page: cur.bv_page,
+                bv_len: len,
+                bv_offset: offset,
+            },
+            _marker: core::marker::PhantomData,
+        })
+    }
+}
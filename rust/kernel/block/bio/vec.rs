@@ -5,6 +5,7 @@
 //! C header: [`include/linux/bvec.h`](../../include/linux/bvec.h)
 
 use super::Bio;
+use crate::error::code::*;
 use crate::error::Result;
 use crate::pages::Pages;
 use core::fmt;
@@ -43,7 +44,7 @@ fn bvec_iter_bvec(
 }
 
 #[inline(always)]
-fn bvec_iter_page(
+pub(crate) fn bvec_iter_page(
     bvec: *const bindings::bio_vec,
     iter: &bindings::bvec_iter,
 ) -> *mut bindings::page {
@@ -51,12 +52,12 @@ fn bvec_iter_page(
 }
 
 #[inline(always)]
-fn bvec_iter_len(bvec: *const bindings::bio_vec, iter: &bindings::bvec_iter) -> u32 {
+pub(crate) fn bvec_iter_len(bvec: *const bindings::bio_vec, iter: &bindings::bvec_iter) -> u32 {
     mp_bvec_iter_len(bvec, iter).min(crate::PAGE_SIZE - bvec_iter_offset(bvec, iter))
 }
 
 #[inline(always)]
-fn bvec_iter_offset(bvec: *const bindings::bio_vec, iter: &bindings::bvec_iter) -> u32 {
+pub(crate) fn bvec_iter_offset(bvec: *const bindings::bio_vec, iter: &bindings::bvec_iter) -> u32 {
     mp_bvec_iter_offset(bvec, iter) % crate::PAGE_SIZE
 }
 
@@ -92,13 +93,16 @@ impl Segment<'_> {
     /// Copy data of this segment into `page`.
     #[inline(always)]
     pub fn copy_to_page_atomic(&self, page: &mut Pages<0>) -> Result {
+        // INVARIANT: `bvec_iter_offset`/`bvec_iter_len` clamp every `Segment`
+        // to a single page, so this must always hold.
+        debug_assert!(self.offset() + self.len() <= crate::PAGE_SIZE as usize);
+
         // SAFETY: self.bio_vec is valid and thus bv_page must be a valid
         // pointer to a `struct page`. We do not own the page, but we prevent
         // drop by wrapping the `Pages` in `ManuallyDrop`.
         let our_page = ManuallyDrop::new(unsafe { Pages::<0>::from_raw(self.bio_vec.bv_page) });
         let our_map = our_page.kmap_atomic();
 
-        // TODO: Checck offset is within page - what guarantees does `bio_vec` provide?
         let ptr = unsafe { (our_map.get_ptr() as *const u8).add(self.offset()) };
 
         unsafe { page.write_atomic(ptr, self.offset(), self.len()) }
@@ -107,17 +111,120 @@ impl Segment<'_> {
     /// Copy data from `page` into this segment
     #[inline(always)]
     pub fn copy_from_page_atomic(&mut self, page: &Pages<0>) -> Result {
+        // INVARIANT: `bvec_iter_offset`/`bvec_iter_len` clamp every `Segment`
+        // to a single page, so this must always hold.
+        debug_assert!(self.offset() + self.len() <= crate::PAGE_SIZE as usize);
+
         // SAFETY: self.bio_vec is valid and thus bv_page must be a valid
         // pointer to a `struct page`. We do not own the page, but we prevent
         // drop by wrapping the `Pages` in `ManuallyDrop`.
         let our_page = ManuallyDrop::new(unsafe { Pages::<0>::from_raw(self.bio_vec.bv_page) });
         let our_map = our_page.kmap_atomic();
 
-        // TODO: Checck offset is within page
         let ptr = unsafe { (our_map.get_ptr() as *mut u8).add(self.offset()) };
 
         unsafe { page.read_atomic(ptr, self.offset(), self.len()) }
     }
+
+}
+
+impl<'a> Segment<'a> {
+    /// Maps this segment into the kernel's virtual address space, returning
+    /// a byte slice over its data. Unlike [`Self::copy_to_page_atomic`] and
+    /// [`Self::copy_from_page_atomic`], this does not copy: reads and writes
+    /// through the returned [`MappedSegment`] go directly to the underlying
+    /// page, and may sleep.
+    ///
+    /// The returned [`MappedSegment`] is tied to `'a`, the lifetime of the
+    /// underlying page, rather than to this (possibly short-lived) `Segment`
+    /// value.
+    #[inline(always)]
+    pub fn map_local(&self) -> MappedSegment<'a> {
+        // SAFETY: self.bio_vec is valid and thus bv_page must be a valid
+        // pointer to a `struct page`, live for `'a`.
+        let base = unsafe { bindings::kmap_local_page(self.bio_vec.bv_page) };
+        MappedSegment {
+            base,
+            offset: self.offset(),
+            len: self.len(),
+            _marker: core::marker::PhantomData,
+        }
+    }
+
+    /// Copy the data of this segment into `dst`, which must be at least [`Self::len`] bytes
+    /// long. Unlike [`Self::copy_to_page_atomic`], this maps the page with [`Self::map_local`]
+    /// and so may sleep.
+    pub fn read_into(&self, dst: &mut [u8]) -> Result {
+        if dst.len() < self.len() {
+            return Err(EINVAL);
+        }
+
+        dst[..self.len()].copy_from_slice(&self.map_local());
+        Ok(())
+    }
+
+    /// Copy `src` into this segment's data. `src` must be at least [`Self::len`] bytes long.
+    /// Unlike [`Self::copy_from_page_atomic`], this maps the page with [`Self::map_local`] and
+    /// so may sleep.
+    pub fn write_from(&mut self, src: &[u8]) -> Result {
+        if src.len() < self.len() {
+            return Err(EINVAL);
+        }
+
+        self.map_local().copy_from_slice(&src[..self.len()]);
+        Ok(())
+    }
+
+    /// Construct a `Segment` from a raw `bio_vec`.
+    ///
+    /// # Safety
+    ///
+    /// `bio_vec.bv_page` must be a valid page, live for at least `'a`, and
+    /// `bio_vec.bv_offset`/`bio_vec.bv_len` must describe a range within
+    /// that page.
+    #[inline(always)]
+    pub(crate) unsafe fn from_raw(bio_vec: bindings::bio_vec) -> Self {
+        Self {
+            bio_vec,
+            _marker: core::marker::PhantomData,
+        }
+    }
+}
+
+/// A [`Segment`] mapped into the kernel's virtual address space.
+///
+/// The mapping is released when this value is dropped.
+pub struct MappedSegment<'a> {
+    base: *mut core::ffi::c_void,
+    offset: usize,
+    len: usize,
+    _marker: core::marker::PhantomData<&'a ()>,
+}
+
+impl core::ops::Deref for MappedSegment<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `self.base` is a valid mapping of at least `PAGE_SIZE`
+        // bytes, and `self.offset + self.len <= PAGE_SIZE` by the type
+        // invariant of the `Segment` this was mapped from.
+        unsafe { core::slice::from_raw_parts((self.base as *const u8).add(self.offset), self.len) }
+    }
+}
+
+impl core::ops::DerefMut for MappedSegment<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: As above, and we hold the only reference to this mapping.
+        unsafe { core::slice::from_raw_parts_mut((self.base as *mut u8).add(self.offset), self.len) }
+    }
+}
+
+impl Drop for MappedSegment<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `self.base` was returned by a matching `kmap_local_page`
+        // in `Segment::map_local` and has not been unmapped yet.
+        unsafe { bindings::kunmap_local(self.base) };
+    }
 }
 
 impl core::fmt::Display for Segment<'_> {
@@ -179,3 +286,162 @@ impl<'a> core::iter::Iterator for BioSegmentIterator<'a> {
         })
     }
 }
+
+/// A wrapper around a `struct bio_vec`, like [`Segment`], but not clamped to
+/// a single page: a `MultiPageSegment` may span several contiguous physical
+/// pages.
+///
+/// # Invariants
+///
+/// `bio_vec` must always be initialized and valid.
+pub struct MultiPageSegment<'a> {
+    bio_vec: bindings::bio_vec,
+    _marker: core::marker::PhantomData<&'a ()>,
+}
+
+impl MultiPageSegment<'_> {
+    /// Get the length of the segment in bytes.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.bio_vec.bv_len as usize
+    }
+
+    /// Returns true if the length of the segment is 0.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the offset field of the `bio_vec`.
+    #[inline(always)]
+    pub fn offset(&self) -> usize {
+        self.bio_vec.bv_offset as usize
+    }
+
+    /// Copy the data of this segment into `dst`, which must be at least
+    /// [`Self::len`] bytes long. Maps (and unmaps) each page the segment
+    /// spans in turn.
+    pub fn copy_to_slice(&self, dst: &mut [u8]) -> Result {
+        if dst.len() < self.len() {
+            return Err(EINVAL);
+        }
+
+        let mut done = 0;
+        while done < self.len() {
+            let cur = self.offset() + done;
+            let page_index = cur / crate::PAGE_SIZE as usize;
+            let offset_in_page = cur % crate::PAGE_SIZE as usize;
+            let chunk = (self.len() - done).min(crate::PAGE_SIZE as usize - offset_in_page);
+
+            // SAFETY: `self.bio_vec` is valid, so `bv_page + page_index` is a
+            // valid pointer to a `struct page` covered by this segment. We do
+            // not own the page, but we prevent drop by wrapping the `Pages`
+            // in `ManuallyDrop`.
+            let our_page = ManuallyDrop::new(unsafe {
+                Pages::<0>::from_raw(self.bio_vec.bv_page.add(page_index))
+            });
+
+            // SAFETY: `dst` has at least `chunk` bytes left from `done`, as
+            // checked above.
+            unsafe {
+                our_page.read_atomic(dst.as_mut_ptr().add(done), offset_in_page, chunk)?;
+            }
+
+            done += chunk;
+        }
+
+        Ok(())
+    }
+
+    /// Copy data from `src` into this segment. `src` must be at least
+    /// [`Self::len`] bytes long. Maps (and unmaps) each page the segment
+    /// spans in turn.
+    pub fn copy_from_slice(&mut self, src: &[u8]) -> Result {
+        if src.len() < self.len() {
+            return Err(EINVAL);
+        }
+
+        let mut done = 0;
+        while done < self.len() {
+            let cur = self.offset() + done;
+            let page_index = cur / crate::PAGE_SIZE as usize;
+            let offset_in_page = cur % crate::PAGE_SIZE as usize;
+            let chunk = (self.len() - done).min(crate::PAGE_SIZE as usize - offset_in_page);
+
+            // SAFETY: `self.bio_vec` is valid, so `bv_page + page_index` is a
+            // valid pointer to a `struct page` covered by this segment. We do
+            // not own the page, but we prevent drop by wrapping the `Pages`
+            // in `ManuallyDrop`.
+            let our_page = ManuallyDrop::new(unsafe {
+                Pages::<0>::from_raw(self.bio_vec.bv_page.add(page_index))
+            });
+
+            // SAFETY: `src` has at least `chunk` bytes left from `done`, as
+            // checked above.
+            unsafe {
+                our_page.write_atomic(src.as_ptr().add(done), offset_in_page, chunk)?;
+            }
+
+            done += chunk;
+        }
+
+        Ok(())
+    }
+}
+
+impl core::fmt::Display for MultiPageSegment<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MultiPageSegment {:?} len: {}",
+            self.bio_vec.bv_page, self.bio_vec.bv_len
+        )
+    }
+}
+
+/// An iterator over [`MultiPageSegment`]s of a [`Bio`], yielding whole
+/// `bio_vec` entries without splitting them at page boundaries.
+pub struct BioMultiPageSegmentIterator<'a> {
+    bio: &'a Bio<'a>,
+    iter: bindings::bvec_iter,
+}
+
+impl<'a> BioMultiPageSegmentIterator<'a> {
+    #[inline(always)]
+    pub(crate) fn new(bio: &'a Bio<'a>) -> BioMultiPageSegmentIterator<'_> {
+        Self {
+            bio,
+            iter: bio.iter(),
+        }
+    }
+}
+
+impl<'a> core::iter::Iterator for BioMultiPageSegmentIterator<'a> {
+    type Item = MultiPageSegment<'a>;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.iter.bi_size == 0 {
+            return None;
+        }
+
+        let bio_vec_ret = bindings::bio_vec {
+            bv_page: mp_bvec_iter_page(self.bio.io_vec(), &self.iter),
+            bv_len: mp_bvec_iter_len(self.bio.io_vec(), &self.iter),
+            bv_offset: mp_bvec_iter_offset(self.bio.io_vec(), &self.iter),
+        };
+
+        unsafe {
+            bindings::bio_advance_iter_single(
+                self.bio.get_raw(),
+                &mut self.iter as *mut bindings::bvec_iter,
+                bio_vec_ret.bv_len,
+            )
+        };
+
+        Some(MultiPageSegment {
+            bio_vec: bio_vec_ret,
+            _marker: core::marker::PhantomData,
+        })
+    }
+}
@@ -4,12 +4,18 @@
 //! blk-mq subsystem
 
 mod gen_disk;
+mod integrity;
 mod operations;
+mod queue_limits;
 mod raw_writer;
 mod request;
 mod tag_set;
+mod zoned;
 
 pub use gen_disk::GenDisk;
-pub use operations::Operations;
-pub use request::{Request, RequestQueue, RequestRef};
-pub use tag_set::{TagSet, TagSetRef};
+pub use integrity::{ChecksumType, IntegrityProfile, ProtectionType};
+pub use operations::{Geometry, Operations};
+pub use queue_limits::QueueLimits;
+pub use request::{IoCompletionBatch, Request, RequestList, RequestQueue, RequestRef};
+pub use tag_set::{HctxType, QueueMap, QueueMapSet, TagSet, TagSetConfig, TagSetRef};
+pub use zoned::{ReportZoneCb, Zone, ZoneCondition, ZonedModel, ZoneType};
@@ -5,14 +5,303 @@
 //! C header: [`include/linux/blkdev.h`](srctree/include/linux/blkdev.h)
 //! C header: [`include/linux/blk_mq.h`](srctree/include/linux/blk_mq.h)
 
-use crate::block::mq::{raw_writer::RawWriter, Operations, TagSet};
+use crate::block::mq::{
+    raw_writer::RawWriter, zoned::ReportZoneCb, IntegrityProfile, Operations, QueueLimits, TagSet,
+    ZonedModel,
+};
 use crate::{
-    bindings, error::from_err_ptr, error::Result, sync::Arc, types::ForeignOwnable,
-    types::ScopeGuard,
+    bindings, error::from_err_ptr, error::from_result, error::Result, sync::Arc,
+    types::ForeignOwnable, types::ScopeGuard,
 };
 use core::fmt::{self, Write};
 use core::marker::PhantomData;
 
+/// Builds the `block_device_operations` vtable for a `GenDisk<T, _>`.
+struct OperationsVtable<T: Operations>(PhantomData<T>);
+
+impl<T: Operations> OperationsVtable<T> {
+    /// This function is called by the C kernel. A pointer to this function
+    /// is installed in the `block_device_operations` vtable for the
+    /// gendisk.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by the block layer. The caller must
+    /// ensure that `bdev` is valid.
+    unsafe extern "C" fn ioctl_callback(
+        bdev: *mut bindings::block_device,
+        mode: bindings::blk_mode_t,
+        cmd: core::ffi::c_uint,
+        arg: core::ffi::c_ulong,
+    ) -> core::ffi::c_int {
+        from_result(|| {
+            // SAFETY: `bdev` is valid as required by this function.
+            let queue_data = unsafe { (*(*bdev).bd_disk).queue };
+
+            // SAFETY: `queue_data` is valid as it was derived from `bdev`
+            // above.
+            let queue_data = unsafe { (*queue_data).queuedata };
+
+            // SAFETY: `queue.queuedata` was created by `try_new()` with a
+            // call to `ForeignOwnable::into_foreign()` to create
+            // `queuedata`. `ForeignOwnable::from_foreign()` is only called
+            // when the tagset is dropped, which happens after we are
+            // dropped.
+            let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+            T::ioctl(queue_data, mode, cmd, arg)
+        })
+    }
+
+    /// This function is called by the C kernel. A pointer to this function
+    /// is installed in the `block_device_operations` vtable for the
+    /// gendisk.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by the block layer. The caller must
+    /// ensure that `bdev` is valid.
+    unsafe extern "C" fn compat_ioctl_callback(
+        bdev: *mut bindings::block_device,
+        mode: bindings::blk_mode_t,
+        cmd: core::ffi::c_uint,
+        arg: core::ffi::c_ulong,
+    ) -> core::ffi::c_int {
+        from_result(|| {
+            // SAFETY: `bdev` is valid as required by this function.
+            let queue_data = unsafe { (*(*bdev).bd_disk).queue };
+
+            // SAFETY: `queue_data` is valid as it was derived from `bdev`
+            // above.
+            let queue_data = unsafe { (*queue_data).queuedata };
+
+            // SAFETY: `queue.queuedata` was created by `try_new()` with a
+            // call to `ForeignOwnable::into_foreign()` to create
+            // `queuedata`. `ForeignOwnable::from_foreign()` is only called
+            // when the tagset is dropped, which happens after we are
+            // dropped.
+            let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+            T::compat_ioctl(queue_data, mode, cmd, arg)
+        })
+    }
+
+    /// This function is called by the C kernel. A pointer to this function
+    /// is installed in the `block_device_operations` vtable for the
+    /// gendisk.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by the block layer. The caller must
+    /// ensure that `disk` is valid, and that `cb`, if `Some`, is valid to
+    /// call with `data` for the duration of this function.
+    unsafe extern "C" fn report_zones_callback(
+        disk: *mut bindings::gendisk,
+        sector: bindings::sector_t,
+        nr_zones: core::ffi::c_uint,
+        cb: bindings::report_zones_cb,
+        data: *mut core::ffi::c_void,
+    ) -> core::ffi::c_int {
+        from_result(|| {
+            // SAFETY: `disk` is valid as required by this function.
+            let queue_data = unsafe { (*(*disk).queue).queuedata };
+
+            // SAFETY: `queue.queuedata` was created by `try_new()` with a
+            // call to `ForeignOwnable::into_foreign()` to create
+            // `queuedata`. `ForeignOwnable::from_foreign()` is only called
+            // when the tagset is dropped, which happens after we are
+            // dropped.
+            let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+            // SAFETY: `cb` and `data` are valid for the duration of this
+            // call, as required by the safety requirements of this
+            // function.
+            let cb = unsafe { ReportZoneCb::from_raw(cb, data) };
+
+            Ok(T::report_zones(queue_data, sector, nr_zones, cb)? as i32)
+        })
+    }
+
+    /// This function is called by the C kernel. A pointer to this function
+    /// is installed in the `block_device_operations` vtable for the
+    /// gendisk.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by the block layer. The caller must
+    /// ensure that `disk` is valid.
+    unsafe extern "C" fn open_callback(
+        disk: *mut bindings::gendisk,
+        mode: bindings::blk_mode_t,
+    ) -> core::ffi::c_int {
+        from_result(|| {
+            // SAFETY: `disk` is valid as required by this function.
+            let queue_data = unsafe { (*(*disk).queue).queuedata };
+
+            // SAFETY: `queue.queuedata` was created by `try_new()` with a
+            // call to `ForeignOwnable::into_foreign()` to create
+            // `queuedata`. `ForeignOwnable::from_foreign()` is only called
+            // when the tagset is dropped, which happens after we are
+            // dropped.
+            let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+            T::open(queue_data, mode)?;
+            Ok(0)
+        })
+    }
+
+    /// This function is called by the C kernel. A pointer to this function
+    /// is installed in the `block_device_operations` vtable for the
+    /// gendisk.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by the block layer. The caller must
+    /// ensure that `disk` is valid.
+    unsafe extern "C" fn release_callback(disk: *mut bindings::gendisk) {
+        // SAFETY: `disk` is valid as required by this function.
+        let queue_data = unsafe { (*(*disk).queue).queuedata };
+
+        // SAFETY: `queue.queuedata` was created by `try_new()` with a call to
+        // `ForeignOwnable::into_foreign()` to create `queuedata`.
+        // `ForeignOwnable::from_foreign()` is only called when the tagset is
+        // dropped, which happens after we are dropped.
+        let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+        T::release(queue_data);
+    }
+
+    /// This function is called by the C kernel. A pointer to this function
+    /// is installed in the `block_device_operations` vtable for the
+    /// gendisk.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by the block layer. The caller must
+    /// ensure that `bdev` and `geo` are valid.
+    unsafe extern "C" fn getgeo_callback(
+        bdev: *mut bindings::block_device,
+        geo: *mut bindings::hd_geometry,
+    ) -> core::ffi::c_int {
+        from_result(|| {
+            // SAFETY: `bdev` is valid as required by this function.
+            let queue_data = unsafe { (*(*bdev).bd_disk).queue };
+
+            // SAFETY: `queue_data` is valid as it was derived from `bdev`
+            // above.
+            let queue_data = unsafe { (*queue_data).queuedata };
+
+            // SAFETY: `queue.queuedata` was created by `try_new()` with a
+            // call to `ForeignOwnable::into_foreign()` to create
+            // `queuedata`. `ForeignOwnable::from_foreign()` is only called
+            // when the tagset is dropped, which happens after we are
+            // dropped.
+            let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+            let geometry = T::getgeo(queue_data)?;
+
+            // SAFETY: `geo` is valid for write, as required by this function.
+            unsafe {
+                (*geo).heads = geometry.heads;
+                (*geo).sectors = geometry.sectors;
+                (*geo).cylinders = geometry.cylinders;
+                (*geo).start = geometry.start as _;
+            }
+
+            Ok(0)
+        })
+    }
+
+    /// This function is called by the C kernel. A pointer to this function
+    /// is installed in the `block_device_operations` vtable for the
+    /// gendisk.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by the block layer. The caller must
+    /// ensure that `disk` is valid and that `id` points to 16 bytes valid
+    /// for write.
+    unsafe extern "C" fn get_unique_id_callback(
+        disk: *mut bindings::gendisk,
+        id: *mut u8,
+        id_type: bindings::blk_unique_id,
+    ) -> core::ffi::c_int {
+        from_result(|| {
+            // SAFETY: `disk` is valid as required by this function.
+            let queue_data = unsafe { (*(*disk).queue).queuedata };
+
+            // SAFETY: `queue.queuedata` was created by `try_new()` with a
+            // call to `ForeignOwnable::into_foreign()` to create
+            // `queuedata`. `ForeignOwnable::from_foreign()` is only called
+            // when the tagset is dropped, which happens after we are
+            // dropped.
+            let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+            // SAFETY: `id` points to 16 bytes valid for write, as required by
+            // this function.
+            let id = unsafe { &mut *id.cast::<[u8; 16]>() };
+
+            T::get_unique_id(queue_data, id, id_type)
+        })
+    }
+
+    const TABLE: bindings::block_device_operations = bindings::block_device_operations {
+        submit_bio: None,
+        open: if T::HAS_OPEN {
+            Some(Self::open_callback)
+        } else {
+            None
+        },
+        release: if T::HAS_RELEASE {
+            Some(Self::release_callback)
+        } else {
+            None
+        },
+        ioctl: if T::HAS_IOCTL {
+            Some(Self::ioctl_callback)
+        } else {
+            None
+        },
+        compat_ioctl: if T::HAS_COMPAT_IOCTL {
+            Some(Self::compat_ioctl_callback)
+        } else {
+            None
+        },
+        check_events: None,
+        unlock_native_capacity: None,
+        getgeo: if T::HAS_GETGEO {
+            Some(Self::getgeo_callback)
+        } else {
+            None
+        },
+        set_read_only: None,
+        swap_slot_free_notify: None,
+        report_zones: if T::HAS_REPORT_ZONES {
+            Some(Self::report_zones_callback)
+        } else {
+            None
+        },
+        devnode: None,
+        alternative_gpt_sector: None,
+        get_unique_id: if T::HAS_GET_UNIQUE_ID {
+            Some(Self::get_unique_id_callback)
+        } else {
+            None
+        },
+        // TODO: Set to THIS_MODULE. Waiting for const_refs_to_static feature to
+        // be merged (unstable in rustc 1.78 which is staged for linux 6.10)
+        // https://github.com/rust-lang/rust/issues/119618
+        owner: core::ptr::null_mut(),
+        pr_ops: core::ptr::null_mut(),
+        free_disk: None,
+        poll_bio: None,
+    };
+
+    const fn build() -> &'static bindings::block_device_operations {
+        &Self::TABLE
+    }
+}
+
 /// A generic block device.
 ///
 /// # Invariants
@@ -44,6 +333,56 @@ impl GenDiskState for Initialized {}
 impl GenDiskState for Added {}
 
 impl<T: Operations> GenDisk<T, Initialized> {
+    /// Try to create a new `GenDisk`.
+    ///
+    /// `limits`, if given, is applied to the request queue before it is
+    /// reachable by any request, so that the device is correctly configured
+    /// from the very first IO instead of being patched up afterwards with
+    /// [`set_queue_logical_block_size`](Self::set_queue_logical_block_size)
+    /// and
+    /// [`set_queue_physical_block_size`](Self::set_queue_physical_block_size),
+    /// which race with early IO and should be avoided by new callers.
+    pub fn try_new(
+        tagset: Arc<TagSet<T>>,
+        queue_data: T::QueueData,
+        limits: Option<QueueLimits>,
+    ) -> Result<Self> {
+        let data = queue_data.into_foreign();
+        let recover_data = ScopeGuard::new(|| {
+            // SAFETY: T::QueueData was created by the call to `into_foreign()` above
+            unsafe { T::QueueData::from_foreign(data) };
+        });
+
+        let lock_class_key = crate::sync::LockClassKey::new();
+
+        let mut limits = limits.unwrap_or_default();
+
+        // SAFETY: `tagset.raw_tag_set()` points to a valid and initialized tag
+        // set, and `limits.as_ptr()` points to a valid `struct queue_limits`.
+        let gendisk = from_err_ptr(unsafe {
+            bindings::__blk_mq_alloc_disk(
+                tagset.raw_tag_set(),
+                limits.as_ptr(),
+                data.cast_mut(),
+                lock_class_key.as_ptr(),
+            )
+        })?;
+
+        // SAFETY: gendisk is a valid pointer as we initialized it above
+        unsafe { (*gendisk).fops = OperationsVtable::<T>::build() };
+
+        recover_data.dismiss();
+
+        // INVARIANT: `gendisk` was initialized above.
+        // INVARIANT: `gendisk.queue.queue_data` is set to `data` in the call to
+        // `__blk_mq_alloc_disk` above.
+        Ok(GenDisk {
+            _tagset: tagset,
+            gendisk,
+            _phantom: PhantomData,
+        })
+    }
+
     /// Register the device with the kernel. When this function returns, the
     /// device is accessible from VFS. The kernel may issue reads to the device
     /// during registration to discover partition information.
@@ -96,6 +435,10 @@ impl<T: Operations> GenDisk<T, Initialized> {
     ///
     /// This is the smallest unit the storage device can address. It is
     /// typically 512 bytes.
+    ///
+    /// Prefer passing [`QueueLimits::logical_block_size`] to
+    /// [`try_new`](Self::try_new) instead: this method changes the queue
+    /// after it may already be processing requests.
     pub fn set_queue_logical_block_size(&mut self, size: u32) {
         // SAFETY: By type invariant, `self.gendisk` points to a valid and
         // initialized instance of `struct gendisk`.
@@ -108,11 +451,56 @@ impl<T: Operations> GenDisk<T, Initialized> {
     /// atomically. It is usually the same as the logical block size but may be
     /// bigger. One example is SATA drives with 4KB sectors that expose a
     /// 512-byte logical block size to the operating system.
+    ///
+    /// Prefer passing [`QueueLimits::physical_block_size`] to
+    /// [`try_new`](Self::try_new) instead: this method changes the queue
+    /// after it may already be processing requests.
     pub fn set_queue_physical_block_size(&mut self, size: u32) {
         // SAFETY: By type invariant, `self.gendisk` points to a valid and
         // initialized instance of `struct gendisk`.
         unsafe { bindings::blk_queue_physical_block_size((*self.gendisk).queue, size) };
     }
+
+    /// Mark the device as a zoned block device with the given zone model.
+    ///
+    /// `T::report_zones` must be implemented so the block layer can query
+    /// the zones this declares the device to have; the number and size of
+    /// those zones follow from the capacity set through
+    /// [`Self::set_capacity_sectors`] and the `chunk_sectors` queue limit
+    /// passed to [`Self::try_new`].
+    pub fn set_zoned(&mut self, model: ZonedModel) {
+        // SAFETY: By type invariant, `self.gendisk` points to a valid and
+        // initialized instance of `struct gendisk`.
+        unsafe { bindings::disk_set_zoned(self.gendisk, model.as_raw()) };
+    }
+
+    /// Advertise (or disable) a volatile write-back cache for the device.
+    ///
+    /// When `write_back` is `true`, the block layer will insert
+    /// `REQ_OP_FLUSH` requests (and, if `fua` is `true`, mark individual
+    /// write requests `FUA`) around writes that need to survive a crash,
+    /// dispatched to [`Operations::flush`](crate::block::mq::Operations::flush).
+    /// The driver must implement `Operations::flush` and complete those
+    /// requests only once its cache has actually been flushed to stable
+    /// storage, or acknowledged writes can be lost on a crash.
+    pub fn set_write_cache(&mut self, write_back: bool, fua: bool) {
+        // SAFETY: By type invariant, `self.gendisk` points to a valid and
+        // initialized instance of `struct gendisk`.
+        unsafe { bindings::blk_queue_write_cache((*self.gendisk).queue, write_back, fua) };
+    }
+
+    /// Register a block integrity (T10 PI / DIX) profile for the device.
+    ///
+    /// `T::generate` and `T::verify` are not invoked by this call or by the
+    /// block layer; the driver is expected to call them itself from its own
+    /// IO-processing code to fill in or check the guard tags of bios that
+    /// carry integrity metadata, using `profile` to know their shape.
+    pub fn register_integrity(&mut self, mut profile: IntegrityProfile) {
+        // SAFETY: By type invariant, `self.gendisk` points to a valid and
+        // initialized instance of `struct gendisk`, and `profile.as_ptr()`
+        // points to a valid `struct blk_integrity`.
+        unsafe { bindings::blk_integrity_register(self.gendisk, profile.as_ptr()) };
+    }
 }
 
 impl<T: Operations, S: GenDiskState> GenDisk<T, S> {
@@ -167,65 +555,3 @@ impl<T: Operations, S: GenDiskState> Drop for GenDisk<T, S> {
         let _queue_data = unsafe { T::QueueData::from_foreign(queue_data) };
     }
 }
-
-/// Try to create a new `GenDisk`.
-pub fn try_new<T: Operations>(
-    tagset: Arc<TagSet<T>>,
-    queue_data: T::QueueData,
-) -> Result<GenDisk<T, Initialized>> {
-    let data = queue_data.into_foreign();
-    let recover_data = ScopeGuard::new(|| {
-        // SAFETY: T::QueueData was created by the call to `into_foreign()` above
-        unsafe { T::QueueData::from_foreign(data) };
-    });
-
-    let lock_class_key = crate::sync::LockClassKey::new();
-
-    // SAFETY: `tagset.raw_tag_set()` points to a valid and initialized tag set
-    let gendisk = from_err_ptr(unsafe {
-        bindings::__blk_mq_alloc_disk(
-            tagset.raw_tag_set(),
-            core::ptr::null_mut(), // TODO: We can pass queue limits right here
-            data.cast_mut(),
-            lock_class_key.as_ptr(),
-        )
-    })?;
-
-    const TABLE: bindings::block_device_operations = bindings::block_device_operations {
-        submit_bio: None,
-        open: None,
-        release: None,
-        ioctl: None,
-        compat_ioctl: None,
-        check_events: None,
-        unlock_native_capacity: None,
-        getgeo: None,
-        set_read_only: None,
-        swap_slot_free_notify: None,
-        report_zones: None,
-        devnode: None,
-        alternative_gpt_sector: None,
-        get_unique_id: None,
-        // TODO: Set to THIS_MODULE. Waiting for const_refs_to_static feature to
-        // be merged (unstable in rustc 1.78 which is staged for linux 6.10)
-        // https://github.com/rust-lang/rust/issues/119618
-        owner: core::ptr::null_mut(),
-        pr_ops: core::ptr::null_mut(),
-        free_disk: None,
-        poll_bio: None,
-    };
-
-    // SAFETY: gendisk is a valid pointer as we initialized it above
-    unsafe { (*gendisk).fops = &TABLE };
-
-    recover_data.dismiss();
-
-    // INVARIANT: `gendisk` was initialized above.
-    // INVARIANT: `gendisk.queue.queue_data` is set to `data` in the call to
-    // `__blk_mq_alloc_disk` above.
-    Ok(GenDisk {
-        _tagset: tagset,
-        gendisk,
-        _phantom: PhantomData,
-    })
-}
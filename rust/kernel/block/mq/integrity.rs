@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Block integrity (T10 PI / DIX) profile, registered with a [`GenDisk`] so
+//! the block layer knows the shape of the protection information metadata
+//! attached to the device's bios.
+//!
+//! [`GenDisk`]: crate::block::mq::GenDisk
+//!
+//! C header: [`include/linux/blk-integrity.h`](srctree/include/linux/blk-integrity.h)
+
+use crate::{bindings, error::code::*, error::Result};
+
+/// The guard tag checksum carried in each protection information tuple.
+///
+/// This mirrors the C `enum blk_integrity_checksum`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumType {
+    /// No checksum is present; the tuple only carries a reference tag.
+    None,
+
+    /// The checksum is the IP checksum used by DIX.
+    Ip,
+
+    /// The checksum is the T10 CRC-16 (CRC-T10DIF) used by T10 PI.
+    Crc,
+
+    /// The checksum is the T10 CRC-64 used by T10 PI.
+    Crc64,
+}
+
+impl ChecksumType {
+    fn as_raw(self) -> bindings::blk_integrity_checksum {
+        match self {
+            ChecksumType::None => bindings::blk_integrity_checksum_BLK_INTEGRITY_CSUM_NONE,
+            ChecksumType::Ip => bindings::blk_integrity_checksum_BLK_INTEGRITY_CSUM_IP,
+            ChecksumType::Crc => bindings::blk_integrity_checksum_BLK_INTEGRITY_CSUM_CRC,
+            ChecksumType::Crc64 => bindings::blk_integrity_checksum_BLK_INTEGRITY_CSUM_CRC64,
+        }
+    }
+}
+
+/// The kind of protection scheme a device's guard tags implement.
+///
+/// This is Rust-facing, descriptive metadata for the driver; it has no
+/// direct `struct blk_integrity` field counterpart, since ref-tag checking
+/// is implemented at the SCSI/NVMe layer rather than the block layer in the
+/// real kernel. Drivers may use it to pick how [`Operations::generate`] and
+/// [`Operations::verify`] interpret the reference tag.
+///
+/// [`Operations::generate`]: crate::block::mq::Operations::generate
+/// [`Operations::verify`]: crate::block::mq::Operations::verify
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ProtectionType {
+    /// T10 PI Type 1: the reference tag is the low 32 bits of the sector.
+    Type1,
+
+    /// T10 PI Type 2: like Type 1, but the reference tag is only checked
+    /// when supplied by the initiator.
+    Type2,
+
+    /// T10 PI Type 3: the reference tag is not checked against the sector.
+    Type3,
+
+    /// DIX: protection information is exchanged with the block layer but
+    /// not passed on to the storage device.
+    Dix,
+}
+
+/// A builder for `struct blk_integrity`.
+///
+/// Accumulates the shape of the protection information tuples a driver
+/// attaches to its bios, to be registered with a [`GenDisk`] through
+/// [`GenDisk::register_integrity`](crate::block::mq::GenDisk::register_integrity).
+#[derive(Clone, Copy)]
+pub struct IntegrityProfile {
+    bi: bindings::blk_integrity,
+    kind: ProtectionType,
+}
+
+impl IntegrityProfile {
+    /// Create a new integrity profile for the given protection type and
+    /// checksum.
+    pub fn new(kind: ProtectionType, csum_type: ChecksumType) -> Self {
+        // SAFETY: `blk_integrity` only contains integers that are valid when
+        // zeroed.
+        let mut bi: bindings::blk_integrity = unsafe { core::mem::zeroed() };
+        bi.csum_type = csum_type.as_raw();
+        Self { bi, kind }
+    }
+
+    /// Set the size of a single protection information tuple, in bytes.
+    pub fn tuple_size(mut self, size: u8) -> Result<Self> {
+        if size == 0 {
+            return Err(EINVAL);
+        }
+        self.bi.tuple_size = size;
+        Ok(self)
+    }
+
+    /// Set the byte offset of the reference tag within a tuple.
+    pub fn pi_offset(mut self, offset: u8) -> Self {
+        self.bi.pi_offset = offset;
+        self
+    }
+
+    /// Set the binary logarithm of the number of data bytes a single tuple
+    /// covers (the "metadata interval").
+    pub fn interval_exp(mut self, interval_exp: u8) -> Self {
+        self.bi.interval_exp = interval_exp;
+        self
+    }
+
+    /// Set the size of the opaque application tag carried in each tuple, in
+    /// bytes.
+    pub fn tag_size(mut self, size: u8) -> Self {
+        self.bi.tag_size = size;
+        self
+    }
+
+    /// The protection type this profile was created with.
+    pub fn kind(&self) -> ProtectionType {
+        self.kind
+    }
+
+    pub(crate) fn as_ptr(&mut self) -> *mut bindings::blk_integrity {
+        &mut self.bi
+    }
+}
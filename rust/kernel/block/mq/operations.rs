@@ -6,12 +6,21 @@
 
 use crate::{
     bindings,
+    block::bio::Segment,
     block::mq::request::RequestDataWrapper,
     block::mq::Request,
+    block::mq::raw_writer::RawWriter,
+    block::mq::IoCompletionBatch,
+    block::mq::QueueMapSet,
+    block::mq::RequestList,
+    block::mq::zoned::ReportZoneCb,
     error::{from_result, Result},
     types::{ARef, ForeignOwnable},
 };
-use core::{marker::PhantomData, sync::atomic::AtomicU64, sync::atomic::Ordering};
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicI32, AtomicU64, Ordering},
+};
 
 type ForeignBorrowed<'a, T> = <T as ForeignOwnable>::Borrowed<'a>;
 
@@ -35,27 +44,296 @@ pub trait Operations: Sized {
     /// blk_mq_tag_set`.
     type TagSetData: ForeignOwnable;
 
+    /// Data associated with a `struct blk_mq_hw_ctx`. This is stored as a
+    /// pointer in the hardware queue context's `driver_data` field, set up
+    /// by [`Self::init_hctx`] and torn down by [`Self::exit_hctx`].
+    type HctxData: ForeignOwnable;
+
     /// Called by the kernel to queue a request with the driver. If `is_last` is
     /// `false`, the driver is allowed to defer committing the request.
     fn queue_rq(
         queue_data: ForeignBorrowed<'_, Self::QueueData>,
         rq: ARef<Request<Self>>, is_last: bool) -> Result;
 
+    /// Called by the kernel when a [`Request::is_flush`] request is queued,
+    /// asking the driver to flush its volatile write cache before
+    /// completing `rq`. Only called for devices whose write cache was
+    /// enabled through
+    /// [`GenDisk::set_write_cache`](crate::block::mq::GenDisk::set_write_cache);
+    /// other devices never see a flush request. If this method is not
+    /// implemented, flush requests are dispatched to [`Self::queue_rq`] like
+    /// any other.
+    fn flush(queue_data: ForeignBorrowed<'_, Self::QueueData>, rq: ARef<Request<Self>>) -> Result {
+        let _ = (queue_data, rq);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
     /// Called by the kernel to indicate that queued requests should be submitted.
     fn commit_rqs(
         queue_data: ForeignBorrowed<'_, Self::QueueData>,
     );
 
+    /// Called by the kernel to queue a batch of requests at once. `rqlist`
+    /// yields the requests the block layer is offering to this call; the
+    /// driver pops and commits as many as it can handle, leaving the rest in
+    /// `rqlist` for the block layer to dispatch individually through
+    /// `queue_rq`.
+    fn queue_rqs(queue_data: ForeignBorrowed<'_, Self::QueueData>, rqlist: RequestList<'_, Self>) {
+        let _ = (queue_data, rqlist);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
     /// Called by the kernel when the request is completed.
     fn complete(_rq: ARef<Request<Self>>);
 
+    /// Called by the kernel to set up driver-private state for a hardware
+    /// queue context. The returned value is stored in the `blk_mq_hw_ctx`
+    /// and handed back to [`Self::exit_hctx`] when the context is torn down.
+    fn init_hctx(
+        tagset_data: ForeignBorrowed<'_, Self::TagSetData>,
+        hctx_idx: u32,
+    ) -> Result<Self::HctxData>;
+
+    /// Called by the kernel to tear down a hardware queue context previously
+    /// set up by [`Self::init_hctx`].
+    fn exit_hctx(hctx_data: Self::HctxData, hctx_idx: u32);
+
     /// Called by the kernel to poll the device for completed requests. Only
-    /// used for poll queues.
-    fn poll() -> bool {
+    /// used for poll queues. `hctx_data` is the value this hardware queue's
+    /// [`Self::init_hctx`] returned, so a driver can reach whatever
+    /// per-hardware-queue completion ring or doorbell it needs without
+    /// having a request in hand. Requests observed as completed during the
+    /// sweep should be pushed onto `iob` so the block layer can complete
+    /// them together in one batch.
+    fn poll(
+        queue_data: ForeignBorrowed<'_, Self::QueueData>,
+        hctx_data: ForeignBorrowed<'_, Self::HctxData>,
+        iob: &mut IoCompletionBatch<'_, Self>,
+    ) -> bool {
+        let _ = (queue_data, hctx_data, iob);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel when the request has exceeded its deadline.
+    ///
+    /// This is blk-mq's own per-request timeout facility (`blk_mq_ops::timeout`),
+    /// distinct from the hrtimer-based [`RawTimer`]/[`RawTimerCallback`]
+    /// implementations on `ARef<Request<T>>`: those drive a driver-managed
+    /// hrtimer a driver may use for its own purposes (for example a
+    /// command-level retry timer), while this method is invoked directly by
+    /// the block layer's own stuck-request detection and is the right place
+    /// to implement controller abort/reset logic.
+    ///
+    /// Implementations decide whether the request should be given more time
+    /// ([`TimeoutReturn::ResetTimer`]) or whether the block layer should
+    /// finish tearing it down ([`TimeoutReturn::Done`]).
+    ///
+    /// [`RawTimer`]: kernel::hrtimer::RawTimer
+    /// [`RawTimerCallback`]: kernel::hrtimer::RawTimerCallback
+    ///
+    /// # Invariants
+    ///
+    /// If the implementation returns [`TimeoutReturn::ResetTimer`], it must
+    /// not have consumed `rq`'s refcount; the request is still in flight and
+    /// must be left that way (for example by [`mem::forget`]ing `rq` rather
+    /// than dropping it). If the implementation instead finishes the request
+    /// from within this method, it must do so through [`Request::complete`]
+    /// or one of the `end*` methods, which follow the same leaked-refcount
+    /// protocol as [`Request::complete`].
+    ///
+    /// [`mem::forget`]: core::mem::forget
+    fn timeout(rq: ARef<Request<Self>>) -> TimeoutReturn {
+        let _ = rq;
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel to request a dispatch budget before `queue_rq`
+    /// is called. Returning `Err` tells the block layer the driver is busy;
+    /// dispatch of this queue is deferred until budget becomes available.
+    fn get_budget(_queue_data: ForeignBorrowed<'_, Self::QueueData>) -> Result<BudgetToken> {
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel to return a budget token previously obtained
+    /// from [`Self::get_budget`].
+    fn put_budget(_queue_data: ForeignBorrowed<'_, Self::QueueData>, _token: BudgetToken) {
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel to let the driver set up the CPU-to-hardware-queue
+    /// mapping for each queue type (default, read, poll) instead of falling
+    /// back to the kernel's default mapping.
+    fn map_queues(tagset: &mut QueueMapSet<'_>) {
+        let _ = tagset;
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel to print debugfs information about `rq`. `rq`
+    /// is only borrowed for the duration of this call; no refcount is
+    /// involved.
+    fn show_rq(rq: &Request<Self>, writer: &mut RawWriter) -> core::fmt::Result {
+        let _ = (rq, writer);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel when userspace calls `ioctl` on the gendisk
+    /// associated with this `Operations` implementation. The returned value
+    /// is passed back to userspace as the `ioctl` return value.
+    fn ioctl(
+        queue_data: ForeignBorrowed<'_, Self::QueueData>,
+        mode: bindings::blk_mode_t,
+        cmd: u32,
+        arg: u64,
+    ) -> Result<i32> {
+        let _ = (queue_data, mode, cmd, arg);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel when a 32-bit userspace process calls `ioctl`
+    /// on the gendisk associated with this `Operations` implementation on a
+    /// 64-bit kernel.
+    fn compat_ioctl(
+        queue_data: ForeignBorrowed<'_, Self::QueueData>,
+        mode: bindings::blk_mode_t,
+        cmd: u32,
+        arg: u64,
+    ) -> Result<i32> {
+        let _ = (queue_data, mode, cmd, arg);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel when userspace opens the gendisk associated with
+    /// this `Operations` implementation.
+    fn open(queue_data: ForeignBorrowed<'_, Self::QueueData>, mode: bindings::blk_mode_t) -> Result {
+        let _ = (queue_data, mode);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel when the last open reference to the gendisk
+    /// associated with this `Operations` implementation is released.
+    fn release(queue_data: ForeignBorrowed<'_, Self::QueueData>) {
+        let _ = queue_data;
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel to answer `HDIO_GETGEO` for the gendisk
+    /// associated with this `Operations` implementation.
+    fn getgeo(queue_data: ForeignBorrowed<'_, Self::QueueData>) -> Result<Geometry> {
+        let _ = queue_data;
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel to fill in a unique identifier of the given
+    /// `id_type` for the gendisk associated with this `Operations`
+    /// implementation. Returns the number of bytes of `id` that were
+    /// written.
+    fn get_unique_id(
+        queue_data: ForeignBorrowed<'_, Self::QueueData>,
+        id: &mut [u8; 16],
+        id_type: bindings::blk_unique_id,
+    ) -> Result<i32> {
+        let _ = (queue_data, id, id_type);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the kernel to report up to `nr_zones` zones starting at
+    /// `sector`, in order of increasing start sector, by calling
+    /// `cb.report()` once per zone. Only called for devices marked zoned
+    /// through [`GenDisk::set_zoned`](crate::block::mq::GenDisk::set_zoned).
+    ///
+    /// Returns the number of zones reported.
+    fn report_zones(
+        queue_data: ForeignBorrowed<'_, Self::QueueData>,
+        sector: u64,
+        nr_zones: u32,
+        cb: ReportZoneCb<'_>,
+    ) -> Result<u32> {
+        let _ = (queue_data, sector, nr_zones, cb);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the driver's own IO-processing code (not by the block
+    /// layer) to compute the protection-information guard tag(s) covering
+    /// `data`, writing them into `tag`. Pure-Rust drivers implementing
+    /// software T10 PI / DIX can use this to fill in the metadata segment
+    /// of a bio before submitting it, or before reporting completion of a
+    /// write that carries integrity metadata.
+    fn generate(data: &Segment<'_>, tag: &mut [u8]) -> Result {
+        let _ = (data, tag);
+        crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// Called by the driver's own IO-processing code (not by the block
+    /// layer) to verify that `tag` is the correct guard tag for `data`.
+    /// Pure-Rust drivers implementing software T10 PI / DIX can use this to
+    /// check the metadata segment of a read bio against the data segments
+    /// it covers.
+    fn verify(data: &Segment<'_>, tag: &[u8]) -> Result {
+        let _ = (data, tag);
         crate::build_error(crate::error::VTABLE_DEFAULT_ERROR)
     }
 }
 
+/// The legacy CHS geometry of a block device, as reported through
+/// `HDIO_GETGEO` by [`Operations::getgeo`].
+///
+/// This mirrors the C `struct hd_geometry`.
+#[derive(Clone, Copy)]
+pub struct Geometry {
+    /// Number of heads.
+    pub heads: u8,
+
+    /// Number of sectors per track.
+    pub sectors: u8,
+
+    /// Number of cylinders.
+    pub cylinders: u16,
+
+    /// Starting sector of this device, relative to the whole disk.
+    pub start: u64,
+}
+
+/// A dispatch budget token handed out by [`Operations::get_budget`] and
+/// later returned through [`Operations::put_budget`].
+///
+/// This mirrors the plain `int` budget token used by the C
+/// `get_budget`/`put_budget`/`*_rq_budget_token` contract in `struct
+/// blk_mq_ops`.
+#[derive(Clone, Copy)]
+pub struct BudgetToken(core::ffi::c_int);
+
+impl BudgetToken {
+    fn into_raw(self) -> core::ffi::c_int {
+        self.0
+    }
+
+    fn from_raw(token: core::ffi::c_int) -> Self {
+        Self(token)
+    }
+}
+
+/// The result of a call to [`Operations::timeout`].
+///
+/// This mirrors the C `enum blk_eh_timer_return`.
+pub enum TimeoutReturn {
+    /// The driver has finished (or is in the process of finishing) the
+    /// request by other means; the block layer may tear it down.
+    Done,
+
+    /// The driver judges the request to still be making progress. The block
+    /// layer resets the deadline and the request remains in flight.
+    ResetTimer,
+}
+
+impl TimeoutReturn {
+    fn into_raw(self) -> bindings::blk_eh_timer_return {
+        match self {
+            TimeoutReturn::Done => bindings::BLK_EH_DONE,
+            TimeoutReturn::ResetTimer => bindings::BLK_EH_RESET_TIMER,
+        }
+    }
+}
+
 /// A vtable for blk-mq to interact with a block device driver.
 ///
 /// A `bindings::blk_mq_opa` vtable is constructed from pointers to the `extern
@@ -109,12 +387,16 @@ impl<T: Operations> OperationsVTable<T> {
         // SAFETY: We have exclusive access and we just set the refcount above.
         unsafe { Request::start_unchecked(&rq) };
 
-        let ret = T::queue_rq(
-            queue_data,
-            rq,
-            // SAFETY: `bd` is valid as required by the safety requirement for this function.
-            unsafe { (*bd).last },
-        );
+        let ret = if T::HAS_FLUSH && request.is_flush() {
+            T::flush(queue_data, rq)
+        } else {
+            T::queue_rq(
+                queue_data,
+                rq,
+                // SAFETY: `bd` is valid as required by the safety requirement for this function.
+                unsafe { (*bd).last },
+            )
+        };
 
         if let Err(e) = ret {
             e.to_blk_status()
@@ -123,6 +405,43 @@ impl<T: Operations> OperationsVTable<T> {
         }
     }
 
+    /// This function is called by the C kernel. A pointer to this function is
+    /// installed in the `blk_mq_ops` vtable for the driver.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by blk-mq C infrastructure. `rqlist`
+    /// must be valid for the duration of this call, and every request
+    /// reachable from it must be a valid, not yet started `Request<T>` for
+    /// which `OperationsVTable<T>::init_request_callback` was called.
+    unsafe extern "C" fn queue_rqs_callback(rqlist: *mut bindings::rq_list) {
+        // SAFETY: `rqlist` is valid for the duration of this call, as
+        // required by the safety requirements of this function.
+        let rqlist = unsafe { &mut *rqlist };
+
+        let Some(head) = (!rqlist.head.is_null()).then_some(rqlist.head) else {
+            return;
+        };
+
+        // SAFETY: `head` is a valid `struct request` as required by the
+        // safety requirements of this function.
+        let queue_data = unsafe { (*(*head).q).queuedata };
+
+        // SAFETY: `queue.queuedata` was created by `GenDisk::try_new()` with a
+        // call to `ForeignOwnable::into_pointer()` to create `queuedata`.
+        // `ForeignOwnable::from_foreign()` is only called when the tagset is
+        // dropped, which happens after we are dropped.
+        let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+        // SAFETY: `rqlist` is valid for the duration of this call, and every
+        // request reachable from it satisfies the safety requirements of
+        // `RequestList::from_raw`, as required by the safety requirements of
+        // this function.
+        let rqlist = unsafe { RequestList::from_raw(rqlist) };
+
+        T::queue_rqs(queue_data, rqlist);
+    }
+
     /// This function is called by the C kernel. A pointer to this function is
     /// installed in the `blk_mq_ops` vtable for the driver.
     ///
@@ -142,6 +461,91 @@ impl<T: Operations> OperationsVTable<T> {
         T::commit_rqs(queue_data)
     }
 
+    /// This function is called by the C kernel. A pointer to this function is
+    /// installed in the `blk_mq_ops` vtable for the driver.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by blk-mq C infrastructure. The
+    /// caller must ensure that `q` is valid.
+    unsafe extern "C" fn get_budget_callback(q: *mut bindings::request_queue) -> core::ffi::c_int {
+        // SAFETY: `q` is valid as required by this function.
+        let queue_data = unsafe { (*q).queuedata };
+
+        // SAFETY: `queue.queuedata` was created by `GenDisk::try_new()` with a
+        // call to `ForeignOwnable::into_pointer()` to create `queuedata`.
+        // `ForeignOwnable::from_foreign()` is only called when the tagset is
+        // dropped, which happens after we are dropped.
+        let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+        match T::get_budget(queue_data) {
+            Ok(token) => token.into_raw(),
+            Err(_e) => -1,
+        }
+    }
+
+    /// This function is called by the C kernel. A pointer to this function is
+    /// installed in the `blk_mq_ops` vtable for the driver.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by blk-mq C infrastructure. The
+    /// caller must ensure that `q` is valid.
+    unsafe extern "C" fn put_budget_callback(
+        q: *mut bindings::request_queue,
+        budget_token: core::ffi::c_int,
+    ) {
+        // SAFETY: `q` is valid as required by this function.
+        let queue_data = unsafe { (*q).queuedata };
+
+        // SAFETY: `queue.queuedata` was created by `GenDisk::try_new()` with a
+        // call to `ForeignOwnable::into_pointer()` to create `queuedata`.
+        // `ForeignOwnable::from_foreign()` is only called when the tagset is
+        // dropped, which happens after we are dropped.
+        let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+        T::put_budget(queue_data, BudgetToken::from_raw(budget_token));
+    }
+
+    /// This function is called by the C kernel. A pointer to this function is
+    /// installed in the `blk_mq_ops` vtable for the driver.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by blk-mq C infrastructure. `rq`
+    /// must point to a valid request for which
+    /// `Self::init_request_callback` was called.
+    unsafe extern "C" fn set_rq_budget_token_callback(
+        rq: *mut bindings::request,
+        token: core::ffi::c_int,
+    ) {
+        // SAFETY: The tagset invariants guarantee that all requests are
+        // allocated with extra memory for the request data.
+        let pdu = unsafe { bindings::blk_mq_rq_to_pdu(rq) }.cast::<RequestDataWrapper<T>>();
+
+        // SAFETY: `pdu`'s `budget_token` field is initialized and valid for
+        // write.
+        unsafe { (*RequestDataWrapper::<T>::budget_token_ptr(pdu)).store(token, Ordering::Relaxed) };
+    }
+
+    /// This function is called by the C kernel. A pointer to this function is
+    /// installed in the `blk_mq_ops` vtable for the driver.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by blk-mq C infrastructure. `rq`
+    /// must point to a valid request for which
+    /// `Self::init_request_callback` was called.
+    unsafe extern "C" fn get_rq_budget_token_callback(rq: *mut bindings::request) -> core::ffi::c_int {
+        // SAFETY: The tagset invariants guarantee that all requests are
+        // allocated with extra memory for the request data.
+        let pdu = unsafe { bindings::blk_mq_rq_to_pdu(rq) }.cast::<RequestDataWrapper<T>>();
+
+        // SAFETY: `pdu`'s `budget_token` field is initialized and valid for
+        // read.
+        unsafe { (*RequestDataWrapper::<T>::budget_token_ptr(pdu)).load(Ordering::Relaxed) }
+    }
+
     /// This function is called by the C kernel. A pointer to this function is
     /// installed in the `blk_mq_ops` vtable for the driver.
     ///
@@ -163,12 +567,55 @@ impl<T: Operations> OperationsVTable<T> {
     ///
     /// # Safety
     ///
-    /// This function may only be called by blk-mq C infrastructure.
+    /// This function may only be called by blk-mq C infrastructure. `rq`
+    /// must point to a valid `struct request` that was previously dispatched
+    /// through `Self::queue_rq_callback`.
+    unsafe extern "C" fn timeout_callback(
+        rq: *mut bindings::request,
+    ) -> bindings::blk_eh_timer_return {
+        // SAFETY: This function can only be dispatched for a request that
+        // was started by `queue_rq_callback`, which left behind the
+        // baseline in-flight refcount that we pick back up here.
+        let rq = unsafe { Request::aref_from_raw(rq) };
+        T::timeout(rq).into_raw()
+    }
+
+    /// This function is called by the C kernel. A pointer to this function is
+    /// installed in the `blk_mq_ops` vtable for the driver.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by blk-mq C infrastructure. The
+    /// caller must ensure that `hctx` and `iob` are valid, and that `hctx`
+    /// was set up by a prior call to `Self::init_hctx_callback` that has not
+    /// already been torn down.
     unsafe extern "C" fn poll_callback(
-        _hctx: *mut bindings::blk_mq_hw_ctx,
-        _iob: *mut bindings::io_comp_batch,
+        hctx: *mut bindings::blk_mq_hw_ctx,
+        iob: *mut bindings::io_comp_batch,
     ) -> core::ffi::c_int {
-        T::poll().into()
+        // SAFETY: `hctx` is valid as required by this function.
+        let queue_data = unsafe { (*(*hctx).queue).queuedata };
+
+        // SAFETY: `queue.queuedata` was created by `GenDisk::try_new()` with a
+        // call to `ForeignOwnable::into_pointer()` to create `queuedata`.
+        // `ForeignOwnable::from_foreign()` is only called when the tagset is
+        // dropped, which happens after we are dropped.
+        let queue_data = unsafe { T::QueueData::borrow(queue_data) };
+
+        // SAFETY: `hctx` is valid as required by this function.
+        let hctx_data = unsafe { (*hctx).driver_data };
+
+        // SAFETY: `hctx_data` was created by `Self::init_hctx_callback` with
+        // a call to `ForeignOwnable::into_foreign()`, and is not dropped
+        // until `Self::exit_hctx_callback`, which the caller guarantees has
+        // not yet happened.
+        let hctx_data = unsafe { T::HctxData::borrow(hctx_data) };
+
+        // SAFETY: `iob` is valid for the duration of this call, as required
+        // by this function.
+        let mut iob = unsafe { IoCompletionBatch::from_raw(&mut *iob) };
+
+        T::poll(queue_data, hctx_data, &mut iob).into()
     }
 
     /// This function is called by the C kernel. A pointer to this function is
@@ -178,13 +625,27 @@ impl<T: Operations> OperationsVTable<T> {
     ///
     /// This function may only be called by blk-mq C infrastructure. This
     /// function may only be called onece before `exit_hctx_callback` is called
-    /// for the same context.
+    /// for the same context. `tagset_data` must be the pointer stored by
+    /// `TagSet::new()` via `ForeignOwnable::into_foreign()`.
     unsafe extern "C" fn init_hctx_callback(
-        _hctx: *mut bindings::blk_mq_hw_ctx,
-        _tagset_data: *mut core::ffi::c_void,
-        _hctx_idx: core::ffi::c_uint,
+        hctx: *mut bindings::blk_mq_hw_ctx,
+        tagset_data: *mut core::ffi::c_void,
+        hctx_idx: core::ffi::c_uint,
     ) -> core::ffi::c_int {
-        from_result(|| Ok(0))
+        from_result(|| {
+            // SAFETY: `tagset_data` was created by `TagSet::new()` with a
+            // call to `ForeignOwnable::into_foreign()`.
+            // `ForeignOwnable::from_foreign()` is only called when the tag
+            // set is dropped, which happens after `hctx` is torn down.
+            let tagset_data = unsafe { T::TagSetData::borrow(tagset_data) };
+
+            let hctx_data = T::init_hctx(tagset_data, hctx_idx as u32)?;
+
+            // SAFETY: `hctx` is valid as required by this function.
+            unsafe { (*hctx).driver_data = hctx_data.into_foreign().cast_mut() };
+
+            Ok(0)
+        })
     }
 
     /// This function is called by the C kernel. A pointer to this function is
@@ -192,11 +653,21 @@ impl<T: Operations> OperationsVTable<T> {
     ///
     /// # Safety
     ///
-    /// This function may only be called by blk-mq C infrastructure.
+    /// This function may only be called by blk-mq C infrastructure. `hctx`
+    /// must have been set up by a prior call to `Self::init_hctx_callback`
+    /// that has not already been torn down.
     unsafe extern "C" fn exit_hctx_callback(
-        _hctx: *mut bindings::blk_mq_hw_ctx,
-        _hctx_idx: core::ffi::c_uint,
+        hctx: *mut bindings::blk_mq_hw_ctx,
+        hctx_idx: core::ffi::c_uint,
     ) {
+        // SAFETY: `hctx` is valid as required by this function.
+        let hctx_data = unsafe { (*hctx).driver_data };
+
+        // SAFETY: `hctx_data` was created by `Self::init_hctx_callback` with
+        // a call to `ForeignOwnable::into_foreign()`.
+        let hctx_data = unsafe { T::HctxData::from_foreign(hctx_data) };
+
+        T::exit_hctx(hctx_data, hctx_idx as u32)
     }
 
     /// This function is called by the C kernel. A pointer to this function is
@@ -221,6 +692,10 @@ impl<T: Operations> OperationsVTable<T> {
             // valid for write.
             unsafe { RequestDataWrapper::refcount_ptr(pdu).write(AtomicU64::new(0)) };
 
+            // SAFETY: The budget_token field is allocated but not
+            // initialized, this is valid for write.
+            unsafe { RequestDataWrapper::budget_token_ptr(pdu).write(AtomicI32::new(-1)) };
+
             Ok(0)
         })
     }
@@ -246,15 +721,88 @@ impl<T: Operations> OperationsVTable<T> {
         unsafe { core::ptr::drop_in_place(pdu) };
     }
 
+    /// This function is called by the C kernel. A pointer to this function is
+    /// installed in the `blk_mq_ops` vtable for the driver.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by blk-mq C infrastructure. The
+    /// caller must ensure that `set` is valid.
+    unsafe extern "C" fn map_queues_callback(set: *mut bindings::blk_mq_tag_set) {
+        // SAFETY: `set` is valid as required by this function.
+        let set = unsafe { &mut *set };
+
+        // SAFETY: `set` is valid for the duration of this call.
+        let mut tagset = unsafe { QueueMapSet::from_raw(set) };
+
+        T::map_queues(&mut tagset);
+    }
+
+    /// This function is called by the C kernel. A pointer to this function is
+    /// installed in the `blk_mq_ops` vtable for the driver.
+    ///
+    /// # Safety
+    ///
+    /// This function may only be called by blk-mq C infrastructure. The
+    /// caller must ensure that `m` and `rq` are valid.
+    #[cfg(CONFIG_BLK_DEBUG_FS)]
+    unsafe extern "C" fn show_rq_callback(m: *mut bindings::seq_file, rq: *mut bindings::request) {
+        // SAFETY: `rq` is valid as required by this function. We only borrow
+        // it, we do not take ownership of a refcount.
+        let rq = unsafe { &*rq.cast::<Request<T>>() };
+
+        // SAFETY: `m` is valid as required by this function. `m.buf` has
+        // `m.size - m.count` bytes free for write starting at
+        // `m.buf + m.count`.
+        let mut writer = unsafe {
+            RawWriter::from_buffer(
+                (*m).buf.cast::<u8>().add((*m).count),
+                (*m).size - (*m).count,
+            )
+        };
+
+        if T::show_rq(rq, &mut writer).is_ok() {
+            let written = ((*m).size - (*m).count) - writer.remaining();
+
+            // SAFETY: `writer` only ever advanced within the bounds handed to
+            // it above, so `m.count + written` is still within `m.buf`.
+            unsafe { (*m).count += written };
+        }
+    }
+
     const VTABLE: bindings::blk_mq_ops = bindings::blk_mq_ops {
         queue_rq: Some(Self::queue_rq_callback),
-        queue_rqs: None,
+        queue_rqs: if T::HAS_QUEUE_RQS {
+            Some(Self::queue_rqs_callback)
+        } else {
+            None
+        },
         commit_rqs: Some(Self::commit_rqs_callback),
-        get_budget: None,
-        put_budget: None,
-        set_rq_budget_token: None,
-        get_rq_budget_token: None,
-        timeout: None,
+        get_budget: if T::HAS_GET_BUDGET {
+            Some(Self::get_budget_callback)
+        } else {
+            None
+        },
+        put_budget: if T::HAS_PUT_BUDGET {
+            Some(Self::put_budget_callback)
+        } else {
+            None
+        },
+        set_rq_budget_token: if T::HAS_GET_BUDGET {
+            Some(Self::set_rq_budget_token_callback)
+        } else {
+            None
+        },
+        get_rq_budget_token: if T::HAS_GET_BUDGET {
+            Some(Self::get_rq_budget_token_callback)
+        } else {
+            None
+        },
+        timeout: if T::HAS_TIMEOUT {
+            Some(Self::timeout_callback)
+        } else {
+            None
+        },
         poll: if T::HAS_POLL {
             Some(Self::poll_callback)
         } else {
@@ -267,9 +815,17 @@ impl<T: Operations> OperationsVTable<T> {
         exit_request: Some(Self::exit_request_callback),
         cleanup_rq: None,
         busy: None,
-        map_queues: None,
+        map_queues: if T::HAS_MAP_QUEUES {
+            Some(Self::map_queues_callback)
+        } else {
+            None
+        },
         #[cfg(CONFIG_BLK_DEBUG_FS)]
-        show_rq: None,
+        show_rq: if T::HAS_SHOW_RQ {
+            Some(Self::show_rq_callback)
+        } else {
+            None
+        },
     };
 
     pub(crate) const fn build() -> &'static bindings::blk_mq_ops {
@@ -0,0 +1,173 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Queue limits, used to describe a block device's DMA and addressing
+//! constraints to the block layer before the device is allocated.
+//!
+//! C header: [`include/linux/blkdev.h`](srctree/include/linux/blkdev.h)
+
+use crate::{bindings, error::code::*, error::Result, PAGE_SIZE};
+
+/// A builder for `struct queue_limits`.
+///
+/// Accumulates the constraints a driver wants applied to a [`GenDisk`]'s
+/// request queue, validating each one as it is set, so that the limits are
+/// in force from the very first request the device can see instead of being
+/// patched in after the gendisk is already live.
+///
+/// [`GenDisk`]: crate::block::mq::GenDisk
+#[derive(Clone, Copy)]
+pub struct QueueLimits {
+    limits: bindings::queue_limits,
+}
+
+impl QueueLimits {
+    /// Create a new, empty set of queue limits.
+    ///
+    /// Any field not explicitly set keeps the C default of `0`, which the
+    /// block layer treats as "unconstrained" (or, for
+    /// `logical`/`physical_block_size`, is replaced with the kernel's
+    /// default of 512 bytes by `blk_validate_limits`).
+    pub fn new() -> Self {
+        // SAFETY: `queue_limits` only contains integers and a bitmap that are
+        // valid when zeroed.
+        let limits = unsafe { core::mem::zeroed() };
+        Self { limits }
+    }
+
+    /// Set the logical block size, in bytes.
+    ///
+    /// This is the smallest unit the storage device can address. It must be
+    /// a power of two between 512 and [`PAGE_SIZE`].
+    pub fn logical_block_size(mut self, size: u32) -> Result<Self> {
+        if !size.is_power_of_two() || !(512..=PAGE_SIZE as u32).contains(&size) {
+            return Err(EINVAL);
+        }
+        self.limits.logical_block_size = size;
+        Ok(self)
+    }
+
+    /// Set the physical block size, in bytes.
+    ///
+    /// This is the smallest unit the storage device can write atomically. It
+    /// is usually the same as the logical block size but may be bigger, and
+    /// must be a power of two between 512 and [`PAGE_SIZE`].
+    pub fn physical_block_size(mut self, size: u32) -> Result<Self> {
+        if !size.is_power_of_two() || !(512..=PAGE_SIZE as u32).contains(&size) {
+            return Err(EINVAL);
+        }
+        self.limits.physical_block_size = size;
+        Ok(self)
+    }
+
+    /// Set the maximum number of sectors the device can handle in a single
+    /// request.
+    pub fn max_hw_sectors(mut self, sectors: u32) -> Result<Self> {
+        if sectors == 0 {
+            return Err(EINVAL);
+        }
+        self.limits.max_hw_sectors = sectors;
+        Ok(self)
+    }
+
+    /// Set the maximum number of DMA segments a request may be split into.
+    pub fn max_segments(mut self, segments: u16) -> Result<Self> {
+        if segments == 0 {
+            return Err(EINVAL);
+        }
+        self.limits.max_segments = segments;
+        Ok(self)
+    }
+
+    /// Set the maximum size of a single DMA segment, in bytes.
+    pub fn max_segment_size(mut self, size: u32) -> Result<Self> {
+        if size == 0 {
+            return Err(EINVAL);
+        }
+        self.limits.max_segment_size = size;
+        Ok(self)
+    }
+
+    /// Set the minimum and optimal IO sizes, in bytes.
+    ///
+    /// `min` is the smallest IO the device can perform without incurring a
+    /// read-modify-write penalty; `opt` is the preferred IO size for
+    /// streaming workloads. Neither is validated against the other, as the
+    /// block layer treats `0` in either field as "unknown".
+    pub fn io_min_opt(mut self, min: u32, opt: u32) -> Self {
+        self.limits.io_min = min;
+        self.limits.io_opt = opt;
+        self
+    }
+
+    /// Set the discard granularity, in bytes.
+    ///
+    /// This is the smallest unit the device can discard; discard requests
+    /// are aligned and sized to multiples of this value.
+    pub fn discard_granularity(mut self, granularity: u32) -> Self {
+        self.limits.discard_granularity = granularity;
+        self
+    }
+
+    /// Set the size of the chunks the device is internally divided into, in
+    /// sectors (512B).
+    ///
+    /// Requests are never merged across a `chunk_sectors` boundary. `0`
+    /// (the default) means the device has no such internal boundary. For a
+    /// zoned device this is also the zone size, since zones are never
+    /// merged across either.
+    pub fn chunk_sectors(mut self, sectors: u32) -> Self {
+        self.limits.chunk_sectors = sectors;
+        self
+    }
+
+    /// Set the virtual DMA boundary mask.
+    ///
+    /// No single DMA segment may straddle a boundary where
+    /// `(addr & mask) == mask`. `0` (the default) means the device has no
+    /// such boundary.
+    pub fn virt_boundary_mask(mut self, mask: u64) -> Self {
+        self.limits.virt_boundary_mask = mask;
+        self
+    }
+
+    /// Set the segment boundary mask.
+    ///
+    /// No single DMA segment may straddle a boundary where
+    /// `(addr & mask) == mask`. Defaults to `u32::MAX` by the block layer if
+    /// left unset.
+    pub fn seg_boundary_mask(mut self, mask: u64) -> Self {
+        self.limits.seg_boundary_mask = mask;
+        self
+    }
+
+    /// Set the maximum number of zones that may simultaneously be in the
+    /// "open" condition (implicitly or explicitly), for a zoned device.
+    ///
+    /// `0` (the default) means the device has no such limit.
+    pub fn max_open_zones(mut self, zones: u32) -> Self {
+        self.limits.max_open_zones = zones;
+        self
+    }
+
+    /// Set the maximum number of zones that may simultaneously be "active"
+    /// (open, or explicitly finished but not yet reset), for a zoned
+    /// device.
+    ///
+    /// `0` (the default) means the device has no such limit.
+    pub fn max_active_zones(mut self, zones: u32) -> Self {
+        self.limits.max_active_zones = zones;
+        self
+    }
+
+    /// Return a pointer to the underlying `struct queue_limits`, for passing
+    /// to `__blk_mq_alloc_disk`.
+    pub(crate) fn as_ptr(&mut self) -> *mut bindings::queue_limits {
+        &mut self.limits
+    }
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
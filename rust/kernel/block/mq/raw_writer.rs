@@ -13,6 +13,22 @@ impl RawWriter {
     pub(crate) fn from_array<const N: usize>(a: &mut [core::ffi::c_char; N]) -> Self {
         unsafe { Self::new(&mut a[0] as *mut _ as _, N) }
     }
+
+    /// Create a `RawWriter` over a raw buffer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes of `len` bytes for the lifetime of the
+    /// returned `RawWriter`.
+    pub(crate) unsafe fn from_buffer(ptr: *mut u8, len: usize) -> Self {
+        unsafe { Self::new(ptr, len) }
+    }
+
+    /// The number of bytes that can still be written before the buffer is
+    /// exhausted.
+    pub(crate) fn remaining(&self) -> usize {
+        self.len
+    }
 }
 
 impl Write for RawWriter {
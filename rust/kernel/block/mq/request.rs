@@ -8,8 +8,9 @@ use kernel::hrtimer::RawTimer;
 
 use crate::{
     bindings,
+    block::bio::{bvec_iter_len, bvec_iter_offset, bvec_iter_page, Bio, BioIterator, MappedSegment, Segment},
     block::mq::Operations,
-    error::{Error, Result},
+    error::{code::*, Error, Result},
     hrtimer::{HasTimer, TimerCallback},
     types::{ARef, AlwaysRefCounted, Opaque},
 };
@@ -17,7 +18,7 @@ use core::{
     ffi::c_void,
     marker::PhantomData,
     ptr::{addr_of_mut, NonNull},
-    sync::atomic::{AtomicU64, Ordering},
+    sync::atomic::{AtomicI32, AtomicU64, Ordering},
 };
 
 /// A wrapper around a blk-mq `struct request`. This represents an IO request.
@@ -161,6 +162,125 @@ impl<T: Operations> Request<T> {
         unsafe { (*self.0.get()).__sector as usize }
     }
 
+    /// Returns `true` if this is a `REQ_OP_FLUSH` request.
+    ///
+    /// A flush request carries no data of its own; it asks the driver to
+    /// flush its volatile write cache (and, combined with FUA, to make any
+    /// already-acknowledged writes durable) before the request is
+    /// completed. Only sent to devices whose write cache was enabled
+    /// through
+    /// [`GenDisk::set_write_cache`](crate::block::mq::GenDisk::set_write_cache).
+    #[inline(always)]
+    pub fn is_flush(&self) -> bool {
+        self.command() == bindings::REQ_OP_FLUSH
+    }
+
+    /// Record the sector a `REQ_OP_ZONE_APPEND` request was actually
+    /// written at.
+    ///
+    /// A zone-append bio is built with [`Self::sector`] pointing at the
+    /// start of its target zone, since the device alone chooses the
+    /// zone's write pointer. Drivers handling zone-append commands must
+    /// call this with the sector the device reported in its completion
+    /// before completing the request, so the issuer of the append can
+    /// learn where its data landed.
+    #[inline(always)]
+    pub fn set_zone_append_sector(&self, sector: u64) {
+        // SAFETY: By type invariant of `Self`, `self.0` is valid and live.
+        // The block layer does not read `__sector` again until this
+        // request is completed, which the caller has not done yet.
+        unsafe { (*self.0.get()).__sector = sector as _ };
+    }
+
+    /// Returns an iterator over the bios attached to this request.
+    #[inline(always)]
+    pub fn bio_iter(&self) -> BioIterator<'_> {
+        BioIterator {
+            // SAFETY: By type invariant of `Self`, `self.0` is valid and
+            // live, so `bio` is a valid (possibly null) `struct bio`
+            // pointer, live for as long as `self`.
+            bio: unsafe { Bio::from_raw((*self.0.get()).bio) },
+        }
+    }
+
+    /// Returns an iterator over the mapped payload segments of this
+    /// request, walking every attached bio's segments in turn.
+    ///
+    /// Whether the driver should read or write through the yielded
+    /// [`MappedSegment`]s depends on [`Self::command`]'s data direction.
+    #[inline(always)]
+    pub fn payload_iter(&self) -> RequestSegmentIterator<'_, T> {
+        RequestSegmentIterator::new(self.bio_iter())
+    }
+
+    /// Returns the number of physical segments this request's payload
+    /// spans, as computed by the block layer.
+    #[inline(always)]
+    pub fn nr_phys_segments(&self) -> u16 {
+        // SAFETY: By type invariant of `Self`, `self.0` is a valid and live
+        // `struct request`.
+        unsafe { bindings::blk_rq_nr_phys_segments(self.0.get()) }
+    }
+
+    /// Returns the total number of bytes of data carried by this request.
+    #[inline(always)]
+    pub fn data_len(&self) -> u32 {
+        // SAFETY: By type invariant of `Self`, `self.0` is valid and live.
+        unsafe { (*self.0.get()).__data_len }
+    }
+
+    /// Populates `sg` with a scatter-gather list describing this request's
+    /// payload and returns the number of entries written.
+    ///
+    /// `sg` must have room for at least [`Self::nr_phys_segments`] entries.
+    pub fn build_sg_list(&self, sg: &mut [bindings::scatterlist]) -> Result<usize> {
+        if sg.len() < self.nr_phys_segments() as usize {
+            return Err(EINVAL);
+        }
+
+        // SAFETY: By type invariant of `Self`, `self.0` is a valid and live
+        // `struct request`, and `sg` has room for at least
+        // `blk_rq_nr_phys_segments(self.0)` entries, which is the documented
+        // contract of `blk_rq_map_sg`.
+        let nr_mapped =
+            unsafe { bindings::blk_rq_map_sg((*self.0.get()).q, self.0.get(), sg.as_mut_ptr()) };
+
+        Ok(nr_mapped as usize)
+    }
+
+    /// Maps a scatter-gather list built by [`Self::build_sg_list`] for this
+    /// request for DMA through `dev`, returning a guard that undoes the
+    /// mapping when dropped.
+    ///
+    /// # Safety
+    ///
+    /// `dev` must be a valid, initialized `struct device` capable of DMA.
+    /// `sg` and `nents` must be the list and entry count returned by a
+    /// matching call to [`Self::build_sg_list`] on this same request.
+    pub unsafe fn dma_map_sg<'b>(
+        &self,
+        dev: *mut bindings::device,
+        sg: &'b mut [bindings::scatterlist],
+        nents: usize,
+        direction: bindings::dma_data_direction,
+    ) -> Result<RequestSgMapping<'b>> {
+        // SAFETY: By the safety requirements of this function, `dev` is a
+        // valid DMA-capable device and `sg`/`nents` describe a valid
+        // scatter-gather list.
+        let mapped =
+            unsafe { bindings::dma_map_sg(dev, sg.as_mut_ptr(), nents as i32, direction) };
+        if mapped == 0 {
+            return Err(EIO);
+        }
+
+        Ok(RequestSgMapping {
+            dev,
+            sg,
+            nents,
+            direction,
+        })
+    }
+
     /// Return a pointer to the `RequestDataWrapper` stored in the private area
     /// of the request structure.
     ///
@@ -194,6 +314,263 @@ impl<T: Operations> Request<T> {
     }
 }
 
+/// A list of requests handed to [`Operations::queue_rqs`] by the block layer.
+///
+/// The driver pops the requests it can commit from the front of the list.
+/// Any requests left in the list when [`Operations::queue_rqs`] returns are
+/// requeued or failed by the block layer through the ordinary `queue_rq`
+/// path, per the C `blk_mq_ops::queue_rqs` contract.
+///
+/// [`Operations::queue_rqs`]: crate::block::mq::Operations::queue_rqs
+pub struct RequestList<'a, T: Operations> {
+    rq_list: &'a mut bindings::rq_list,
+    _p: PhantomData<T>,
+}
+
+impl<'a, T: Operations> RequestList<'a, T> {
+    /// Create a `RequestList` from a raw `struct rq_list`.
+    ///
+    /// # Safety
+    ///
+    /// - `rq_list` must be valid for the duration of `'a`.
+    /// - Every `struct request` reachable from `rq_list` must satisfy the
+    ///   type invariants of `Request<T>`, and must not have been started or
+    ///   handed out as an `ARef` yet.
+    pub(crate) unsafe fn from_raw(rq_list: &'a mut bindings::rq_list) -> Self {
+        Self {
+            rq_list,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Operations> Iterator for RequestList<'a, T> {
+    type Item = ARef<Request<T>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: By the type invariants of `Self`, `self.rq_list` is a valid
+        // `struct rq_list`.
+        let rq = unsafe { bindings::rq_list_pop(self.rq_list) };
+        if rq.is_null() {
+            return None;
+        }
+
+        // SAFETY: `rq` was just popped from `self.rq_list`, so by the safety
+        // requirements of `Self::from_raw` it is a valid `Request<T>` that
+        // has not yet been started or handed out as an `ARef`.
+        let request = unsafe { &*rq.cast::<Request<T>>() };
+
+        // One refcount for the `ARef`, one for being in flight.
+        request.wrapper_ref().refcount().store(2, Ordering::Relaxed);
+
+        let aref =
+            // SAFETY: We own a refcount that we took above. We pass that to `ARef`.
+            unsafe { Request::aref_from_raw(rq) };
+
+        // SAFETY: We have exclusive access and we just set the refcount above.
+        unsafe { Request::start_unchecked(&aref) };
+
+        Some(aref)
+    }
+}
+
+/// An iterator over the mapped payload segments of a [`Request`], across all
+/// of its attached bios.
+///
+/// Returned by [`Request::payload_iter`].
+pub struct RequestSegmentIterator<'a, T: Operations> {
+    bio_iter: BioIterator<'a>,
+    current: Option<Bio<'a>>,
+    iter: bindings::bvec_iter,
+    _p: PhantomData<T>,
+}
+
+impl<'a, T: Operations> RequestSegmentIterator<'a, T> {
+    fn new(mut bio_iter: BioIterator<'a>) -> Self {
+        let current = bio_iter.next();
+        let iter = current.as_ref().map_or_else(
+            // SAFETY: A zeroed `bvec_iter` has `bi_size == 0`, so `next()`
+            // will immediately report this request as having no payload.
+            || unsafe { core::mem::zeroed() },
+            |bio| bio.iter(),
+        );
+        Self {
+            bio_iter,
+            current,
+            iter,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Operations> Iterator for RequestSegmentIterator<'a, T> {
+    type Item = MappedSegment<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let bio = self.current.as_ref()?;
+
+            if self.iter.bi_size == 0 {
+                self.current = self.bio_iter.next();
+                self.iter = match self.current.as_ref() {
+                    Some(bio) => bio.iter(),
+                    None => return None,
+                };
+                continue;
+            }
+
+            // SAFETY: `bio.io_vec()`/`bio.get_raw()` return pointers that
+            // are valid for as long as `bio` is, and `self.iter` describes a
+            // position within that `bio`'s `bio_vec` array.
+            let bio_vec = bindings::bio_vec {
+                bv_page: bvec_iter_page(bio.io_vec(), &self.iter),
+                bv_len: bvec_iter_len(bio.io_vec(), &self.iter),
+                bv_offset: bvec_iter_offset(bio.io_vec(), &self.iter),
+            };
+
+            // SAFETY: `bio.get_raw()` is a valid `struct bio` and
+            // `self.iter` is its current iteration state.
+            unsafe {
+                bindings::bio_advance_iter_single(
+                    bio.get_raw(),
+                    &mut self.iter as *mut bindings::bvec_iter,
+                    bio_vec.bv_len,
+                )
+            };
+
+            // SAFETY: `bio_vec.bv_page` is a page owned by `bio`, which
+            // outlives `'a`, and `bv_offset`/`bv_len` were computed by the
+            // page-clamping `bvec_iter_*` helpers above.
+            return Some(unsafe { Segment::from_raw(bio_vec) }.map_local());
+        }
+    }
+}
+
+/// A guard for a scatter-gather list mapped for DMA by
+/// [`Request::dma_map_sg`].
+///
+/// Unmaps the list when dropped.
+pub struct RequestSgMapping<'a> {
+    dev: *mut bindings::device,
+    sg: &'a mut [bindings::scatterlist],
+    nents: usize,
+    direction: bindings::dma_data_direction,
+}
+
+impl RequestSgMapping<'_> {
+    /// Returns the scatter-gather list, for handing to a DMA-capable
+    /// device's descriptor setup.
+    pub fn sg_list(&self) -> &[bindings::scatterlist] {
+        self.sg
+    }
+}
+
+impl Drop for RequestSgMapping<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `self.dev`/`self.sg`/`self.nents`/`self.direction` are
+        // exactly the values passed to the matching `dma_map_sg` call in
+        // `Request::dma_map_sg`.
+        unsafe {
+            bindings::dma_unmap_sg(
+                self.dev,
+                self.sg.as_mut_ptr(),
+                self.nents as i32,
+                self.direction,
+            )
+        };
+    }
+}
+
+/// A safe wrapper over `struct io_comp_batch`, handed to
+/// [`Operations::poll`].
+///
+/// Drivers push requests they have observed as completed during a poll
+/// sweep onto the batch; the block layer completes them all together once
+/// [`Operations::poll`] returns, amortizing the completion cost across the
+/// batch.
+///
+/// [`Operations::poll`]: crate::block::mq::Operations::poll
+pub struct IoCompletionBatch<'a, T: Operations> {
+    iob: &'a mut bindings::io_comp_batch,
+    _p: PhantomData<T>,
+}
+
+impl<'a, T: Operations> IoCompletionBatch<'a, T> {
+    /// Create an `IoCompletionBatch` from a raw `struct io_comp_batch`.
+    ///
+    /// # Safety
+    ///
+    /// `iob` must be valid for the duration of `'a`.
+    pub(crate) unsafe fn from_raw(iob: &'a mut bindings::io_comp_batch) -> Self {
+        Self {
+            iob,
+            _p: PhantomData,
+        }
+    }
+
+    /// Add `rq` to the completion batch, consuming it.
+    ///
+    /// This performs the same exclusive-ownership check as [`Request::end`]:
+    /// if `rq` is not the only `ARef` referencing the request, it is handed
+    /// back unchanged and the batch is not touched.
+    ///
+    /// On success, `rq` is now owned by the batch and will be completed
+    /// with status `io_error` when the batch is flushed. On failure (for
+    /// example if the batch does not accept requests with this completion
+    /// shape), `rq` is handed back and the driver must complete it through
+    /// another path, such as [`Request::complete`].
+    pub fn add(&mut self, rq: ARef<Request<T>>, io_error: i32) -> Result<(), ARef<Request<T>>> {
+        let rq = Request::try_set_end(rq)?;
+        let raw_rq = rq.into_raw().cast::<bindings::request>();
+
+        // SAFETY: `raw_rq` is a valid request for which we just leaked the
+        // refcount we owned through `rq`, and `self.iob` is a valid
+        // `io_comp_batch` for the duration of the current `poll` call.
+        let added = unsafe {
+            bindings::blk_mq_add_to_batch(
+                raw_rq,
+                self.iob,
+                io_error,
+                Some(Self::complete_batch_callback),
+            )
+        };
+
+        if added {
+            Ok(())
+        } else {
+            // The batch did not accept the request, so the `try_set_end`
+            // transition above must be undone before handing the request
+            // back: the caller will complete it through another path, which
+            // expects to find the request still exclusively owned by Rust
+            // (refcount 2), not already handed off to C (refcount 0).
+            //
+            // SAFETY: `raw_rq` is a valid request whose private data we are
+            // permitted to dereference.
+            let wrapper_ptr = unsafe { Request::<T>::wrapper_ptr(raw_rq.cast()) };
+            // SAFETY: `wrapper_ptr` points to the live `RequestDataWrapper`
+            // embedded in `raw_rq`.
+            unsafe { wrapper_ptr.as_ref() }
+                .refcount()
+                .store(2, Ordering::Relaxed);
+
+            // SAFETY: The request was not accepted into the batch, so we
+            // still own the refcount we leaked above, which we have just
+            // restored to the exclusive-ownership state, and can reclaim it.
+            Err(unsafe { Request::aref_from_raw(raw_rq) })
+        }
+    }
+
+    /// # Safety
+    ///
+    /// May only be called by the block layer when flushing a completion
+    /// batch built through [`Self::add`].
+    unsafe extern "C" fn complete_batch_callback(iob: *mut bindings::io_comp_batch) {
+        // SAFETY: The block layer guarantees `iob` is valid when flushing a
+        // completion batch.
+        unsafe { bindings::blk_mq_end_request_batch(iob) };
+    }
+}
+
 /// A wrapper around data stored in the private area of the C `struct request`.
 pub(crate) struct RequestDataWrapper<T: Operations> {
     /// The Rust request refcount has the following states:
@@ -203,6 +580,12 @@ pub(crate) struct RequestDataWrapper<T: Operations> {
     /// - 2+: There are `ARef` references to the request.
     refcount: AtomicU64,
 
+    /// The dispatch budget token obtained from `Operations::get_budget`, or
+    /// `-1` if none is currently held. Stashed here so the block layer's
+    /// `get_rq_budget_token`/`set_rq_budget_token` hooks can recover it
+    /// without the driver having to track it itself.
+    budget_token: AtomicI32,
+
     /// Driver managed request data
     data: T::RequestData,
 }
@@ -226,6 +609,18 @@ impl<T: Operations> RequestDataWrapper<T> {
         unsafe { addr_of_mut!((*this).refcount) }
     }
 
+    /// Return a pointer to the budget token of the request that is embedding
+    /// the pointee of `this`.
+    ///
+    /// # Safety
+    ///
+    /// - `this` must point to a live allocation of at least the size of `Self`.
+    pub(crate) unsafe fn budget_token_ptr(this: *mut Self) -> *mut AtomicI32 {
+        // SAFETY: Because of the safety requirements of this function, the
+        // field projection is safe.
+        unsafe { addr_of_mut!((*this).budget_token) }
+    }
+
     /// Return a pointer to the `data` field of the `Self` pointed to by `this`.
     ///
     /// # Safety
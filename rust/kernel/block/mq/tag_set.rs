@@ -34,11 +34,16 @@ pub struct TagSet<T: Operations> {
 
 impl<T: Operations> TagSet<T> {
     /// Try to create a new tag set
+    ///
+    /// `config` carries the tunables the block layer exposes beyond the request queue's
+    /// structural shape -- command timeout, `blk_mq_tag_set` flags, and NUMA affinity; see
+    /// [`TagSetConfig`].
     pub fn new(
         nr_hw_queues: u32,
         tagset_data: T::TagSetData,
         num_tags: u32,
         num_maps: u32,
+        config: TagSetConfig,
     ) -> impl PinInit<Self, error::Error> {
         // SAFETY: `blk_mq_tag_set` only contains integers and pointers, which
         // all are allowed to be 0.
@@ -49,11 +54,11 @@ impl<T: Operations> TagSet<T> {
                 bindings::blk_mq_tag_set {
                     ops: OperationsVTable::<T>::build(),
                     nr_hw_queues,
-                    timeout: 0, // 0 means default which is 30Hz in C
-                    numa_node: bindings::NUMA_NO_NODE,
+                    timeout: config.timeout_jiffies,
+                    numa_node: config.numa_node,
                     queue_depth: num_tags,
                     cmd_size,
-                    flags: bindings::BLK_MQ_F_SHOULD_MERGE,
+                    flags: config.flags,
                     driver_data: tagset_data.into_foreign().cast_mut(),
                     nr_maps: num_maps,
                     ..tag_set
@@ -95,6 +100,148 @@ impl<T: Operations> TagSet<T> {
     }
 }
 
+/// A builder for the tunables of a [`TagSet`] beyond its structural shape (hardware queue,
+/// tag and queue-map counts), applied when the underlying `blk_mq_tag_set` is allocated.
+///
+/// Any knob left unset keeps the block layer's own default.
+#[derive(Clone, Copy)]
+pub struct TagSetConfig {
+    flags: u32,
+    numa_node: core::ffi::c_int,
+    timeout_jiffies: u32,
+}
+
+impl TagSetConfig {
+    /// Create a new configuration with the block layer's defaults: request merging and the
+    /// IO scheduler enabled, no NUMA affinity, and the C default command timeout (30s).
+    pub fn new() -> Self {
+        Self {
+            flags: bindings::BLK_MQ_F_SHOULD_MERGE,
+            numa_node: bindings::NUMA_NO_NODE,
+            timeout_jiffies: 0,
+        }
+    }
+
+    /// Set the command timeout, in jiffies, applied to every request dispatched through
+    /// this tag set. `0` keeps the C default (30s).
+    pub fn timeout_jiffies(mut self, timeout_jiffies: u32) -> Self {
+        self.timeout_jiffies = timeout_jiffies;
+        self
+    }
+
+    /// Disable request merging and the IO scheduler for this tag set's queues.
+    pub fn no_merge(mut self) -> Self {
+        self.flags &= !bindings::BLK_MQ_F_SHOULD_MERGE;
+        self.flags |= bindings::BLK_MQ_F_NO_SCHED;
+        self
+    }
+
+    /// Mark this tag set's [`Operations`] as blocking, i.e. allowed to sleep in
+    /// [`Operations::queue_rq`].
+    pub fn blocking(mut self) -> Self {
+        self.flags |= bindings::BLK_MQ_F_BLOCKING;
+        self
+    }
+
+    /// Pin this tag set's memory allocations to NUMA node `node`, instead of the default of
+    /// no affinity.
+    pub fn numa_node(mut self, node: core::ffi::c_int) -> Self {
+        self.numa_node = node;
+        self
+    }
+}
+
+impl Default for TagSetConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The kind of hardware queue a [`QueueMap`] routes CPUs into.
+///
+/// This mirrors the C `enum hctx_type`.
+#[derive(Clone, Copy)]
+pub enum HctxType {
+    /// The default hardware queues, used for most IO.
+    Default,
+    /// Hardware queues dedicated to read requests.
+    Read,
+    /// Hardware queues used for polled IO.
+    Poll,
+}
+
+impl HctxType {
+    fn index(self) -> usize {
+        match self {
+            HctxType::Default => bindings::hctx_type_HCTX_TYPE_DEFAULT as usize,
+            HctxType::Read => bindings::hctx_type_HCTX_TYPE_READ as usize,
+            HctxType::Poll => bindings::hctx_type_HCTX_TYPE_POLL as usize,
+        }
+    }
+}
+
+/// A safe, mutable view over one `struct blk_mq_queue_map` within a
+/// `blk_mq_tag_set`, handed to [`Operations::map_queues`].
+///
+/// [`Operations::map_queues`]: crate::block::mq::Operations::map_queues
+pub struct QueueMap<'a> {
+    map: &'a mut bindings::blk_mq_queue_map,
+}
+
+impl<'a> QueueMap<'a> {
+    /// The number of hardware queues available for this queue type.
+    pub fn nr_queues(&self) -> u32 {
+        self.map.nr_queues
+    }
+
+    /// Route `cpu` to hardware queue `hctx_idx` for this queue type.
+    pub fn set(&mut self, cpu: u32, hctx_idx: u32) {
+        // SAFETY: `mq_map` has `nr_cpu_ids` entries, and `cpu` is a CPU
+        // number, so it is in bounds.
+        unsafe { *self.map.mq_map.as_mut_ptr().add(cpu as usize) = hctx_idx };
+    }
+
+    /// Populate this queue type's mapping using the kernel's default
+    /// CPU-to-hardware-queue mapping algorithm.
+    pub fn map_queues(&mut self) {
+        // SAFETY: `self.map` is a valid `blk_mq_queue_map`.
+        unsafe { bindings::blk_mq_map_queues(self.map) };
+    }
+}
+
+/// A safe, mutable view over a `blk_mq_tag_set`'s queue maps, handed to
+/// [`Operations::map_queues`].
+///
+/// [`Operations::map_queues`]: crate::block::mq::Operations::map_queues
+pub struct QueueMapSet<'a> {
+    set: &'a mut bindings::blk_mq_tag_set,
+}
+
+impl<'a> QueueMapSet<'a> {
+    /// Create a `QueueMapSet` from a raw `struct blk_mq_tag_set`.
+    ///
+    /// # Safety
+    ///
+    /// `set` must be valid for the duration of `'a`.
+    pub(crate) unsafe fn from_raw(set: &'a mut bindings::blk_mq_tag_set) -> Self {
+        Self { set }
+    }
+
+    /// Return the queue map for `ty`, or `None` if the tag set was not
+    /// configured with enough queue maps (`nr_maps`) to reach it.
+    pub fn get(&mut self, ty: HctxType) -> Option<QueueMap<'_>> {
+        let index = ty.index();
+        if index >= self.set.nr_maps as usize {
+            return None;
+        }
+
+        // SAFETY: `index` was just checked to be in bounds of `self.set.map`.
+        let map = unsafe { &mut *self.set.map.as_mut_ptr().add(index) };
+
+        Some(QueueMap { map })
+    }
+}
+
 #[pinned_drop]
 impl<T: Operations> PinnedDrop for TagSet<T> {
     fn drop(self: Pin<&mut Self>) {
@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Types for zoned (host-managed/host-aware) block devices.
+//!
+//! C header: [`include/linux/blkdev.h`](srctree/include/linux/blkdev.h)
+//! C header: [`include/uapi/linux/blkzoned.h`](srctree/include/uapi/linux/blkzoned.h)
+
+use crate::{bindings, error::to_result, error::Result};
+use core::marker::PhantomData;
+
+/// The zone model of a zoned block device.
+///
+/// Mirrors the C `enum blk_zoned_model`, minus `BLK_ZONED_NONE`: a disk on
+/// which [`GenDisk::set_zoned`] is never called is not zoned.
+///
+/// [`GenDisk::set_zoned`]: crate::block::mq::GenDisk::set_zoned
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ZonedModel {
+    /// The device only accepts sequential writes within a zone; random
+    /// writes are rejected.
+    HostManaged,
+    /// The device reports zone information but still accepts random
+    /// writes anywhere on the device.
+    HostAware,
+}
+
+impl ZonedModel {
+    pub(crate) fn as_raw(self) -> bindings::blk_zoned_model {
+        match self {
+            ZonedModel::HostManaged => bindings::blk_zoned_model_BLK_ZONED_HM,
+            ZonedModel::HostAware => bindings::blk_zoned_model_BLK_ZONED_HA,
+        }
+    }
+}
+
+/// The type of a single zone, mirroring the C `enum blk_zone_type`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ZoneType {
+    /// A conventional zone, which accepts writes at any offset.
+    Conventional,
+    /// A zone that requires writes to land at its current write pointer.
+    SeqWriteRequired,
+    /// A zone that prefers, but does not require, sequential writes.
+    SeqWritePreferred,
+}
+
+impl ZoneType {
+    fn as_raw(self) -> u8 {
+        match self {
+            ZoneType::Conventional => bindings::blk_zone_type_BLK_ZONE_TYPE_CONVENTIONAL as u8,
+            ZoneType::SeqWriteRequired => {
+                bindings::blk_zone_type_BLK_ZONE_TYPE_SEQWRITE_REQ as u8
+            }
+            ZoneType::SeqWritePreferred => {
+                bindings::blk_zone_type_BLK_ZONE_TYPE_SEQWRITE_PREF as u8
+            }
+        }
+    }
+}
+
+/// The condition of a single zone, mirroring the C `enum blk_zone_cond`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ZoneCondition {
+    /// Not a write-pointer zone (i.e. a conventional zone).
+    NotWp,
+    /// The zone is empty.
+    Empty,
+    /// The zone has been opened implicitly by a write.
+    ImplicitOpen,
+    /// The zone has been opened explicitly, e.g. by a Zone Open command.
+    ExplicitOpen,
+    /// The zone has been closed after being opened.
+    Closed,
+    /// The zone is read-only.
+    Readonly,
+    /// The zone is full and accepts no further writes.
+    Full,
+    /// The zone is offline and cannot be used.
+    Offline,
+}
+
+impl ZoneCondition {
+    fn as_raw(self) -> u8 {
+        (match self {
+            ZoneCondition::NotWp => bindings::blk_zone_cond_BLK_ZONE_COND_NOT_WP,
+            ZoneCondition::Empty => bindings::blk_zone_cond_BLK_ZONE_COND_EMPTY,
+            ZoneCondition::ImplicitOpen => bindings::blk_zone_cond_BLK_ZONE_COND_IMP_OPEN,
+            ZoneCondition::ExplicitOpen => bindings::blk_zone_cond_BLK_ZONE_COND_EXP_OPEN,
+            ZoneCondition::Closed => bindings::blk_zone_cond_BLK_ZONE_COND_CLOSED,
+            ZoneCondition::Readonly => bindings::blk_zone_cond_BLK_ZONE_COND_READONLY,
+            ZoneCondition::Full => bindings::blk_zone_cond_BLK_ZONE_COND_FULL,
+            ZoneCondition::Offline => bindings::blk_zone_cond_BLK_ZONE_COND_OFFLINE,
+        }) as u8
+    }
+}
+
+/// One zone of a zoned block device, reported to the block layer by
+/// [`Operations::report_zones`] through a [`ReportZoneCb`].
+///
+/// Mirrors the fields of the C `struct blk_zone` that a driver is expected
+/// to fill in.
+///
+/// [`Operations::report_zones`]: crate::block::mq::Operations::report_zones
+#[derive(Clone, Copy)]
+pub struct Zone {
+    /// The zone's first sector.
+    pub start: u64,
+    /// The zone's length, in sectors.
+    pub len: u64,
+    /// The zone's write pointer, as a sector offset from the start of the
+    /// device. Meaningless for [`ZoneType::Conventional`] zones.
+    pub wp: u64,
+    /// The number of sectors usable for writes. May be smaller than `len`
+    /// for devices whose zone capacity is smaller than their zone size.
+    pub capacity: u64,
+    /// The zone's type.
+    pub kind: ZoneType,
+    /// The zone's current condition.
+    pub cond: ZoneCondition,
+}
+
+impl Zone {
+    fn to_raw(self) -> bindings::blk_zone {
+        // SAFETY: `blk_zone` only contains integers, which are valid when
+        // zeroed; every field we don't set below is reserved padding.
+        let mut raw: bindings::blk_zone = unsafe { core::mem::zeroed() };
+        raw.start = self.start;
+        raw.len = self.len;
+        raw.wp = self.wp;
+        raw.capacity = self.capacity;
+        raw.type_ = self.kind.as_raw();
+        raw.cond = self.cond.as_raw();
+        raw
+    }
+}
+
+/// A callback handed to [`Operations::report_zones`], used to report each
+/// zone the driver discovers back to the block layer, in order of
+/// increasing start sector.
+///
+/// [`Operations::report_zones`]: crate::block::mq::Operations::report_zones
+pub struct ReportZoneCb<'a> {
+    cb: bindings::report_zones_cb,
+    data: *mut core::ffi::c_void,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl ReportZoneCb<'_> {
+    /// Create a `ReportZoneCb` from a raw callback and opaque data pointer
+    /// handed down by the block layer.
+    ///
+    /// # Safety
+    ///
+    /// `cb`, if `Some`, must be valid to call with `data` for the duration
+    /// of `'a`.
+    pub(crate) unsafe fn from_raw(cb: bindings::report_zones_cb, data: *mut core::ffi::c_void) -> Self {
+        Self {
+            cb,
+            data,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Report zone number `idx` to the block layer.
+    pub fn report(&self, idx: u32, zone: Zone) -> Result {
+        let mut raw = zone.to_raw();
+
+        // SAFETY: By the safety requirements of `Self::from_raw`, `self.cb`
+        // is valid to call with `self.data` for as long as `self` is alive.
+        let ret = unsafe {
+            self.cb
+                .expect("ReportZoneCb constructed from a NULL callback")(
+                &mut raw, idx, self.data,
+            )
+        };
+        to_result(ret)
+    }
+}
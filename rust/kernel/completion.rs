@@ -0,0 +1,101 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! A one-shot completion signal between two contexts.
+//!
+//! C header: [`include/linux/completion.h`](srctree/include/linux/completion.h)
+
+use crate::{prelude::*, time::Ktime, types::Opaque};
+
+/// The outcome of [`Completion::wait`] or [`Completion::wait_timeout`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionResult {
+    /// The completion was signalled.
+    Completed,
+    /// The timeout elapsed before the completion was signalled.
+    TimedOut,
+    /// A signal became pending before the completion was signalled.
+    Interrupted,
+}
+
+/// A one-shot completion signal backed by a C `struct completion`.
+///
+/// A waiter blocks in [`Completion::wait`]/[`Completion::wait_timeout`] until another context
+/// (e.g. an [`hrtimer`](crate::hrtimer) callback) calls [`Completion::complete`], which wakes it
+/// directly instead of requiring it to busy-spin polling a flag.
+///
+/// # Invariants
+///
+/// `self.inner` is always initialized by `init_completion`.
+#[pin_data]
+pub struct Completion {
+    #[pin]
+    inner: Opaque<bindings::completion>,
+}
+
+// SAFETY: `complete`/`wait` are safe to call from any thread; synchronization is handled on the
+// C side.
+unsafe impl Send for Completion {}
+
+// SAFETY: See above.
+unsafe impl Sync for Completion {}
+
+impl Completion {
+    /// Returns an initializer for a new, not-yet-signalled `Completion`.
+    pub fn new() -> impl PinInit<Self> {
+        pin_init!(Self {
+            // INVARIANTS: We initialize `inner` with `init_completion` below.
+            inner <- Opaque::ffi_init(|place: *mut bindings::completion| {
+                // SAFETY: By design of `pin_init!`, `place` is a pointer to a live allocation,
+                // which `init_completion` will initialize.
+                unsafe { bindings::init_completion(place) };
+            }),
+        })
+    }
+
+    fn as_raw(&self) -> *mut bindings::completion {
+        self.inner.get()
+    }
+
+    /// Signal the completion, waking at most one waiter.
+    pub fn complete(&self) {
+        // SAFETY: `self.inner` is initialized by `init_completion`, per this struct's
+        // invariants.
+        unsafe { bindings::complete(self.as_raw()) };
+    }
+
+    /// Signal the completion, waking all current and future waiters.
+    pub fn complete_all(&self) {
+        // SAFETY: `self.inner` is initialized by `init_completion`, per this struct's
+        // invariants.
+        unsafe { bindings::complete_all(self.as_raw()) };
+    }
+
+    /// Block until the completion is signalled, or a signal becomes pending.
+    pub fn wait(&self) -> CompletionResult {
+        // SAFETY: `self.inner` is initialized by `init_completion`, per this struct's
+        // invariants.
+        let ret = unsafe { bindings::wait_for_completion_interruptible(self.as_raw()) };
+
+        if ret == 0 {
+            CompletionResult::Completed
+        } else {
+            CompletionResult::Interrupted
+        }
+    }
+
+    /// Block until the completion is signalled, `timeout` elapses, or a signal becomes pending.
+    pub fn wait_timeout(&self, timeout: Ktime) -> CompletionResult {
+        let jiffies = crate::time::msecs_to_jiffies(timeout.to_ms().max(0) as u64);
+
+        // SAFETY: `self.inner` is initialized by `init_completion`, per this struct's
+        // invariants.
+        let ret =
+            unsafe { bindings::wait_for_completion_interruptible_timeout(self.as_raw(), jiffies) };
+
+        match ret {
+            0 => CompletionResult::TimedOut,
+            ret if ret < 0 => CompletionResult::Interrupted,
+            _ => CompletionResult::Completed,
+        }
+    }
+}
@@ -4,10 +4,14 @@
 //!
 //! Features not covered:
 //!
-//! - Items. All group children are groups.
-//! - Symlink support.
+//! - Attributes on leaf items. [`Item`]s created via [`ItemOperations::make_item`] have no
+//!   attributes of their own; only [`Group`]s can expose `configfs_attrs!`-declared attributes.
+//! - Symlink support between [`Item`]s. [`SymlinkOperations`] only supports symlinks whose
+//!   source and target are both [`Group`]s.
 //! - `disconnect_notify` hook.
-//! - Item `release` hook
+//! - `commit_item`. This was part of the original `configfs` design but was removed from the C
+//!   API long ago; [`Group::add_default_group`] (wrapping `configfs_add_default_group`) is the
+//!   current upstream mechanism for a "pending configuration plus a default subgroup" layout.
 //!
 //! See [the samples folder] for an example.
 //!
@@ -30,6 +34,7 @@ use core::ptr::addr_of_mut;
 use kernel::alloc::flags;
 use kernel::str::CString;
 use kernel::sync::Arc;
+use kernel::sync::{CondVar, Mutex};
 
 /// A `configfs` subsystem.
 ///
@@ -163,6 +168,60 @@ where
             _p: PhantomData,
         })
     }
+
+    fn as_raw(&self) -> *mut bindings::config_group {
+        self.group.get()
+    }
+
+    /// Adds `child` as a default subgroup of `self`.
+    ///
+    /// Default subgroups appear in `configfs` the moment `self`'s directory is created, without
+    /// requiring a `mkdir(2)` through [`GroupOperations::make_group`] — the classic "pending
+    /// configuration plus a committed default subgroup" `configfs` layout.
+    ///
+    /// Must be called after both `self` and `child` have reached their final pinned location
+    /// (e.g. from the container's [`PinInit`] closure, via [`pin_init!`]'s `chain`), and before
+    /// `self` itself is registered with `configfs` (via [`Subsystem::register`] or by being
+    /// returned from a [`GroupOperations::make_group`]).
+    pub fn add_default_group<CHLD>(&self, child: &Group<CHLD>) {
+        // SAFETY: `self` and `child` are both live, pinned `config_group`s, per this function's
+        // safety requirements documented above.
+        unsafe { bindings::configfs_add_default_group(child.as_raw(), self.as_raw()) };
+    }
+}
+
+/// A `configfs` item.
+///
+/// Unlike [`Group`], an item has no subdirectories of its own. To add a leaf item to `configfs`,
+/// embed a field of this type into a struct and use it for the `ITEM` generic of
+/// [`ItemOperations`].
+#[pin_data]
+#[repr(transparent)]
+pub struct Item<C> {
+    #[pin]
+    item: Opaque<bindings::config_item>,
+    _p: PhantomData<C>,
+}
+
+impl<C> Item<C>
+where
+    C: 'static,
+{
+    /// Create an initializer for a new item.
+    ///
+    /// When instantiated, the item will appear as a directory with the name given by `name`,
+    /// but unlike a [`Group`], it cannot itself contain further subdirectories.
+    pub fn new(name: CString, item_type: &'static ItemType<C>) -> impl PinInit<Self> {
+        pin_init!(Self {
+            item <- kernel::init::zeroed().chain(|v: &mut Opaque<bindings::config_item>| {
+                let place = v.get();
+                let name = name.as_bytes_with_nul().as_ptr();
+                unsafe { bindings::config_item_init_type_name(place, name as _, item_type.as_ptr()) }
+                Ok(())
+            }),
+            _p: PhantomData,
+        })
+    }
 }
 
 struct GroupOperationsVTable<PAR, PPTR, CHLD, CPTR, PCPTR>(
@@ -230,13 +289,47 @@ where
         drop(child);
     }
 
+    unsafe extern "C" fn is_visible(
+        item: *mut bindings::config_item,
+        _attr: *mut bindings::configfs_attribute,
+        n: kernel::ffi::c_int,
+    ) -> bindings::umode_t {
+        let c_group: *mut bindings::config_group = item.cast();
+        let r_group_ptr: *mut Group<PAR> = c_group.cast();
+        let container_ptr = unsafe { PAR::container_ptr(r_group_ptr) };
+        let container_ref = unsafe { PPTR::borrow(container_ptr) };
+
+        PAR::is_visible(container_ref, n).unwrap_or(0)
+    }
+
+    unsafe extern "C" fn is_bin_visible(
+        item: *mut bindings::config_item,
+        _attr: *mut bindings::configfs_bin_attribute,
+        n: kernel::ffi::c_int,
+    ) -> bindings::umode_t {
+        let c_group: *mut bindings::config_group = item.cast();
+        let r_group_ptr: *mut Group<PAR> = c_group.cast();
+        let container_ptr = unsafe { PAR::container_ptr(r_group_ptr) };
+        let container_ref = unsafe { PPTR::borrow(container_ptr) };
+
+        PAR::is_bin_visible(container_ref, n).unwrap_or(0)
+    }
+
     const VTABLE: bindings::configfs_group_operations = bindings::configfs_group_operations {
         make_item: None,
         make_group: Some(Self::make_group),
         disconnect_notify: None,
         drop_item: Some(Self::drop_item),
-        is_visible: None,
-        is_bin_visible: None,
+        is_visible: if PAR::HAS_IS_VISIBLE {
+            Some(Self::is_visible)
+        } else {
+            None
+        },
+        is_bin_visible: if PAR::HAS_IS_BIN_VISIBLE {
+            Some(Self::is_bin_visible)
+        } else {
+            None
+        },
     };
 }
 
@@ -268,6 +361,226 @@ where
     fn drop_item(_this: PPTR::Borrowed<'_>, _child: PCPTR::Borrowed<'_>) {
         kernel::build_error!(kernel::error::VTABLE_DEFAULT_ERROR)
     }
+
+    /// The kernel will call this method to decide whether the attribute at index `n` in `this`'s
+    /// [`AttributeList`] should be shown.
+    ///
+    /// Returning `None` hides the attribute; returning `Some(mode)` shows it with that file mode.
+    fn is_visible(_this: PPTR::Borrowed<'_>, _n: kernel::ffi::c_int) -> Option<bindings::umode_t> {
+        kernel::build_error!(kernel::error::VTABLE_DEFAULT_ERROR)
+    }
+
+    /// The kernel will call this method to decide whether the binary attribute at index `n` in
+    /// `this`'s [`BinAttributeList`] should be shown.
+    ///
+    /// Returning `None` hides the attribute; returning `Some(mode)` shows it with that file mode.
+    fn is_bin_visible(_this: PPTR::Borrowed<'_>, _n: kernel::ffi::c_int) -> Option<bindings::umode_t> {
+        kernel::build_error!(kernel::error::VTABLE_DEFAULT_ERROR)
+    }
+}
+
+struct ItemOperationsVTable<PAR, PPTR, ITEM, IPTR, PIPTR>(
+    PhantomData<(PAR, PPTR, ITEM, IPTR, PIPTR)>,
+)
+where
+    PAR: ItemOperations<PAR, PPTR, ITEM, IPTR, PIPTR> + HasGroup,
+    PPTR: ForeignOwnable<PointedTo = PAR>,
+    ITEM: HasItem,
+    IPTR: InPlaceInit<ITEM, PinnedSelf = PIPTR>,
+    PIPTR: ForeignOwnable<PointedTo = ITEM>;
+
+impl<PAR, PPTR, ITEM, IPTR, PIPTR> ItemOperationsVTable<PAR, PPTR, ITEM, IPTR, PIPTR>
+where
+    PAR: ItemOperations<PAR, PPTR, ITEM, IPTR, PIPTR> + HasGroup + 'static,
+    PPTR: ForeignOwnable<PointedTo = PAR>,
+    ITEM: HasItem + 'static,
+    IPTR: InPlaceInit<ITEM, PinnedSelf = PIPTR>,
+    PIPTR: ForeignOwnable<PointedTo = ITEM>,
+{
+    unsafe extern "C" fn make_item(
+        parent_group: *mut bindings::config_group,
+        name: *const kernel::ffi::c_char,
+    ) -> *mut bindings::config_item {
+        let r_group_ptr: *mut Group<PAR> = parent_group.cast();
+        let container_ptr = unsafe { PAR::container_ptr(r_group_ptr) };
+        let container_ref = unsafe { PPTR::borrow(container_ptr) };
+        let child_init = match PAR::make_item(container_ref, unsafe { CStr::from_char_ptr(name) })
+        {
+            Ok(child) => child,
+            Err(e) => return e.to_ptr(),
+        };
+
+        let child = IPTR::try_pin_init(child_init, flags::GFP_KERNEL);
+
+        match child {
+            Ok(child) => {
+                let child_ptr = child.into_foreign();
+                unsafe { ITEM::item_ptr(child_ptr) }
+                    .cast::<bindings::config_item>()
+                    .cast_mut()
+            }
+            Err(e) => e.to_ptr(),
+        }
+    }
+
+    unsafe extern "C" fn drop_item(
+        _parent_group: *mut bindings::config_group,
+        item: *mut bindings::config_item,
+    ) {
+        // This only drops `configfs`' own reference. The Rust ownership is reclaimed by
+        // `ConfigItemOperationsVTable::release` once the refcount this drops actually reaches
+        // zero, which may be later than this call if something else (e.g. a symlink or an
+        // in-flight syscall) is still holding a reference to `item`.
+        //
+        // SAFETY: `item` is a live `config_item`, and this function owns the reference to it
+        // that `configfs` is asking to be dropped.
+        unsafe { bindings::config_item_put(item) };
+    }
+
+    const VTABLE: bindings::configfs_group_operations = bindings::configfs_group_operations {
+        make_item: Some(Self::make_item),
+        make_group: None,
+        disconnect_notify: None,
+        drop_item: Some(Self::drop_item),
+        is_visible: None,
+        is_bin_visible: None,
+    };
+}
+
+/// Operations implemented by `configfs` groups that can create leaf items.
+///
+/// Implement this trait on structs that embed a [`Subsystem`] or a [`Group`] to let `mkdir(2)`
+/// create leaf [`Item`]s inside them, as opposed to further [`Group`]s via [`GroupOperations`].
+#[vtable]
+pub trait ItemOperations<PAR, PPTR, ITEM, IPTR, PIPTR>
+where
+    PAR: HasGroup,
+    PPTR: ForeignOwnable<PointedTo = PAR>,
+    ITEM: HasItem,
+    IPTR: InPlaceInit<ITEM, PinnedSelf = PIPTR>,
+    PIPTR: ForeignOwnable<PointedTo = ITEM>,
+{
+    /// The kernel will call this method in response to `mkdir(2)` in the
+    /// directory representing `this`.
+    ///
+    /// To accept the request to create an item, implementations should
+    /// instantiate an `ITEM` and return an `IPTR` to it. To prevent creation,
+    /// return a suitable error.
+    fn make_item(this: PPTR::Borrowed<'_>, name: &CStr) -> Result<impl PinInit<ITEM, Error>>;
+}
+
+/// Populates a [`bindings::configfs_item_operations`] whose `release` reclaims the Rust
+/// ownership of an [`Item`] when its `config_item`'s refcount actually reaches zero.
+///
+/// This is wired into the [`Item`]'s own [`ItemType`] (via [`ItemType::new_item`]), as opposed to
+/// [`GroupOperationsVTable`]/[`ItemOperationsVTable`]'s `drop_item`, which only runs at `rmdir(2)`
+/// time and may fire well before the last reference is actually dropped.
+struct ConfigItemOperationsVTable<C, PTR>(PhantomData<(C, PTR)>)
+where
+    C: HasItem,
+    PTR: ForeignOwnable<PointedTo = C>;
+
+impl<C, PTR> ConfigItemOperationsVTable<C, PTR>
+where
+    C: HasItem + 'static,
+    PTR: ForeignOwnable<PointedTo = C>,
+{
+    unsafe extern "C" fn release(item: *mut bindings::config_item) {
+        let r_item_ptr: *mut Item<C> = item.cast::<Item<C>>().cast_mut();
+        let container_ptr = unsafe { C::container_ptr(r_item_ptr) };
+
+        // SAFETY: `container_ptr` was produced by `PTR::into_foreign` when the item was created,
+        // and `release` is only called once, when the last reference to `item` is dropped.
+        let child: PTR = unsafe { PTR::from_foreign(container_ptr) };
+        drop(child);
+    }
+
+    const VTABLE: bindings::configfs_item_operations = bindings::configfs_item_operations {
+        release: Some(Self::release),
+        allow_link: None,
+        drop_link: None,
+    };
+}
+
+/// Populates a [`bindings::configfs_item_operations`] whose `allow_link`/`drop_link` dispatch to
+/// [`SymlinkOperations`].
+///
+/// This is wired into a [`Group`]'s own [`ItemType`] (via [`ItemType::new_with_symlinks`]), letting
+/// `ln -s`(2)/`rm`(2) create and remove `configfs` symlinks from the directory representing `C` to
+/// the directory representing a `TARGET`.
+struct SymlinkOperationsVTable<C, TARGET>(PhantomData<(C, TARGET)>)
+where
+    C: SymlinkOperations<TARGET> + HasGroup,
+    TARGET: HasGroup;
+
+impl<C, TARGET> SymlinkOperationsVTable<C, TARGET>
+where
+    C: SymlinkOperations<TARGET> + HasGroup + 'static,
+    TARGET: HasGroup + 'static,
+{
+    unsafe extern "C" fn allow_link(
+        src: *mut bindings::config_item,
+        target: *mut bindings::config_item,
+    ) -> kernel::ffi::c_int {
+        let src_group_ptr = unsafe { kernel::container_of!(src, bindings::config_group, cg_item) };
+        let src_group_ptr: *mut Group<C> = src_group_ptr.cast::<Group<C>>().cast_mut();
+        let src_ptr = unsafe { C::container_ptr(src_group_ptr) };
+
+        let target_group_ptr =
+            unsafe { kernel::container_of!(target, bindings::config_group, cg_item) };
+        let target_group_ptr: *mut Group<TARGET> = target_group_ptr.cast::<Group<TARGET>>().cast_mut();
+        let target_ptr = unsafe { TARGET::container_ptr(target_group_ptr) };
+
+        match C::allow_link(unsafe { &*src_ptr }, unsafe { &*target_ptr }) {
+            Ok(()) => 0,
+            Err(e) => e.to_errno(),
+        }
+    }
+
+    unsafe extern "C" fn drop_link(
+        src: *mut bindings::config_item,
+        target: *mut bindings::config_item,
+    ) {
+        let src_group_ptr = unsafe { kernel::container_of!(src, bindings::config_group, cg_item) };
+        let src_group_ptr: *mut Group<C> = src_group_ptr.cast::<Group<C>>().cast_mut();
+        let src_ptr = unsafe { C::container_ptr(src_group_ptr) };
+
+        let target_group_ptr =
+            unsafe { kernel::container_of!(target, bindings::config_group, cg_item) };
+        let target_group_ptr: *mut Group<TARGET> = target_group_ptr.cast::<Group<TARGET>>().cast_mut();
+        let target_ptr = unsafe { TARGET::container_ptr(target_group_ptr) };
+
+        C::drop_link(unsafe { &*src_ptr }, unsafe { &*target_ptr });
+    }
+
+    const VTABLE: bindings::configfs_item_operations = bindings::configfs_item_operations {
+        release: None,
+        allow_link: Some(Self::allow_link),
+        drop_link: Some(Self::drop_link),
+    };
+}
+
+/// Operations allowing `configfs` to create and remove symlinks from the directory representing
+/// `Self` to the directory representing a `TARGET`.
+///
+/// Implement this trait on a type that embeds a [`Group`] and pass [`ItemType::new_with_symlinks`]
+/// the resulting [`ItemType`] to let `ln -s`(2) link the group's directory to a `TARGET`'s
+/// directory, e.g. to bind a "port" group to a "target" group.
+#[vtable]
+pub trait SymlinkOperations<TARGET>
+where
+    Self: HasGroup,
+    TARGET: HasGroup,
+{
+    /// The kernel calls this method to check whether a symlink from `this`'s directory to
+    /// `target`'s directory should be allowed. Returning an error rejects the link.
+    fn allow_link(this: &Self, target: &TARGET) -> Result;
+
+    /// The kernel calls this method after a previously-allowed symlink from `this`'s directory to
+    /// `target`'s directory has been removed.
+    fn drop_link(_this: &Self, _target: &TARGET) {
+        kernel::build_error!(kernel::error::VTABLE_DEFAULT_ERROR)
+    }
 }
 
 /// A `configfs` attribute.
@@ -309,23 +622,39 @@ where
         let r_group_ptr: *mut Group<HG> = c_group.cast();
         let container_ptr = unsafe { HG::container_ptr(r_group_ptr) };
         let container_ref = unsafe { &*container_ptr };
-        AO::store(container_ref, unsafe {
+        match AO::store(container_ref, unsafe {
             core::slice::from_raw_parts(page.cast(), size)
-        });
-        size as isize
+        }) {
+            Ok(()) => size as isize,
+            Err(e) => e.to_errno() as isize,
+        }
     }
 
     /// Create a new attribute.
     ///
-    /// The attribute will appear as a file with name given by `name`.
+    /// The attribute will appear as a file with name given by `name`, with its mode taken from
+    /// [`AttributeOperations::MODE`]. Equivalent to [`Self::new_with_mode`] with that mode.
     pub const fn new(name: &'static CStr) -> Self {
+        Self::new_with_mode(name, AO::MODE)
+    }
+
+    /// Create a new attribute with an explicit [`AttributeMode`].
+    ///
+    /// The attribute will appear as a file with name given by `name`. A [`AttributeMode::ReadOnly`]
+    /// attribute never has its `store` installed, and a [`AttributeMode::WriteOnly`] attribute
+    /// never has its `show` installed, regardless of what [`AttributeOperations`] implements.
+    pub const fn new_with_mode(name: &'static CStr, mode: AttributeMode) -> Self {
         Self {
             attribute: Opaque::new(bindings::configfs_attribute {
                 ca_name: name as *const _ as _,
                 ca_owner: core::ptr::null_mut(),
-                ca_mode: 0o660,
-                show: Some(Self::show),
-                store: if AO::HAS_STORE {
+                ca_mode: mode.as_raw(),
+                show: if matches!(mode, AttributeMode::WriteOnly) {
+                    None
+                } else {
+                    Some(Self::show)
+                },
+                store: if AO::HAS_STORE && !matches!(mode, AttributeMode::ReadOnly) {
                     Some(Self::store)
                 } else {
                     None
@@ -336,6 +665,28 @@ where
     }
 }
 
+/// The file mode of a `configfs` [`Attribute`], controlling whether `show`, `store`, or both are
+/// installed in the underlying `configfs_attribute`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AttributeMode {
+    /// Only `show` is installed; writes to the attribute file fail with `-EACCES`.
+    ReadOnly,
+    /// Only `store` is installed; reads from the attribute file fail with `-EACCES`.
+    WriteOnly,
+    /// Both `show` and `store` are installed.
+    ReadWrite,
+}
+
+impl AttributeMode {
+    const fn as_raw(self) -> bindings::umode_t {
+        match self {
+            AttributeMode::ReadOnly => 0o440,
+            AttributeMode::WriteOnly => 0o220,
+            AttributeMode::ReadWrite => 0o660,
+        }
+    }
+}
+
 /// Operations supported by an attribute.
 ///
 /// Implement this trait on type and pass that type as generic parameter when
@@ -346,6 +697,13 @@ pub trait AttributeOperations<AO>
 where
     AO: HasGroup,
 {
+    /// The [`AttributeMode`] [`Attribute::new`] creates this attribute with.
+    ///
+    /// Defaults to [`AttributeMode::ReadWrite`]; set this to [`AttributeMode::ReadOnly`] or
+    /// [`AttributeMode::WriteOnly`] to have [`Attribute::new`] omit `store`/`show` from the
+    /// underlying `configfs_attribute` accordingly.
+    const MODE: AttributeMode = AttributeMode::ReadWrite;
+
     /// This function is called by the kernel to read the value of an attribute.
     ///
     /// Implementations should write the rendering of the attribute to `page`
@@ -354,11 +712,211 @@ where
 
     /// This function is called by the kernel to update the value of an attribute.
     ///
-    /// Implementations should parse the value from `page` and update internal
-    /// state to reflect the parsed value. Partial writes are not supported and
-    /// implementations should expect the full page to arrive in one write
-    /// operation.
-    fn store(_container: &AO, _page: &[u8]) {
+    /// Implementations should parse the value from `page` and update internal state to reflect
+    /// the parsed value, returning an error (e.g. [`EINVAL`](kernel::error::code::EINVAL)) if
+    /// `page` cannot be parsed, which `configfs` reports back to the writer as a failed write
+    /// rather than a silently accepted one. Partial writes are not supported and implementations
+    /// should expect the full page to arrive in one write operation.
+    fn store(_container: &AO, _page: &[u8]) -> Result {
+        kernel::build_error!(kernel::error::VTABLE_DEFAULT_ERROR)
+    }
+}
+
+/// Writes [`core::fmt::Display`] output into a fixed-size `configfs` attribute page, truncating
+/// (rather than panicking or overflowing) once the page is full.
+struct PageWriter<'a> {
+    page: &'a mut [u8; 4096],
+    pos: usize,
+}
+
+impl<'a> PageWriter<'a> {
+    fn new(page: &'a mut [u8; 4096]) -> Self {
+        Self { page, pos: 0 }
+    }
+}
+
+impl core::fmt::Write for PageWriter<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let bytes = s.as_bytes();
+        let remaining = self.page.len() - self.pos;
+        let n = core::cmp::min(remaining, bytes.len());
+
+        self.page[self.pos..self.pos + n].copy_from_slice(&bytes[..n]);
+        self.pos += n;
+
+        if n < bytes.len() {
+            Err(core::fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Renders `*value.lock()` via [`core::fmt::Display`] into `page`.
+///
+/// Intended for use from an [`AttributeOperations::show`] implementation backed by a
+/// `#[pin] value: Mutex<T>` field on the container, instead of hand-parsing the raw page.
+pub fn show_typed<T: core::fmt::Display>(value: &Mutex<T>, page: &mut [u8; 4096]) -> isize {
+    use core::fmt::Write;
+
+    let guard = value.lock();
+    let mut writer = PageWriter::new(page);
+    let _ = write!(writer, "{}", &*guard);
+    writer.pos as isize
+}
+
+/// Parses `page` via [`core::str::FromStr`], and on success updates `*value.lock()` and wakes
+/// `changed`.
+///
+/// Intended for use from an [`AttributeOperations::store`] implementation backed by a
+/// `#[pin] value: Mutex<T>` and a `#[pin] changed: CondVar` field on the container. Returns
+/// [`EINVAL`](kernel::error::code::EINVAL) without updating `value` or notifying `changed` if
+/// `page` is not valid UTF-8 or fails to parse as a `T`, so the failure reaches the writer instead
+/// of appearing to silently succeed.
+pub fn store_typed<T: core::str::FromStr>(
+    value: &Mutex<T>,
+    changed: &CondVar,
+    page: &[u8],
+) -> Result {
+    let text = core::str::from_utf8(page).map_err(|_| kernel::error::code::EINVAL)?;
+    let parsed = text
+        .trim_end_matches('\n')
+        .parse::<T>()
+        .map_err(|_| kernel::error::code::EINVAL)?;
+
+    *value.lock() = parsed;
+    changed.notify_all();
+    Ok(())
+}
+
+/// Blocks until `changed` is signalled, then renders `*value.lock()` via [`core::fmt::Display`]
+/// into `page`.
+///
+/// Intended for use from the [`AttributeOperations::show`] implementation of a read-only
+/// companion attribute sharing `value`/`changed` with a [`store_typed`]-backed attribute, letting
+/// userspace `cat` the file and block until the next write — the classic `configfs`
+/// `value`/`value_changed` pattern.
+pub fn show_changed<T: core::fmt::Display>(
+    value: &Mutex<T>,
+    changed: &CondVar,
+    page: &mut [u8; 4096],
+) -> isize {
+    use core::fmt::Write;
+
+    let mut guard = value.lock();
+    changed.wait(&mut guard);
+    let mut writer = PageWriter::new(page);
+    let _ = write!(writer, "{}", &*guard);
+    writer.pos as isize
+}
+
+/// A `configfs` binary attribute.
+///
+/// Unlike [`Attribute`], a binary attribute transfers an arbitrary-length blob through an
+/// allocated buffer, up to [`BinAttributeOperations::MAX_SIZE`], instead of a fixed 4096-byte
+/// page. This avoids the silent truncation that [`AttributeOperations::store`] is prone to for
+/// values that don't fit in a page.
+#[repr(transparent)]
+pub struct BinAttribute<AO, HG> {
+    attribute: Opaque<bindings::configfs_bin_attribute>,
+    _p: PhantomData<(AO, HG)>,
+}
+
+unsafe impl<AO, HG> Sync for BinAttribute<AO, HG> {}
+
+unsafe impl<AO, HG> Send for BinAttribute<AO, HG> {}
+
+impl<AO, HG> BinAttribute<AO, HG>
+where
+    AO: BinAttributeOperations<HG>,
+    HG: HasGroup,
+{
+    unsafe extern "C" fn read(
+        item: *mut bindings::config_item,
+        buf: *mut kernel::ffi::c_void,
+        size: usize,
+    ) -> isize {
+        let c_group: *mut bindings::config_group = item.cast();
+        let r_group_ptr: *mut Group<HG> = c_group.cast();
+        let container_ptr = unsafe { HG::container_ptr(r_group_ptr) };
+        let container_ref = unsafe { &*container_ptr };
+        let buf = unsafe { core::slice::from_raw_parts_mut(buf.cast::<u8>(), size) };
+        match AO::read(container_ref, buf) {
+            Ok(n) => n as isize,
+            Err(e) => e.to_errno() as isize,
+        }
+    }
+
+    unsafe extern "C" fn write(
+        item: *mut bindings::config_item,
+        buf: *const kernel::ffi::c_void,
+        size: usize,
+    ) -> isize {
+        let c_group: *mut bindings::config_group = item.cast();
+        let r_group_ptr: *mut Group<HG> = c_group.cast();
+        let container_ptr = unsafe { HG::container_ptr(r_group_ptr) };
+        let container_ref = unsafe { &*container_ptr };
+        let buf = unsafe { core::slice::from_raw_parts(buf.cast::<u8>(), size) };
+        match AO::write(container_ref, buf) {
+            Ok(()) => size as isize,
+            Err(e) => e.to_errno() as isize,
+        }
+    }
+
+    /// Create a new binary attribute.
+    ///
+    /// The attribute will appear as a file with name given by `name`, accepting reads and writes
+    /// of up to [`BinAttributeOperations::MAX_SIZE`] bytes.
+    pub const fn new(name: &'static CStr) -> Self {
+        Self {
+            attribute: Opaque::new(bindings::configfs_bin_attribute {
+                cb_attr: bindings::configfs_attribute {
+                    ca_name: name as *const _ as _,
+                    ca_owner: core::ptr::null_mut(),
+                    ca_mode: 0o660,
+                    show: None,
+                    store: None,
+                },
+                cb_private: core::ptr::null_mut(),
+                cb_max_size: AO::MAX_SIZE,
+                read: Some(Self::read),
+                write: if AO::HAS_WRITE {
+                    Some(Self::write)
+                } else {
+                    None
+                },
+            }),
+            _p: PhantomData,
+        }
+    }
+}
+
+/// Operations supported by a binary attribute.
+///
+/// Implement this trait on a type and pass that type as the generic parameter when creating a
+/// [`BinAttribute`]. The type carrying the implementation serves no purpose other than specifying
+/// the attribute operations.
+#[vtable]
+pub trait BinAttributeOperations<AO>
+where
+    AO: HasGroup,
+{
+    /// The largest value, in bytes, that `configfs` will ever hand to [`Self::write`], and that
+    /// [`Self::read`] may return.
+    const MAX_SIZE: usize;
+
+    /// This function is called by the kernel to read the value of an attribute.
+    ///
+    /// Implementations should write the rendering of the attribute to `page` and return the
+    /// number of bytes written.
+    fn read(container: &AO, page: &mut [u8]) -> Result<usize>;
+
+    /// This function is called by the kernel to update the value of an attribute.
+    ///
+    /// Implementations should parse the value from `page` and update internal state to reflect
+    /// the parsed value. Unlike [`AttributeOperations::store`], `page` may span more than a
+    /// single page, up to [`Self::MAX_SIZE`].
+    fn write(_container: &AO, _page: &[u8]) -> Result {
         kernel::build_error!(kernel::error::VTABLE_DEFAULT_ERROR)
     }
 }
@@ -399,6 +957,41 @@ impl<const N: usize, C: HasGroup> AttributeList<N, C> {
     }
 }
 
+/// A list of binary attributes.
+///
+/// This type is used to construct a new [`ItemType`]. It represents a list of [`BinAttribute`]
+/// that will appear in the directory representing a [`Group`]. Users should not directly
+/// instantiate this type, rather they should use the `bin_attributes:` section of the
+/// [`kernel::configfs_attrs`] macro to declare a static set of binary attributes for a group.
+#[repr(transparent)]
+pub struct BinAttributeList<const N: usize, C>(
+    UnsafeCell<[*mut kernel::ffi::c_void; N]>,
+    PhantomData<C>,
+)
+where
+    C: HasGroup;
+unsafe impl<const N: usize, C: HasGroup> Send for BinAttributeList<N, C> {}
+unsafe impl<const N: usize, C: HasGroup> Sync for BinAttributeList<N, C> {}
+
+impl<const N: usize, C: HasGroup> BinAttributeList<N, C> {
+    #[doc(hidden)]
+    pub const fn new() -> Self {
+        Self(UnsafeCell::new([core::ptr::null_mut(); N]), PhantomData)
+    }
+
+    #[doc(hidden)]
+    pub const fn add<const I: usize, O: BinAttributeOperations<C>>(
+        &'static self,
+        attribute: &'static BinAttribute<O, C>,
+    ) {
+        if I >= N - 1 {
+            kernel::build_error("Invalid attribute index");
+        }
+
+        unsafe { (&mut *self.0.get())[I] = attribute as *const _ as _ };
+    }
+}
+
 /// A representation of the attributes that will appear in a [`Group`].
 ///
 /// Users should not directly instantiate objects of this type. Rather, they
@@ -418,6 +1011,7 @@ impl<C: HasGroup> ItemType<C> {
     #[doc(hidden)]
     pub const fn new_with_child_ctor<const N: usize, PAR, PPTR, CHLD, CPTR, PCPTR>(
         attributes: &'static AttributeList<N, C>,
+        bin_attributes: *mut *mut kernel::ffi::c_void,
     ) -> Self
     where
         PAR: GroupOperations<PAR, PPTR, CHLD, CPTR, PCPTR> + HasGroup + 'static,
@@ -433,21 +1027,70 @@ impl<C: HasGroup> ItemType<C> {
                     as *const _) as *mut _,
                 ct_item_ops: core::ptr::null_mut(),
                 ct_attrs: attributes as *const _ as _,
-                ct_bin_attrs: core::ptr::null_mut(),
+                ct_bin_attrs: bin_attributes as _,
             }),
             _p: PhantomData,
         }
     }
 
     #[doc(hidden)]
-    pub const fn new<const N: usize>(attributes: &'static AttributeList<N, C>) -> Self {
+    pub const fn new<const N: usize>(
+        attributes: &'static AttributeList<N, C>,
+        bin_attributes: *mut *mut kernel::ffi::c_void,
+    ) -> Self {
         Self {
             item_type: Opaque::new(bindings::config_item_type {
                 ct_owner: core::ptr::null_mut(),
                 ct_group_ops: core::ptr::null_mut(),
                 ct_item_ops: core::ptr::null_mut(),
                 ct_attrs: attributes as *const _ as _,
-                ct_bin_attrs: core::ptr::null_mut(),
+                ct_bin_attrs: bin_attributes as _,
+            }),
+            _p: PhantomData,
+        }
+    }
+
+    #[doc(hidden)]
+    pub const fn new_with_item_ctor<const N: usize, PAR, PPTR, ITEM, IPTR, PIPTR>(
+        attributes: &'static AttributeList<N, C>,
+        bin_attributes: *mut *mut kernel::ffi::c_void,
+    ) -> Self
+    where
+        PAR: ItemOperations<PAR, PPTR, ITEM, IPTR, PIPTR> + HasGroup + 'static,
+        PPTR: ForeignOwnable<PointedTo = PAR>,
+        ITEM: HasItem + 'static,
+        IPTR: InPlaceInit<ITEM, PinnedSelf = PIPTR>,
+        PIPTR: ForeignOwnable<PointedTo = ITEM>,
+    {
+        Self {
+            item_type: Opaque::new(bindings::config_item_type {
+                ct_owner: core::ptr::null_mut(),
+                ct_group_ops: (&ItemOperationsVTable::<PAR, PPTR, ITEM, IPTR, PIPTR>::VTABLE
+                    as *const _) as *mut _,
+                ct_item_ops: core::ptr::null_mut(),
+                ct_attrs: attributes as *const _ as _,
+                ct_bin_attrs: bin_attributes as _,
+            }),
+            _p: PhantomData,
+        }
+    }
+
+    #[doc(hidden)]
+    pub const fn new_with_symlinks<const N: usize, TARGET>(
+        attributes: &'static AttributeList<N, C>,
+        bin_attributes: *mut *mut kernel::ffi::c_void,
+    ) -> Self
+    where
+        C: SymlinkOperations<TARGET> + 'static,
+        TARGET: HasGroup + 'static,
+    {
+        Self {
+            item_type: Opaque::new(bindings::config_item_type {
+                ct_owner: core::ptr::null_mut(),
+                ct_group_ops: core::ptr::null_mut(),
+                ct_item_ops: (&SymlinkOperationsVTable::<C, TARGET>::VTABLE as *const _) as *mut _,
+                ct_attrs: attributes as *const _ as _,
+                ct_bin_attrs: bin_attributes as _,
             }),
             _p: PhantomData,
         }
@@ -455,6 +1098,29 @@ impl<C: HasGroup> ItemType<C> {
 }
 
 impl<C> ItemType<C> {
+    /// Create an item type for a leaf [`Item`] that has no attributes of its own.
+    ///
+    /// `PTR` is the foreign-ownable pointer type (e.g. `Box<C>` or `Arc<C>`) that was used to
+    /// create the [`Item`]; its ownership is reclaimed by [`ConfigItemOperationsVTable::release`]
+    /// once the item's `config_item` refcount actually reaches zero.
+    #[doc(hidden)]
+    pub const fn new_item<PTR>() -> Self
+    where
+        C: HasItem + 'static,
+        PTR: ForeignOwnable<PointedTo = C>,
+    {
+        Self {
+            item_type: Opaque::new(bindings::config_item_type {
+                ct_owner: core::ptr::null_mut(),
+                ct_group_ops: core::ptr::null_mut(),
+                ct_item_ops: (&ConfigItemOperationsVTable::<C, PTR>::VTABLE as *const _) as *mut _,
+                ct_attrs: core::ptr::null_mut(),
+                ct_bin_attrs: core::ptr::null_mut(),
+            }),
+            _p: PhantomData,
+        }
+    }
+
     fn as_ptr(&self) -> *const bindings::config_item_type {
         self.item_type.get()
     }
@@ -489,6 +1155,35 @@ pub unsafe trait HasGroup {
     }
 }
 
+/// Implement this trait for structs that embed a field of type [`Item`].
+///
+/// # Safety
+///
+/// Implementers of this trait must have a field of type [`Item`] at offset
+/// `OFFSET`. If any member methods are implemented they must be implemented
+/// according to the documentation on the methods in this trait declaration.
+pub unsafe trait HasItem {
+    /// The implementer of the trait must have a field of type [`Item`] at this
+    /// offset.
+    const OFFSET: usize;
+
+    /// Get a pointer to the field of type [`Item`] from a pointer to `Self`.
+    unsafe fn item_ptr(this: *const Self) -> *const Item<Self>
+    where
+        Self: Sized,
+    {
+        unsafe { this.cast::<u8>().add(Self::OFFSET).cast::<Item<Self>>() }
+    }
+
+    /// Get a pointer to `Self` from a pointer to the field of type [`Item`].
+    unsafe fn container_ptr(item: *mut Item<Self>) -> *mut Self
+    where
+        Self: Sized,
+    {
+        unsafe { item.cast::<u8>().sub(Self::OFFSET).cast::<Self>() }
+    }
+}
+
 /// Implement this trait for structs that embed a field of type [`Subsystem`].
 ///
 /// # Safety
@@ -559,6 +1254,35 @@ macro_rules! impl_has_group {
     }
 }
 
+/// Use this macro to implement the [`HasItem<T>`] trait for types that embed an
+/// [`Item`].
+#[macro_export]
+macro_rules! impl_has_item {
+    (
+        impl$({$($generics:tt)*})?
+            HasItem
+            for $self:ty
+        { self.$field:ident }
+        $($rest:tt)*
+    ) => {
+        // SAFETY: This implementation of `item_ptr` only compiles if the
+        // field has the right type.
+        unsafe impl$(<$($generics)*>)? $crate::configfs::HasItem for $self {
+            const OFFSET: usize = ::core::mem::offset_of!(Self, $field) as usize;
+
+            #[inline]
+            unsafe fn item_ptr(this: *const Self) ->
+                *const $crate::configfs::Item<Self>
+            {
+                // SAFETY: The caller promises that the pointer is not dangling.
+                unsafe {
+                    ::core::ptr::addr_of!((*this).$field)
+                }
+            }
+        }
+    }
+}
+
 /// Use to implement the [`HasSubsystem<T>`] trait for types that embed a
 /// [`Subsystem`].
 #[macro_export]
@@ -590,6 +1314,19 @@ macro_rules! impl_has_subsystem {
 
 /// Define a list of configfs attributes statically.
 ///
+/// An optional `bin_attributes` section may be added alongside `attributes` to also expose
+/// binary attributes backed by [`BinAttributeOperations`], for values that do not fit the
+/// single-page, text-oriented [`AttributeOperations::show`]/[`AttributeOperations::store`]
+/// model. Each binary attribute gets its own [`BinAttributeOperations::read`]/
+/// [`BinAttributeOperations::write`] pair and its own [`BinAttributeOperations::MAX_SIZE`] bound,
+/// and is tracked in a separate [`BinAttributeList`] from the text `attributes`, so the two kinds
+/// can be declared, counted, and sized independently of each other.
+///
+/// This macro does not wire up [`ItemOperations`] or [`SymlinkOperations`]: a container whose
+/// directory should create leaf [`Item`]s or link to other [`Group`]s needs to call
+/// [`ItemType::new_with_item_ctor`] or [`ItemType::new_with_symlinks`] directly, the same way it
+/// would call [`ItemType::new_with_child_ctor`] by hand instead of through this macro.
+///
 /// # Example
 ///
 /// ```ignore
@@ -655,6 +1392,9 @@ macro_rules! configfs_attrs {
         attributes: [
             $($name:ident: $attr:ty,)+
         ],
+        $(bin_attributes: [
+            $($bname:ident: $battr:ty,)+
+        ],)?
     ) => {
         $crate::configfs_attrs!(
             count:
@@ -665,6 +1405,10 @@ macro_rules! configfs_attrs {
             @eat($($name $attr,)+),
             @assign(),
             @cnt(0usize),
+            @battrs($($($bname $battr)+)?),
+            @beat($($($bname $battr,)+)?),
+            @bassign(),
+            @bcnt(0usize),
         )
     };
     (
@@ -675,6 +1419,9 @@ macro_rules! configfs_attrs {
         attributes: [
             $($name:ident: $attr:ty,)+
         ],
+        $(bin_attributes: [
+            $($bname:ident: $battr:ty,)+
+        ],)?
     ) => {
         $crate::configfs_attrs!(
             count:
@@ -685,6 +1432,10 @@ macro_rules! configfs_attrs {
             @eat($($name $attr,)+),
             @assign(),
             @cnt(0usize),
+            @battrs($($($bname $battr)+)?),
+            @beat($($($bname $battr,)+)?),
+            @bassign(),
+            @bcnt(0usize),
         )
     };
     (count:
@@ -695,6 +1446,10 @@ macro_rules! configfs_attrs {
      @eat($name:ident $attr:ty, $($rname:ident $rattr:ty,)*),
      @assign($($assign:block)*),
      @cnt($cnt:expr),
+     @battrs($($baname:ident $baattr:ty)*),
+     @beat($($bename:ident $beattr:ty,)*),
+     @bassign($($bassign:block)*),
+     @bcnt($bcnt:expr),
     ) => {
         $crate::configfs_attrs!(count:
                                 @container($container),
@@ -707,6 +1462,10 @@ macro_rules! configfs_attrs {
                                     $crate::macros::paste!( [< $container:upper _ATTRS >]).add::<N, _>(& $crate::macros::paste!( [< $container:upper _ $name:upper _ATTR >]));
                                 }),
                                 @cnt(1usize + $cnt),
+                                @battrs($($baname $baattr)*),
+                                @beat($($bename $beattr,)*),
+                                @bassign($($bassign)*),
+                                @bcnt($bcnt),
         )
     };
     (count:
@@ -717,6 +1476,40 @@ macro_rules! configfs_attrs {
      @eat(),
      @assign($($assign:block)*),
      @cnt($cnt:expr),
+     @battrs($($baname:ident $baattr:ty)*),
+     @beat($bname:ident $battr:ty, $($rbname:ident $rbattr:ty,)*),
+     @bassign($($bassign:block)*),
+     @bcnt($bcnt:expr),
+    ) => {
+        $crate::configfs_attrs!(count:
+                                @container($container),
+                                @child($($child, $pointer, $pinned)?),
+                                @no_child($($no_child)?),
+                                @attrs($($aname $aattr)+),
+                                @eat(),
+                                @assign($($assign)*),
+                                @cnt($cnt),
+                                @battrs($($baname $baattr)*),
+                                @beat($($rbname $rbattr,)*),
+                                @bassign($($bassign)* {
+                                    const M: usize = $bcnt;
+                                    $crate::macros::paste!( [< $container:upper _BIN_ATTRS >]).add::<M, _>(& $crate::macros::paste!( [< $container:upper _ $bname:upper _BIN_ATTR >]));
+                                }),
+                                @bcnt(1usize + $bcnt),
+        )
+    };
+    (count:
+     @container($container:ty),
+     @child($($child:ty, $pointer:ty, $pinned:ty)?),
+     @no_child($($no_child:ident)?),
+     @attrs($($aname:ident $aattr:ty)+),
+     @eat(),
+     @assign($($assign:block)*),
+     @cnt($cnt:expr),
+     @battrs($($baname:ident $baattr:ty)*),
+     @beat(),
+     @bassign($($bassign:block)*),
+     @bcnt($bcnt:expr),
     ) =>
     {
         $crate::configfs_attrs!(final:
@@ -726,6 +1519,9 @@ macro_rules! configfs_attrs {
                                 @attrs($($aname $aattr)+),
                                 @assign($($assign)*),
                                 @cnt($cnt),
+                                @battrs($($baname $baattr)*),
+                                @bassign($($bassign)*),
+                                @bcnt($bcnt),
         )
     };
     (final:
@@ -735,6 +1531,9 @@ macro_rules! configfs_attrs {
      @attrs($($name:ident $attr:ty)+),
      @assign($($assign:block)+),
      @cnt($cnt:expr),
+     @battrs($($bname:ident $battr:ty)*),
+     @bassign($($bassign:block)*),
+     @bcnt($bcnt:expr),
     ) =>
     {
         {
@@ -754,6 +1553,21 @@ macro_rules! configfs_attrs {
 
             $($assign)+
 
+            $(
+                $crate::macros::paste!{
+                    static [< $container:upper _ $bname:upper _BIN_ATTR >] : $crate::configfs::BinAttribute<$battr, $container>
+                        = $crate::configfs::BinAttribute::new(c_str!(::core::stringify!($bname)));
+                }
+            )*
+
+                const M: usize = $bcnt + 1usize;
+            $crate::macros::paste!{
+                static [< $container:upper _BIN_ATTRS >] : $crate::configfs::BinAttributeList<M, $container> =
+                    $crate::configfs::BinAttributeList::new();
+            }
+
+            $($bassign)*
+
             $(
                 $crate::macros::paste!{
                     const [<$no_child:upper>]: bool = true;
@@ -761,7 +1575,14 @@ macro_rules! configfs_attrs {
 
                 $crate::macros::paste!{
                     static [< $container:upper _TPE >] : $crate::configfs::ItemType<$container>  =
-                        $crate::configfs::ItemType::new::<N>(&  [<$ container:upper _ATTRS >] );
+                        $crate::configfs::ItemType::new::<N>(
+                            &  [<$ container:upper _ATTRS >],
+                            if $bcnt == 0usize {
+                                ::core::ptr::null_mut()
+                            } else {
+                                &  [<$ container:upper _BIN_ATTRS >] as *const _ as *mut *mut kernel::ffi::c_void
+                            },
+                        );
                 }
             )?
 
@@ -769,7 +1590,14 @@ macro_rules! configfs_attrs {
                 $crate::macros::paste!{
                     // TODO: Parent not always Arc<$container>
                     static [< $container:upper _TPE >] : $crate::configfs::ItemType<$container>  =
-                        $crate::configfs::ItemType::new_with_child_ctor::<N, $container, Arc<$container>, $child, $pointer, $pinned>(&  [<$ container:upper _ATTRS >] );
+                        $crate::configfs::ItemType::new_with_child_ctor::<N, $container, Arc<$container>, $child, $pointer, $pinned>(
+                            &  [<$ container:upper _ATTRS >],
+                            if $bcnt == 0usize {
+                                ::core::ptr::null_mut()
+                            } else {
+                                &  [<$ container:upper _BIN_ATTRS >] as *const _ as *mut *mut kernel::ffi::c_void
+                            },
+                        );
                 }
             )?
 
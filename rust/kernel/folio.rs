@@ -100,16 +100,80 @@ impl UniqueFolio {
         Ok(MutMapGuard(self.map_page(page_index)?))
     }
 
-    /// Copy `src.len()` bytes from `src` into `self` at offset 0
+    /// Copy `src.len()` bytes from `src` into `self` at offset 0.
+    ///
+    /// Unlike [`Self::map_page_mut`], `src` may span more than one of the folio's constituent
+    /// pages.
     pub fn copy_from_slice(&mut self, src: &[u8]) -> Result {
-        use core::ops::DerefMut;
-        let mut dst_map = self.map_page_mut(0)?;
-        let dst: &mut [u8] = dst_map.deref_mut();
-        dst.get_mut(..src.len())
-            .ok_or(ENOBUFS)?
-            .copy_from_slice(src);
+        let mut remaining = src;
+
+        self.for_each_page(0, src.len(), |s| {
+            let (chunk, rest) = core::mem::take(&mut remaining).split_at(s.len());
+            s.copy_from_slice(chunk);
+            remaining = rest;
+            Ok(())
+        })
+    }
+
+    /// Calls `cb` with a mutable slice into each of the folio's constituent pages that overlap
+    /// `[offset, offset + len)`, consecutively covering the whole range.
+    fn for_each_page(
+        &mut self,
+        offset: usize,
+        len: usize,
+        mut cb: impl FnMut(&mut [u8]) -> Result,
+    ) -> Result {
+        let mut remaining = len;
+        let mut next_offset = offset;
+
+        // Check that we don't overflow the folio.
+        let end = offset.checked_add(len).ok_or(EDOM)?;
+        if end > self.0.size() {
+            return Err(EINVAL);
+        }
+
+        while remaining > 0 {
+            let page_offset = next_offset & (bindings::PAGE_SIZE - 1);
+            let usable = min(remaining, bindings::PAGE_SIZE - page_offset);
+            // SAFETY: `self.0` is valid because `UniqueFolio` holds a reference to it;
+            // `next_offset` is also guaranteed be less than the folio size.
+            let ptr = unsafe { bindings::kmap_local_folio(self.0 .0.get(), next_offset) };
+
+            // SAFETY: `ptr` was just returned by the `kmap_local_folio` above.
+            let _guard = ScopeGuard::new(|| unsafe { bindings::kunmap_local(ptr) });
+
+            // SAFETY: `kmap_local_folio` maps whole page so we know it's mapped for at least
+            // `usable` bytes.
+            let s = unsafe { core::slice::from_raw_parts_mut(ptr.cast::<u8>(), usable) };
+            cb(s)?;
+
+            next_offset += usable;
+            remaining -= usable;
+        }
+
         Ok(())
     }
+
+    /// Reads the folio's contents from `[offset, offset + dst.len())` into `dst`, spanning as
+    /// many of the folio's constituent pages as needed.
+    pub fn read_into(&mut self, offset: usize, dst: &mut [u8]) -> Result {
+        let mut remaining = dst;
+
+        self.for_each_page(offset, remaining.len(), |s| {
+            let (chunk, rest) = core::mem::take(&mut remaining).split_at_mut(s.len());
+            chunk.copy_from_slice(s);
+            remaining = rest;
+            Ok(())
+        })
+    }
+
+    /// Writes zeroes into the folio.
+    pub fn zero_out(&mut self, offset: usize, len: usize) -> Result {
+        self.for_each_page(offset, len, |s| {
+            s.fill(0);
+            Ok(())
+        })
+    }
 }
 
 /// A mapped [`UniqueFolio`].
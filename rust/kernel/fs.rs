@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Types for implementing file systems.
+//!
+//! C header: [`include/linux/fs.h`](srctree/include/linux/fs.h)
+
+mod address_space;
+
+pub use address_space::AddressSpaceOperations;
+
+use crate::types::Opaque;
+
+/// Wraps the kernel's `struct file`.
+///
+/// # Invariants
+///
+/// Instances of this type are always ref-counted by the caller that handed out the reference,
+/// for at least the lifetime of the borrow.
+#[repr(transparent)]
+pub struct File(Opaque<bindings::file>);
+
+impl File {
+    /// Creates a borrowed `File` from a raw pointer.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid `struct file` for the duration of `'a`.
+    pub(crate) unsafe fn from_raw<'a>(ptr: *const bindings::file) -> &'a Self {
+        // SAFETY: By the safety requirements of this function, `ptr` is valid for use as a
+        // reference for the duration of `'a`.
+        unsafe { &*ptr.cast() }
+    }
+}
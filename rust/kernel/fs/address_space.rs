@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! This module provides an interface for file systems to hook folios into the page cache.
+//!
+//! C header: [`include/linux/fs.h`](srctree/include/linux/fs.h)
+
+use crate::{
+    bindings,
+    error::{from_result, Result},
+    folio::LockedFolio,
+    fs::File,
+};
+use core::marker::PhantomData;
+
+/// Implement this trait to back a `struct address_space` with Rust, hooking folios into the page
+/// cache.
+///
+/// This is the minimal surface needed to back a mountable read-only file system, as sketched in
+/// the folio RFC series.
+#[macros::vtable]
+pub trait AddressSpaceOperations: Sized {
+    /// Fills `folio` with data read from the backing store.
+    ///
+    /// `file` is the file the read was issued through, if any. Implementations should populate
+    /// `folio` (for instance, by reading from a block device) and call
+    /// [`LockedFolio::mark_uptodate`] on success. The folio is unlocked by the framework once
+    /// this function returns; there is no need to unlock it explicitly.
+    fn read_folio(file: Option<&File>, folio: &mut LockedFolio<'_>) -> Result;
+}
+
+/// A vtable for the page cache to interact with a file system's [`AddressSpaceOperations`].
+///
+/// A `bindings::address_space_operations` vtable is constructed from pointers to the `extern "C"`
+/// functions of this struct, exposed through [`OperationsVtable::build`].
+pub(crate) struct OperationsVtable<T: AddressSpaceOperations>(PhantomData<T>);
+
+impl<T: AddressSpaceOperations> OperationsVtable<T> {
+    /// This function is called by the C kernel. A pointer to this function is installed in the
+    /// `address_space_operations` vtable for the file system.
+    ///
+    /// # Safety
+    ///
+    /// - `file` must be null or a valid pointer to a `struct file` for the duration of this call.
+    /// - `folio` must point to a valid `struct folio` that the kernel has already locked; the
+    ///   caller transfers the responsibility of unlocking it to this function.
+    unsafe extern "C" fn read_folio_callback(
+        file: *mut bindings::file,
+        folio: *mut bindings::folio,
+    ) -> core::ffi::c_int {
+        // SAFETY: By the safety requirements of this function, `folio` is valid and locked, and
+        // the responsibility of unlocking it is transferred to the `LockedFolio` we create here.
+        // We keep it alive until the end of this function so we may mark it as errored before it
+        // unlocks on drop, and it is dropped exactly once: here.
+        let mut folio = unsafe { LockedFolio::from_raw(folio) };
+
+        // SAFETY: By the safety requirements of this function, `file` is either null or valid for
+        // the duration of this call.
+        let file = unsafe { file.cast_const().as_ref() }.map(|file| {
+            // SAFETY: `file` was just shown to be a valid reference to a `struct file`.
+            unsafe { File::from_raw(file) }
+        });
+
+        from_result(|| {
+            let ret = T::read_folio(file, &mut folio);
+            if ret.is_err() {
+                folio.set_error();
+            }
+            ret.map(|()| 0)
+        })
+    }
+
+    const VTABLE: bindings::address_space_operations = bindings::address_space_operations {
+        writepage: None,
+        read_folio: Some(Self::read_folio_callback),
+        writepages: None,
+        dirty_folio: None,
+        readahead: None,
+        write_begin: None,
+        write_end: None,
+        bmap: None,
+        invalidate_folio: None,
+        release_folio: None,
+        free_folio: None,
+        direct_IO: None,
+        migrate_folio: None,
+        launder_folio: None,
+        is_partially_uptodate: None,
+        is_dirty_writeback: None,
+        error_remove_folio: None,
+        swap_activate: None,
+        swap_deactivate: None,
+        swap_rw: None,
+    };
+
+    /// Builds an instance of `bindings::address_space_operations` for `T`.
+    pub(crate) const fn build() -> &'static bindings::address_space_operations {
+        &Self::VTABLE
+    }
+}
@@ -3,7 +3,21 @@
 //! Intrusive high resolution timers.
 //!
 //! Allows scheduling timer callbacks without doing allocations at the time of
-//! scheduling. For now, only one timer per type is allowed.
+//! scheduling. A host struct may embed more than one [`Timer`] field by
+//! distinguishing them with a marker `Tag` type parameter; see
+//! [`impl_has_timer`].
+//!
+//! [`Timer::new`] picks the clock a timer is measured against via [`ClockSource`] (e.g.
+//! [`ClockSource::BootTime`] for a timer that must keep running across suspend), and
+//! [`TimerPointer::schedule`]/[`TimerPointer::schedule_range`] pick whether the requested expiry
+//! is relative to now or an absolute deadline via [`TimerMode`]; [`TimerPointer::schedule_at`]
+//! and [`TimerPointer::schedule_after`] are shorthands for the common absolute/relative cases.
+//!
+//! A [`TimerCallback::run`] implementation can re-arm itself before returning
+//! [`TimerRestart::Restart`] by calling [`TimerCallbackContext::restart_periodic`] (or the lower
+//! level [`TimerCallbackContext::forward`]/[`TimerCallbackContext::forward_now`]) to build a
+//! periodic timer; returning [`TimerRestart::Restart`] without advancing the expiry first just
+//! fires the callback again immediately and busy-loops.
 //!
 //! # TODO
 //!
@@ -14,46 +28,122 @@
 //!
 
 // TODO: hrtimer_nanosleep
-// TODO: schedule_hrtimeout_range
 // TODO: schedule_hrtimeout_range_clock
-// TODO: schedule_hrtimeout
-// TODO: sleeper API -> task related?
-// TODO: timer modes ABS/REL/HARD/SOFT
 // TODO: Add cancel example
 // TODO: Add non mut pin example
 // TODO: Access target through handle
 
 use core::{marker::PhantomData, ptr};
 
-use crate::{init::PinInit, prelude::*, sync::Arc, time::Ktime, types::Opaque};
+use crate::{
+    error::{code::EBUSY, Result},
+    init::PinInit,
+    prelude::*,
+    sync::Arc,
+    time::Ktime,
+    types::Opaque,
+};
 
 /// A timer backed by a C `struct hrtimer`.
 ///
+/// The `Tag` parameter distinguishes multiple `Timer` fields embedded in the
+/// same host struct `U`; it defaults to `()` for hosts with a single timer.
+///
 /// # Invariants
 ///
 /// * `self.timer` is initialized by `bindings::hrtimer_init`.
 #[repr(transparent)]
 #[pin_data]
-pub struct Timer<U> {
+pub struct Timer<U, Tag = ()> {
     #[pin]
     timer: Opaque<bindings::hrtimer>,
-    _t: PhantomData<U>,
+    _t: PhantomData<(U, Tag)>,
 }
 
 // SAFETY: A `Timer` can be moved to other threads and used/dropped from there.
-unsafe impl<U> Send for Timer<U> {}
+unsafe impl<U, Tag> Send for Timer<U, Tag> {}
 
 // SAFETY: Timer operations are locked on C side, so it is safe to operate on a
 // timer from multiple threads
-unsafe impl<U> Sync for Timer<U> {}
+unsafe impl<U, Tag> Sync for Timer<U, Tag> {}
 
-type RawTimerCallbackPointer = unsafe extern "C" fn(*mut bindings::hrtimer) -> bindings::hrtimer_restart;
+type RawTimerCallbackPointer =
+    unsafe extern "C" fn(*mut bindings::hrtimer) -> bindings::hrtimer_restart;
+
+/// The clock a [`Timer`] is measured against.
+///
+/// Mirrors the `CLOCK_*` constants accepted by `hrtimer_init`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockSource {
+    /// `CLOCK_MONOTONIC`: monotonically increasing, unaffected by `settimeofday`.
+    Monotonic,
+    /// `CLOCK_REALTIME`: wall-clock time, which can jump forwards or backwards.
+    Realtime,
+    /// `CLOCK_BOOTTIME`: like [`ClockSource::Monotonic`], but also counts suspended time.
+    BootTime,
+    /// `CLOCK_TAI`: International Atomic Time.
+    Tai,
+}
+
+impl ClockSource {
+    fn into_raw(self) -> bindings::clockid_t {
+        (match self {
+            Self::Monotonic => bindings::CLOCK_MONOTONIC,
+            Self::Realtime => bindings::CLOCK_REALTIME,
+            Self::BootTime => bindings::CLOCK_BOOTTIME,
+            Self::Tai => bindings::CLOCK_TAI,
+        }) as bindings::clockid_t
+    }
+}
 
-impl<U> Timer<U> {
-    pub fn new<T>() -> impl PinInit<Self>
+/// The expiry mode of a [`Timer`], mirroring the kernel's `HRTIMER_MODE_*` flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerMode {
+    /// Expiry is an absolute time.
+    Abs,
+    /// Expiry is relative to now.
+    Rel,
+    /// Like [`TimerMode::Abs`], but the callback must run in hardirq context.
+    AbsHard,
+    /// Like [`TimerMode::Rel`], but the callback must run in hardirq context.
+    RelHard,
+    /// Like [`TimerMode::Abs`], but the callback may run in softirq context.
+    AbsSoft,
+    /// Like [`TimerMode::Rel`], but the callback may run in softirq context.
+    RelSoft,
+    /// Like [`TimerMode::Abs`], but the timer is bound to the CPU that schedules it, instead of
+    /// being free to migrate to whichever CPU its expiry is next serviced on.
+    AbsPinned,
+    /// Like [`TimerMode::Rel`], but the timer is bound to the CPU that schedules it, instead of
+    /// being free to migrate to whichever CPU its expiry is next serviced on.
+    RelPinned,
+}
+
+impl TimerMode {
+    fn into_raw(self) -> bindings::hrtimer_mode {
+        match self {
+            Self::Abs => bindings::hrtimer_mode_HRTIMER_MODE_ABS,
+            Self::Rel => bindings::hrtimer_mode_HRTIMER_MODE_REL,
+            Self::AbsHard => bindings::hrtimer_mode_HRTIMER_MODE_ABS_HARD,
+            Self::RelHard => bindings::hrtimer_mode_HRTIMER_MODE_REL_HARD,
+            Self::AbsSoft => bindings::hrtimer_mode_HRTIMER_MODE_ABS_SOFT,
+            Self::RelSoft => bindings::hrtimer_mode_HRTIMER_MODE_REL_SOFT,
+            Self::AbsPinned => bindings::hrtimer_mode_HRTIMER_MODE_ABS_PINNED,
+            Self::RelPinned => bindings::hrtimer_mode_HRTIMER_MODE_REL_PINNED,
+        }
+    }
+}
+
+impl<U, Tag> Timer<U, Tag> {
+    /// Return an initializer for a new timer instance, using the given clock source.
+    ///
+    /// `mode` selects whether the timer's expiry is interpreted as absolute or relative, and
+    /// whether its callback may run in softirq context; it is passed on as the default mode
+    /// for `hrtimer_init` and is overridable per-call by [`TimerPointer::schedule`].
+    pub fn new<T>(clock: ClockSource, mode: TimerMode) -> impl PinInit<Self>
     where
-        T: TimerPointer<U>,
-        U: TimerCallback,
+        T: TimerPointer<U, Tag>,
+        U: TimerCallback<Tag>,
     {
         pin_init!( Self {
             // INVARIANTS: We initialize `timer` with `hrtimer_init` below.
@@ -64,8 +154,8 @@ impl<U> Timer<U> {
                 unsafe {
                     bindings::hrtimer_init(
                         place,
-                        bindings::CLOCK_MONOTONIC as i32,
-                        bindings::hrtimer_mode_HRTIMER_MODE_REL,
+                        clock.into_raw() as i32,
+                        mode.into_raw(),
                     );
                 }
 
@@ -129,11 +219,87 @@ impl<U> Timer<U> {
         unsafe { bindings::hrtimer_cancel(c_timer_ptr) != 0 }
     }
 
-    // TODO: try_cancel
-    // TODO: get_remaining
-    // TODO: active
-    // TODO: queued
-    // TODO: callback_running
+    /// Attempt to cancel an initialized and potentially armed timer, without blocking.
+    ///
+    /// Returns `Ok(true)` if the timer was active and has been deactivated, or `Ok(false)` if
+    /// it was already inactive. If the timer's callback is currently running, this returns
+    /// `Err(EBUSY)` instead of waiting for it to finish; callers that can block should use
+    /// [`Timer::raw_cancel`] instead.
+    ///
+    /// # Safety
+    ///
+    /// `self_ptr` must point to a valid `Self`.
+    unsafe fn raw_try_cancel(self_ptr: *const Self) -> Result<bool> {
+        // SAFETY: timer_ptr points to an allocation of at least `Timer` size.
+        let c_timer_ptr = unsafe { Timer::raw_get(self_ptr) };
+
+        // SAFETY: `c_timer_ptr` is initialized and valid. Synchronization is
+        // handled on C side.
+        match unsafe { bindings::hrtimer_try_to_cancel(c_timer_ptr) } {
+            -1 => Err(EBUSY),
+            ret => Ok(ret != 0),
+        }
+    }
+
+    /// Returns whether the timer is currently active, i.e. queued for expiry or with its
+    /// callback currently running.
+    ///
+    /// # Safety
+    ///
+    /// `self_ptr` must point to a valid `Self`.
+    unsafe fn raw_is_active(self_ptr: *const Self) -> bool {
+        // SAFETY: timer_ptr points to an allocation of at least `Timer` size.
+        let c_timer_ptr = unsafe { Timer::raw_get(self_ptr) };
+
+        // SAFETY: `c_timer_ptr` is initialized and valid.
+        unsafe { bindings::hrtimer_active(c_timer_ptr) != 0 }
+    }
+
+    /// Returns whether the timer is currently queued for expiry.
+    ///
+    /// # Safety
+    ///
+    /// `self_ptr` must point to a valid `Self`.
+    unsafe fn raw_is_queued(self_ptr: *const Self) -> bool {
+        // SAFETY: timer_ptr points to an allocation of at least `Timer` size.
+        let c_timer_ptr = unsafe { Timer::raw_get(self_ptr) };
+
+        // SAFETY: `c_timer_ptr` is initialized and valid.
+        unsafe { bindings::hrtimer_is_queued(c_timer_ptr) != 0 }
+    }
+
+    /// Returns whether the timer's callback is currently running, possibly on another CPU.
+    ///
+    /// # Safety
+    ///
+    /// `self_ptr` must point to a valid `Self`.
+    unsafe fn raw_callback_running(self_ptr: *const Self) -> bool {
+        // SAFETY: timer_ptr points to an allocation of at least `Timer` size.
+        let c_timer_ptr = unsafe { Timer::raw_get(self_ptr) };
+
+        // SAFETY: `c_timer_ptr` is initialized and valid.
+        unsafe { bindings::hrtimer_callback_running(c_timer_ptr) != 0 }
+    }
+
+    /// Returns the time remaining until expiry, clamped to zero if the timer has already
+    /// expired.
+    ///
+    /// # Safety
+    ///
+    /// `self_ptr` must point to a valid `Self`.
+    unsafe fn raw_remaining(self_ptr: *const Self) -> Ktime {
+        // SAFETY: `self_ptr` is a valid pointer to a `Self`, per the safety requirements of
+        // this function.
+        let this = unsafe { &*self_ptr };
+        let remaining_ns = this
+            .expires()
+            .to_ns()
+            .saturating_sub(this.get_time().to_ns())
+            .max(0);
+
+        Ktime::from_ns(remaining_ns)
+    }
+
     // TODO: hrtimer_forward outside of callback context
 }
 
@@ -161,9 +327,9 @@ impl<U> Timer<U> {
 /// [`Box<T>`]: Box
 /// [`Arc<T>`]: Arc
 /// [`ARef<T>`]: crate::types::ARef
-pub unsafe trait TimerPointer<U>: Sync + Sized
+pub unsafe trait TimerPointer<U, Tag = ()>: Sync + Sized
 where
-    U: TimerCallback,
+    U: TimerCallback<Tag>,
 {
     /// A handle representing a scheduled timer.
     ///
@@ -174,13 +340,33 @@ where
     /// before the timer is unarmed and the callback has completed.
     type TimerHandle: TimerHandle;
 
-    /// Schedule the timer after `expires` time units. If the timer was already
-    /// scheduled, it is rescheduled at the new expiry time.
-    fn schedule(self, expires: u64) -> Self::TimerHandle;
+    /// Schedule the timer to expire at `expires`, interpreted according to `mode`. If the
+    /// timer was already scheduled, it is rescheduled at the new expiry time.
+    fn schedule(self, expires: Ktime, mode: TimerMode) -> Self::TimerHandle;
+
+    /// Schedule the timer to expire anywhere in the range `[expires, expires + slack]`,
+    /// interpreted according to `mode`, letting the kernel coalesce it with other nearby
+    /// timers to save wakeups. Callers that need an exact expiry should use
+    /// [`TimerPointer::schedule`] instead. If the timer was already scheduled, it is
+    /// rescheduled at the new expiry time.
+    fn schedule_range(self, expires: Ktime, slack: Ktime, mode: TimerMode) -> Self::TimerHandle;
+
+    /// Schedule the timer to expire at the absolute instant `deadline`.
+    ///
+    /// Shorthand for `schedule(deadline, TimerMode::Abs)`.
+    fn schedule_at(self, deadline: Ktime) -> Self::TimerHandle {
+        self.schedule(deadline, TimerMode::Abs)
+    }
 
+    /// Schedule the timer to expire after `delay` has elapsed.
+    ///
+    /// Shorthand for `schedule(delay, TimerMode::Rel)`.
+    fn schedule_after(self, delay: Ktime) -> Self::TimerHandle {
+        self.schedule(delay, TimerMode::Rel)
+    }
 }
 
-pub unsafe trait RawTimerCallback {
+pub unsafe trait RawTimerCallback<Tag = ()> {
     /// Callback to be called from C.
     ///
     /// # Safety
@@ -194,8 +380,47 @@ pub unsafe trait RawTimerCallback {
 /// When dropped, the timer represented by this handle must be cancelled, if it
 /// is armed. If the timer handler is running when the handle is dropped, the
 /// drop method must wait for the handler to finish before returning.
+///
+/// # Scheduling vs. cancelling
+///
+/// Calling [`TimerHandle::cancel`] concurrently with scheduling the same timer from another
+/// thread is order-dependent: whichever call reaches the C core last wins, and a `cancel`
+/// that loses the race will observe the timer as still active even though the caller
+/// intended to deactivate it. Callers that cannot tolerate blocking until a concurrently
+/// running callback finishes, or that need to know whether they raced a reschedule, should
+/// prefer [`TimerHandle::try_cancel`] over [`TimerHandle::cancel`].
+///
+/// [`TimerHandle::cancel`] is the synchronous variant (it wraps `hrtimer_cancel`, which blocks
+/// until any in-flight callback completes); [`TimerHandle::try_cancel`] is the non-blocking
+/// variant (it wraps `hrtimer_try_to_cancel`). Every `TimerPointer::TimerHandle`'s `Drop` impl
+/// uses [`TimerHandle::cancel`], so the pointee is never freed while the callback still holds a
+/// borrow of it.
 pub unsafe trait TimerHandle {
+    /// Cancel the timer, blocking until a concurrently running callback finishes.
     fn cancel(&mut self) -> bool;
+
+    /// Attempt to cancel the timer without blocking.
+    ///
+    /// Returns `Ok(true)` if the timer was active and has been deactivated, `Ok(false)` if it
+    /// was already inactive, or `Err(EBUSY)` if the timer's callback is currently running.
+    /// This is the recommended primitive for callers that cannot block while a callback
+    /// running on another CPU finishes; see the [ordering hazard](TimerHandle#scheduling-vs-cancelling)
+    /// documented above.
+    fn try_cancel(&mut self) -> Result<bool>;
+
+    /// Returns whether the timer is currently active, i.e. queued for expiry or with its
+    /// callback currently running.
+    fn is_active(&self) -> bool;
+
+    /// Returns whether the timer is currently queued for expiry.
+    fn is_queued(&self) -> bool;
+
+    /// Returns whether the timer's callback is currently running, possibly on another CPU.
+    fn callback_running(&self) -> bool;
+
+    /// Returns the time remaining until expiry, clamped to zero if the timer has already
+    /// expired.
+    fn remaining(&self) -> Ktime;
 }
 
 /// Implemented by structs that contain timer nodes.
@@ -203,6 +428,10 @@ pub unsafe trait TimerHandle {
 /// Clients of the timer API would usually safely implement this trait by using
 /// the [`impl_has_timer`] macro.
 ///
+/// A host struct that embeds more than one [`Timer`] implements this trait once
+/// per timer, distinguishing the impls with a marker `Tag` type. Hosts with a
+/// single timer can ignore `Tag`, which defaults to `()`.
+///
 /// # Safety
 ///
 /// Implementers of this trait must ensure that the implementer has a [`Timer`]
@@ -210,7 +439,7 @@ pub unsafe trait TimerHandle {
 /// implemented according to their documentation.
 ///
 /// [`impl_has_timer`]: crate::impl_has_timer
-pub unsafe trait HasTimer<U> {
+pub unsafe trait HasTimer<U, Tag = ()> {
     /// Offset of the [`Timer`] field within `Self`
     const OFFSET: usize;
 
@@ -219,10 +448,10 @@ pub unsafe trait HasTimer<U> {
     /// # Safety
     ///
     /// `ptr` must point to a valid struct of type `Self`.
-    unsafe fn raw_get_timer(ptr: *const Self) -> *const Timer<U> {
+    unsafe fn raw_get_timer(ptr: *const Self) -> *const Timer<U, Tag> {
         // SAFETY: By the safety requirement of this trait, the trait
         // implementor will have a `Timer` field at the specified offset.
-        unsafe { ptr.cast::<u8>().add(Self::OFFSET).cast::<Timer<U>>() }
+        unsafe { ptr.cast::<u8>().add(Self::OFFSET).cast::<Timer<U, Tag>>() }
     }
 
     /// Return a pointer to the struct that is embedding the [`Timer`] pointed
@@ -230,8 +459,8 @@ pub unsafe trait HasTimer<U> {
     ///
     /// # Safety
     ///
-    /// `ptr` must point to a [`Timer<T,U>`] field in a struct of type `Self`.
-    unsafe fn timer_container_of(ptr: *mut Timer<U>) -> *mut Self
+    /// `ptr` must point to a [`Timer<U, Tag>`] field in a struct of type `Self`.
+    unsafe fn timer_container_of(ptr: *mut Timer<U, Tag>) -> *mut Self
     where
         Self: Sized,
     {
@@ -241,15 +470,10 @@ pub unsafe trait HasTimer<U> {
     }
 
     #[cfg(disable)]
-    unsafe fn schedule(&mut self) {
+    unsafe fn schedule(&mut self, expires: u64, mode: TimerMode) {
         // Schedule the timer - if it is already scheduled it is removed and inserted
         unsafe {
-            bindings::hrtimer_start_range_ns(
-                c_timer_ptr,
-                expires as i64,
-                0,
-                bindings::hrtimer_mode_HRTIMER_MODE_REL,
-            );
+            bindings::hrtimer_start_range_ns(c_timer_ptr, expires as i64, 0, mode.into_raw());
         }
     }
 }
@@ -278,20 +502,31 @@ impl From<TimerRestart> for bindings::hrtimer_restart {
 }
 
 /// Implemented by structs that can the target of a timer callback.
-pub trait TimerCallback {
-    type CallbackTarget<'a>: RawTimerCallback;
+///
+/// The `Tag` parameter identifies which of a host's (possibly several)
+/// [`Timer`] fields this implementation handles; it defaults to `()`.
+pub trait TimerCallback<Tag = ()> {
+    type CallbackTarget<'a>: RawTimerCallback<Tag>;
 
     /// Called by the timer logic when the timer fires.
-    fn run(this: Self::CallbackTarget<'_>, context: TimerCallbackContext<'_, Self>) -> TimerRestart
+    ///
+    /// Returning [`TimerRestart::Restart`] without rearming `context` just fires the callback
+    /// again immediately at the same expiry; periodic timers should instead call
+    /// [`TimerCallbackContext::restart_periodic`], which both advances the expiry by a fixed
+    /// interval and returns the matching restart decision.
+    fn run(
+        this: Self::CallbackTarget<'_>,
+        context: TimerCallbackContext<'_, Self, Tag>,
+    ) -> TimerRestart
     where
         Self: Sized;
 }
 
 /// Privileged smart-pointer for timer methods which are only safe to call
 /// within a [`Timer`] callback.
-pub struct TimerCallbackContext<'a, U>(&'a Timer<U>);
+pub struct TimerCallbackContext<'a, U, Tag = ()>(&'a Timer<U, Tag>);
 
-impl<'a, U> TimerCallbackContext<'a, U> {
+impl<'a, U, Tag> TimerCallbackContext<'a, U, Tag> {
     /// Create a new [`TimerCallbackContext`]
     ///
     /// # Safety
@@ -323,11 +558,155 @@ impl<'a, U> TimerCallbackContext<'a, U> {
     pub fn forward_now(&self, interval: Ktime) -> u64 {
         self.forward(self.0.get_time(), interval)
     }
+
+    /// Rearm the timer to fire periodically every `interval`, returning the restart decision
+    /// for [`TimerCallback::run`] together with the number of overruns observed since the
+    /// timer's previous expiry.
+    ///
+    /// If `interval` is zero, the timer is treated as one-shot: it is not rearmed and
+    /// [`TimerRestart::NoRestart`] is returned with an overrun count of `0`.
+    pub fn restart_periodic(&self, interval: Ktime) -> (TimerRestart, u64) {
+        if interval.to_ns() == 0 {
+            return (TimerRestart::NoRestart, 0);
+        }
+
+        (TimerRestart::Restart, self.forward_now(interval))
+    }
 }
 
-unsafe fn c_timer_ptr<U>(timer_ptr: *const U) -> *const bindings::hrtimer
+/// The outcome of [`sleep`], [`sleep_until`] or [`sleep_range`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepResult {
+    /// The requested expiry was reached.
+    Completed,
+    /// A signal became pending before expiry, interrupting the sleep.
+    Interrupted,
+}
+
+/// Put the current task to sleep until `expires`, interpreted according to `mode` and
+/// measured against `clock`, allowing the wakeup to be deferred by up to `slack` so the
+/// kernel can coalesce it with other timers.
+///
+/// This is allocation-free and non-intrusive: the underlying `struct hrtimer_sleeper` lives
+/// on the caller's stack for the duration of the call and is canceled and destroyed again
+/// before this function returns, so no driver struct needs to embed a long-lived [`Timer`]
+/// just to block for a while.
+///
+/// If `interruptible` is `true`, a pending signal wakes the task early and
+/// [`SleepResult::Interrupted`] is returned; otherwise the sleep always runs to completion.
+///
+/// [`sleep_until`] and [`sleep_range`] are thin convenience wrappers around this function.
+pub fn sleep(
+    clock: ClockSource,
+    mut mode: TimerMode,
+    expires: Ktime,
+    slack: Ktime,
+    interruptible: bool,
+) -> SleepResult {
+    let state = if interruptible {
+        bindings::TASK_INTERRUPTIBLE
+    } else {
+        bindings::TASK_UNINTERRUPTIBLE
+    };
+
+    // SAFETY: `sleeper` is plain data, fully initialized by
+    // `hrtimer_init_sleeper_on_stack` below before any other field is accessed.
+    let mut sleeper = core::mem::MaybeUninit::<bindings::hrtimer_sleeper>::uninit();
+    let sleeper_ptr = sleeper.as_mut_ptr();
+
+    // SAFETY: `sleeper_ptr` points to a valid allocation of the right size. This arranges
+    // for the sleeper's timer to wake the current task when it fires.
+    unsafe {
+        bindings::hrtimer_init_sleeper_on_stack(
+            sleeper_ptr,
+            clock.into_raw() as i32,
+            mode.into_raw(),
+        );
+        bindings::hrtimer_set_expires_range_ns(
+            core::ptr::addr_of_mut!((*sleeper_ptr).timer),
+            expires.to_ns(),
+            slack.to_ns(),
+        );
+    }
+
+    loop {
+        // SAFETY: We are the current task and `state` is a valid task state. This must
+        // happen before arming the timer below, or we could miss the wakeup.
+        unsafe { bindings::set_current_state(state as i64) };
+
+        // SAFETY: `sleeper_ptr` was initialized above, with its expiry already set.
+        unsafe { bindings::hrtimer_sleeper_start_expires(sleeper_ptr, mode.into_raw()) };
+
+        // SAFETY: `task` is non-null until the timer callback fires and clears it; we just
+        // set our own state above, so it is safe to deschedule.
+        if unsafe { !(*sleeper_ptr).task.is_null() } {
+            unsafe { bindings::schedule() };
+        }
+
+        // SAFETY: `sleeper_ptr`'s timer was initialized above and is safe to cancel
+        // regardless of whether it already fired.
+        unsafe { bindings::hrtimer_cancel(core::ptr::addr_of_mut!((*sleeper_ptr).timer)) };
+
+        // The timer's expiry is now stored as an absolute time; any further iteration must
+        // keep treating it as such.
+        mode = TimerMode::Abs;
+
+        // SAFETY: `sleeper_ptr` was initialized above.
+        let woken_by_timer = unsafe { (*sleeper_ptr).task.is_null() };
+        // SAFETY: `get_current()` returns the current task, which is always a valid pointer
+        // to pass to `signal_pending`.
+        let woken_by_signal =
+            interruptible && unsafe { bindings::signal_pending(bindings::get_current()) != 0 };
+
+        if woken_by_timer || woken_by_signal {
+            break;
+        }
+    }
+
+    // SAFETY: Always safe to restore `TASK_RUNNING` for the current task.
+    unsafe { bindings::set_current_state(bindings::TASK_RUNNING as i64) };
+
+    // SAFETY: `sleeper_ptr`'s timer was initialized via `hrtimer_init_sleeper_on_stack` and
+    // must be destroyed before this stack frame is torn down, which is about to happen.
+    unsafe { bindings::destroy_hrtimer_on_stack(core::ptr::addr_of_mut!((*sleeper_ptr).timer)) };
+
+    // SAFETY: `sleeper_ptr` was initialized above and is still live.
+    if unsafe { (*sleeper_ptr).task.is_null() } {
+        SleepResult::Completed
+    } else {
+        SleepResult::Interrupted
+    }
+}
+
+/// Put the current task to sleep until `deadline`, measured against `clock`, allowing the
+/// wakeup to be deferred by up to `slack`.
+///
+/// See [`sleep`] for details.
+pub fn sleep_until(
+    clock: ClockSource,
+    deadline: Ktime,
+    slack: Ktime,
+    interruptible: bool,
+) -> SleepResult {
+    sleep(clock, TimerMode::Abs, deadline, slack, interruptible)
+}
+
+/// Put the current task to sleep for approximately `duration`, measured against `clock`,
+/// allowing the wakeup to be deferred by up to `slack`.
+///
+/// See [`sleep`] for details.
+pub fn sleep_range(
+    clock: ClockSource,
+    duration: Ktime,
+    slack: Ktime,
+    interruptible: bool,
+) -> SleepResult {
+    sleep(clock, TimerMode::Rel, duration, slack, interruptible)
+}
+
+unsafe fn c_timer_ptr<U, Tag>(timer_ptr: *const U) -> *const bindings::hrtimer
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     // SAFETY: `self_ptr` is a valid pointer to a `U`.
     let timer_ptr = unsafe { U::raw_get_timer(timer_ptr) };
@@ -336,16 +715,65 @@ where
     unsafe { Timer::raw_get(timer_ptr) }
 }
 
+/// Arm `c_timer_ptr` to expire anywhere in `[expires, expires + slack]`, interpreted
+/// according to `mode`. If the timer was already scheduled, it is rescheduled.
+///
+/// # Safety
+///
+/// `c_timer_ptr` must point to a valid, initialized `bindings::hrtimer`.
+unsafe fn raw_schedule_range(
+    c_timer_ptr: *mut bindings::hrtimer,
+    expires: Ktime,
+    slack: Ktime,
+    mode: TimerMode,
+) {
+    // SAFETY: `c_timer_ptr` is initialized and valid, per the safety requirements of this
+    // function.
+    unsafe {
+        bindings::hrtimer_start_range_ns(
+            c_timer_ptr,
+            expires.to_ns(),
+            slack.to_ns() as u64,
+            mode.into_raw(),
+        );
+    }
+}
+
+pub use arc::ArcTimerHandle;
+pub use boxed::BoxTimerHandle;
 pub use pin::PinTimerHandle;
 pub use pin_mut::PinMutTimerHandle;
-pub use arc::ArcTimerHandle;
 
+mod arc;
+mod boxed;
 mod pin;
 mod pin_mut;
-mod arc;
 
 /// Use to implement the [`HasTimer<T>`] trait.
 ///
+/// A host struct that embeds more than one [`Timer`] field must give each one
+/// a distinct `Tag` type, passed as a second argument to `HasTimer`, so that
+/// each field gets its own [`HasTimer`] impl. A single invocation with no tag
+/// defaults `Tag` to `()`.
+///
+/// ```ignore
+/// struct DeadlineTag;
+/// struct RetryTag;
+///
+/// #[pin_data]
+/// struct Driver {
+///     #[pin]
+///     deadline: Timer<Self, DeadlineTag>,
+///     #[pin]
+///     retry: Timer<Self, RetryTag>,
+/// }
+///
+/// impl_has_timer! {
+///     impl HasTimer<Self, DeadlineTag> for Driver { self.deadline }
+///     impl HasTimer<Self, RetryTag> for Driver { self.retry }
+/// }
+/// ```
+///
 /// See [`module`] documentation for an example.
 ///
 /// [`module`]: crate::hrtimer
@@ -357,14 +785,26 @@ macro_rules! impl_has_timer {
             for $self:ty
         { self.$field:ident }
         $($rest:tt)*
+    ) => {
+        $crate::impl_has_timer! {
+            impl$({$($generics)*})? HasTimer<$timer_type, ()> for $self { self.$field }
+            $($rest)*
+        }
+    };
+    (
+        impl$({$($generics:tt)*})?
+            HasTimer<$timer_type:ty, $tag:ty>
+            for $self:ty
+        { self.$field:ident }
+        $($rest:tt)*
     ) => {
         // SAFETY: This implementation of `raw_get_timer` only compiles if the
         // field has the right type.
-        unsafe impl$(<$($generics)*>)? $crate::hrtimer::HasTimer<$timer_type>  for $self {
+        unsafe impl$(<$($generics)*>)? $crate::hrtimer::HasTimer<$timer_type, $tag>  for $self {
             const OFFSET: usize = ::core::mem::offset_of!(Self, $field) as usize;
 
             #[inline]
-            unsafe fn raw_get_timer(ptr: *const Self) -> *const $crate::hrtimer::Timer<$timer_type> {
+            unsafe fn raw_get_timer(ptr: *const Self) -> *const $crate::hrtimer::Timer<$timer_type, $tag> {
                 // SAFETY: The caller promises that the pointer is not dangling.
                 unsafe {
                     ::core::ptr::addr_of!((*ptr).$field)
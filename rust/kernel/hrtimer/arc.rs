@@ -1,36 +1,76 @@
 use super::c_timer_ptr;
+use super::raw_schedule_range;
 use super::HasTimer;
 use super::RawTimerCallback;
 use super::Timer;
 use super::TimerCallback;
 use super::TimerCallbackContext;
 use super::TimerHandle;
+use super::TimerMode;
 use super::TimerPointer;
+use crate::error::Result;
 use crate::sync::Arc;
+use crate::time::Ktime;
 use core::mem;
 
-pub struct ArcTimerHandle<U>
+pub struct ArcTimerHandle<U, Tag = ()>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     pub(crate) inner: Arc<U>,
+    _tag: core::marker::PhantomData<Tag>,
 }
 
-unsafe impl<U> TimerHandle for ArcTimerHandle<U>
+unsafe impl<U, Tag> TimerHandle for ArcTimerHandle<U, Tag>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     fn cancel(&mut self) -> bool {
         let self_ptr = self.inner.as_ptr();
-        let timer_ptr = unsafe { <U as HasTimer<U>>::raw_get_timer(self_ptr) };
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
 
-        unsafe { Timer::<U>::raw_cancel(timer_ptr) }
+        unsafe { Timer::<U, Tag>::raw_cancel(timer_ptr) }
+    }
+
+    fn try_cancel(&mut self) -> Result<bool> {
+        let self_ptr = self.inner.as_ptr();
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_try_cancel(timer_ptr) }
+    }
+
+    fn is_active(&self) -> bool {
+        let self_ptr = self.inner.as_ptr();
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_is_active(timer_ptr) }
+    }
+
+    fn is_queued(&self) -> bool {
+        let self_ptr = self.inner.as_ptr();
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_is_queued(timer_ptr) }
+    }
+
+    fn callback_running(&self) -> bool {
+        let self_ptr = self.inner.as_ptr();
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_callback_running(timer_ptr) }
+    }
+
+    fn remaining(&self) -> Ktime {
+        let self_ptr = self.inner.as_ptr();
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_remaining(timer_ptr) }
     }
 }
 
-impl<U> Drop for ArcTimerHandle<U>
+impl<U, Tag> Drop for ArcTimerHandle<U, Tag>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     fn drop(&mut self) {
         self.cancel();
@@ -39,15 +79,15 @@ where
 
 // SAFETY: We store an `Arc` in the handle, so the pointee of the `Arc` will
 // outlive the handle.
-unsafe impl<U> TimerPointer for Arc<U>
+unsafe impl<U, Tag> TimerPointer<U, Tag> for Arc<U>
 where
     U: Send + Sync,
-    U: HasTimer<U>,
-    U: for<'a> TimerCallback<CallbackTarget<'a> = Self>,
+    U: HasTimer<U, Tag>,
+    U: for<'a> TimerCallback<Tag, CallbackTarget<'a> = Self>,
 {
-    type TimerHandle = ArcTimerHandle<U>;
+    type TimerHandle = ArcTimerHandle<U, Tag>;
 
-    fn schedule(self, expires: u64) -> ArcTimerHandle<U> {
+    fn schedule(self, expires: Ktime, mode: TimerMode) -> ArcTimerHandle<U, Tag> {
         // Schedule the timer - if it is already scheduled it is removed and
         // inserted.
 
@@ -56,24 +96,38 @@ where
         unsafe {
             bindings::hrtimer_start_range_ns(
                 c_timer_ptr(self.as_ptr()).cast_mut(),
-                expires as i64,
+                expires.to_ns(),
                 0,
-                bindings::hrtimer_mode_HRTIMER_MODE_REL,
+                mode.into_raw(),
             )
         };
 
-        ArcTimerHandle { inner: self }
+        ArcTimerHandle {
+            inner: self,
+            _tag: core::marker::PhantomData,
+        }
+    }
+
+    fn schedule_range(self, expires: Ktime, slack: Ktime, mode: TimerMode) -> ArcTimerHandle<U, Tag> {
+        // SAFETY: c_timer_ptr points to a valid hrtimer instance that was
+        // initialized by `hrtimer_init`.
+        unsafe { raw_schedule_range(c_timer_ptr(self.as_ptr()).cast_mut(), expires, slack, mode) };
+
+        ArcTimerHandle {
+            inner: self,
+            _tag: core::marker::PhantomData,
+        }
     }
 }
 
-unsafe impl<U> RawTimerCallback for Arc<U>
+unsafe impl<U, Tag> RawTimerCallback<Tag> for Arc<U>
 where
-    U: HasTimer<U>,
-    U: for<'a> TimerCallback<CallbackTarget<'a> = Self>,
+    U: HasTimer<U, Tag>,
+    U: for<'a> TimerCallback<Tag, CallbackTarget<'a> = Self>,
 {
     unsafe extern "C" fn run(ptr: *mut bindings::hrtimer) -> bindings::hrtimer_restart {
         // `Timer` is `repr(transparent)`
-        let timer_ptr = ptr.cast::<kernel::hrtimer::Timer<U>>();
+        let timer_ptr = ptr.cast::<kernel::hrtimer::Timer<U, Tag>>();
 
         // SAFETY: By C API contract `ptr` is the pointer we passed when
         // enqueing the timer, so it is a `Timer<T>` embedded in a `T`.
@@ -87,7 +141,7 @@ where
         // * We already verified that `timer_ptr` points to an initialized `Timer`
         // * This is being called from the context of a timer callback
         U::run(receiver, unsafe {
-            TimerCallbackContext::<U>::from_raw(timer_ptr.cast())
+            TimerCallbackContext::<U, Tag>::from_raw(timer_ptr.cast())
         })
         .into()
     }
@@ -0,0 +1,164 @@
+use super::c_timer_ptr;
+use super::raw_schedule_range;
+use super::HasTimer;
+use super::RawTimerCallback;
+use super::Timer;
+use super::TimerCallback;
+use super::TimerCallbackContext;
+use super::TimerHandle;
+use super::TimerMode;
+use super::TimerPointer;
+use crate::error::Result;
+use crate::time::Ktime;
+
+/// A handle for a [`Box<T>`]. When the handle exists, the timer might be
+/// armed.
+///
+/// [`Box<T>`]: Box
+pub struct BoxTimerHandle<U, Tag = ()>
+where
+    U: HasTimer<U, Tag>,
+{
+    pub(crate) inner: Box<U>,
+    _tag: core::marker::PhantomData<Tag>,
+}
+
+// SAFETY: `cancel` blocks until any in-flight callback has finished with its borrow of
+// `self.inner`, so dropping the handle (and with it the `Box`) afterwards cannot race the
+// callback.
+unsafe impl<U, Tag> TimerHandle for BoxTimerHandle<U, Tag>
+where
+    U: HasTimer<U, Tag>,
+{
+    fn cancel(&mut self) -> bool {
+        let self_ptr = self.inner.as_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: `timer_ptr` points to a valid and initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_cancel(timer_ptr) }
+    }
+
+    fn try_cancel(&mut self) -> Result<bool> {
+        let self_ptr = self.inner.as_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: `timer_ptr` points to a valid and initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_try_cancel(timer_ptr) }
+    }
+
+    fn is_active(&self) -> bool {
+        let self_ptr = self.inner.as_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: `timer_ptr` points to a valid and initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_is_active(timer_ptr) }
+    }
+
+    fn is_queued(&self) -> bool {
+        let self_ptr = self.inner.as_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: `timer_ptr` points to a valid and initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_is_queued(timer_ptr) }
+    }
+
+    fn callback_running(&self) -> bool {
+        let self_ptr = self.inner.as_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: `timer_ptr` points to a valid and initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_callback_running(timer_ptr) }
+    }
+
+    fn remaining(&self) -> Ktime {
+        let self_ptr = self.inner.as_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: `timer_ptr` points to a valid and initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_remaining(timer_ptr) }
+    }
+}
+
+impl<U, Tag> Drop for BoxTimerHandle<U, Tag>
+where
+    U: HasTimer<U, Tag>,
+{
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+// SAFETY: We own the `Box` in the handle, so the pointee of the `Box` will outlive the handle.
+unsafe impl<U, Tag> TimerPointer<U, Tag> for Box<U>
+where
+    U: Send + Sync,
+    U: HasTimer<U, Tag>,
+    U: for<'a> TimerCallback<Tag, CallbackTarget<'a> = &'a U>,
+{
+    type TimerHandle = BoxTimerHandle<U, Tag>;
+
+    fn schedule(self, expires: Ktime, mode: TimerMode) -> Self::TimerHandle {
+        // Schedule the timer - if it is already scheduled it is removed and inserted.
+
+        // SAFETY: `c_timer_ptr` points to a valid hrtimer instance that was initialized by
+        // `hrtimer_init`.
+        unsafe {
+            bindings::hrtimer_start_range_ns(
+                c_timer_ptr(self.as_ref() as *const U).cast_mut(),
+                expires.to_ns(),
+                0,
+                mode.into_raw(),
+            )
+        };
+
+        BoxTimerHandle {
+            inner: self,
+            _tag: core::marker::PhantomData,
+        }
+    }
+
+    fn schedule_range(self, expires: Ktime, slack: Ktime, mode: TimerMode) -> Self::TimerHandle {
+        // SAFETY: `c_timer_ptr` points to a valid hrtimer instance that was initialized by
+        // `hrtimer_init`.
+        unsafe {
+            raw_schedule_range(
+                c_timer_ptr(self.as_ref() as *const U).cast_mut(),
+                expires,
+                slack,
+                mode,
+            )
+        };
+
+        BoxTimerHandle {
+            inner: self,
+            _tag: core::marker::PhantomData,
+        }
+    }
+}
+
+unsafe impl<U, Tag> RawTimerCallback<Tag> for Box<U>
+where
+    U: HasTimer<U, Tag>,
+    U: for<'a> TimerCallback<Tag, CallbackTarget<'a> = &'a U>,
+{
+    unsafe extern "C" fn run(ptr: *mut bindings::hrtimer) -> bindings::hrtimer_restart {
+        // `Timer` is `repr(transparent)`
+        let timer_ptr = ptr.cast::<Timer<U, Tag>>();
+
+        // SAFETY: By C API contract `ptr` is the pointer we passed when enqueuing the timer, so
+        // it is a `Timer<U, Tag>` embedded in a `U` that is kept alive by the `Box` owned by the
+        // `BoxTimerHandle` this callback's timer was scheduled through.
+        let receiver_ptr = unsafe { U::timer_container_of(timer_ptr) };
+
+        // SAFETY: `receiver_ptr` points to a live `U`, per the above.
+        let receiver_ref = unsafe { &*receiver_ptr };
+
+        // SAFETY:
+        // * We already verified that `timer_ptr` points to an initialized `Timer`.
+        // * This is being called from the context of a timer callback.
+        U::run(receiver_ref, unsafe {
+            TimerCallbackContext::<U, Tag>::from_raw(timer_ptr.cast())
+        })
+        .into()
+    }
+}
@@ -1,10 +1,14 @@
+use super::raw_schedule_range;
 use super::HasTimer;
 use super::RawTimerCallback;
 use super::Timer;
 use super::TimerCallback;
 use super::TimerCallbackContext;
 use super::TimerHandle;
+use super::TimerMode;
 use super::TimerPointer;
+use crate::error::Result;
+use crate::time::Ktime;
 use core::pin::Pin;
 
 /// A handle for a `Pin<&HasTimer>`. When the handle exists, the timer might be
@@ -13,32 +17,78 @@ use core::pin::Pin;
 /// # Invariants
 ///
 /// - The `Timer` in `inner` is valid and initialized.
-pub struct PinTimerHandle<'a, U>
+pub struct PinTimerHandle<'a, U, Tag = ()>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     pub(crate) inner: Pin<&'a U>,
+    _tag: core::marker::PhantomData<Tag>,
 }
 
 // SAFETY: We cancel the timer when the handle is dropped. The implementation of
 // the `cancel` method will block if the timer handler is running.
-unsafe impl<'a, U> TimerHandle for PinTimerHandle<'a, U>
+unsafe impl<'a, U, Tag> TimerHandle for PinTimerHandle<'a, U, Tag>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     fn cancel(&mut self) -> bool {
         let self_ptr = self.inner.get_ref() as *const U;
-        let timer_ptr = unsafe { <U as HasTimer<U>>::raw_get_timer(self_ptr) };
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
 
         // SAFETY: By type invariant, `timer_ptr` points to a valid and
         // initialized `Timer`.
-        unsafe { Timer::<U>::raw_cancel(timer_ptr) }
+        unsafe { Timer::<U, Tag>::raw_cancel(timer_ptr) }
+    }
+
+    fn try_cancel(&mut self) -> Result<bool> {
+        let self_ptr = self.inner.get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: By type invariant, `timer_ptr` points to a valid and
+        // initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_try_cancel(timer_ptr) }
+    }
+
+    fn is_active(&self) -> bool {
+        let self_ptr = self.inner.get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: By type invariant, `timer_ptr` points to a valid and
+        // initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_is_active(timer_ptr) }
+    }
+
+    fn is_queued(&self) -> bool {
+        let self_ptr = self.inner.get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: By type invariant, `timer_ptr` points to a valid and
+        // initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_is_queued(timer_ptr) }
+    }
+
+    fn callback_running(&self) -> bool {
+        let self_ptr = self.inner.get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: By type invariant, `timer_ptr` points to a valid and
+        // initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_callback_running(timer_ptr) }
+    }
+
+    fn remaining(&self) -> Ktime {
+        let self_ptr = self.inner.get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        // SAFETY: By type invariant, `timer_ptr` points to a valid and
+        // initialized `Timer`.
+        unsafe { Timer::<U, Tag>::raw_remaining(timer_ptr) }
     }
 }
 
-impl<'a, U> Drop for PinTimerHandle<'a, U>
+impl<'a, U, Tag> Drop for PinTimerHandle<'a, U, Tag>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     fn drop(&mut self) {
         self.cancel();
@@ -47,15 +97,15 @@ where
 
 // SAFETY: We capture the lifetime of `Self` when we create a `PinTimerHandle`,
 // so `Self` will outlive the handle.
-unsafe impl<'a, U> TimerPointer for Pin<&'a U>
+unsafe impl<'a, U, Tag> TimerPointer<U, Tag> for Pin<&'a U>
 where
     U: Send + Sync,
-    U: HasTimer<U>,
-    U: TimerCallback<CallbackTarget<'a> = Self>,
+    U: HasTimer<U, Tag>,
+    U: TimerCallback<Tag, CallbackTarget<'a> = Self>,
 {
-    type TimerHandle = PinTimerHandle<'a, U>;
+    type TimerHandle = PinTimerHandle<'a, U, Tag>;
 
-    fn schedule(self, expires: u64) -> Self::TimerHandle {
+    fn schedule(self, expires: Ktime, mode: TimerMode) -> Self::TimerHandle {
         use core::ops::Deref;
 
         // Cast to pointer
@@ -65,29 +115,48 @@ where
         unsafe {
             bindings::hrtimer_start_range_ns(
                 U::c_timer_ptr(self_ptr).cast_mut(),
-                expires as i64,
+                expires.to_ns(),
                 0,
-                bindings::hrtimer_mode_HRTIMER_MODE_REL,
+                mode.into_raw(),
             );
         }
 
-        PinTimerHandle { inner: self }
+        PinTimerHandle {
+            inner: self,
+            _tag: core::marker::PhantomData,
+        }
+    }
+
+    fn schedule_range(self, expires: Ktime, slack: Ktime, mode: TimerMode) -> Self::TimerHandle {
+        use core::ops::Deref;
+
+        // Cast to pointer
+        let self_ptr = self.deref() as *const U;
+
+        // SAFETY: `U::c_timer_ptr` points to a valid hrtimer instance that was
+        // initialized by `hrtimer_init`.
+        unsafe { raw_schedule_range(U::c_timer_ptr(self_ptr).cast_mut(), expires, slack, mode) };
+
+        PinTimerHandle {
+            inner: self,
+            _tag: core::marker::PhantomData,
+        }
     }
 }
 
-unsafe impl<'a, U> RawTimerCallback for Pin<&'a U>
+unsafe impl<'a, U, Tag> RawTimerCallback<Tag> for Pin<&'a U>
 where
-    U: HasTimer<U>,
-    U: TimerCallback<CallbackTarget<'a> = Self>,
+    U: HasTimer<U, Tag>,
+    U: TimerCallback<Tag, CallbackTarget<'a> = Self>,
 {
     unsafe extern "C" fn run(ptr: *mut bindings::hrtimer) -> bindings::hrtimer_restart {
         // `Timer` is `repr(transparent)`
-        let timer_ptr = ptr as *mut Timer<U>;
+        let timer_ptr = ptr as *mut Timer<U, Tag>;
         let receiver_ptr = unsafe { U::timer_container_of(timer_ptr) };
         let receiver_ref = unsafe { &*receiver_ptr };
         let receiver_pin = unsafe { Pin::new_unchecked(receiver_ref) };
         U::run(receiver_pin, unsafe {
-            TimerCallbackContext::<U>::from_raw(timer_ptr.cast())
+            TimerCallbackContext::<U, Tag>::from_raw(timer_ptr.cast())
         })
         .into()
     }
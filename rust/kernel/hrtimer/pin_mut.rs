@@ -1,37 +1,80 @@
 use super::c_timer_ptr;
+use super::raw_schedule_range;
 use super::HasTimer;
 use super::Timer;
 use super::TimerCallback;
 use super::TimerCallbackContext;
 use super::TimerHandle;
+use super::TimerMode;
 use super::TimerPointer;
+use crate::error::Result;
+use crate::time::Ktime;
 use core::pin::Pin;
 
-pub struct PinMutTimerHandle<'a, U>
+pub struct PinMutTimerHandle<'a, U, Tag = ()>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     pub(crate) inner: Pin<&'a mut U>,
+    _tag: core::marker::PhantomData<Tag>,
 }
 
-unsafe impl<'a, U> TimerHandle for PinMutTimerHandle<'a, U>
+unsafe impl<'a, U, Tag> TimerHandle for PinMutTimerHandle<'a, U, Tag>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     fn cancel(&mut self) -> bool {
         let timer_ptr = unsafe {
-            <U as HasTimer<U>>::raw_get_timer(unsafe {
+            <U as HasTimer<U, Tag>>::raw_get_timer(unsafe {
                 self.inner.as_mut().get_unchecked_mut() as *mut _
             })
         };
 
-        unsafe { Timer::<U>::raw_cancel(timer_ptr) }
+        unsafe { Timer::<U, Tag>::raw_cancel(timer_ptr) }
+    }
+
+    fn try_cancel(&mut self) -> Result<bool> {
+        let timer_ptr = unsafe {
+            <U as HasTimer<U, Tag>>::raw_get_timer(unsafe {
+                self.inner.as_mut().get_unchecked_mut() as *mut _
+            })
+        };
+
+        unsafe { Timer::<U, Tag>::raw_try_cancel(timer_ptr) }
+    }
+
+    fn is_active(&self) -> bool {
+        let self_ptr = self.inner.as_ref().get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_is_active(timer_ptr) }
+    }
+
+    fn is_queued(&self) -> bool {
+        let self_ptr = self.inner.as_ref().get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_is_queued(timer_ptr) }
+    }
+
+    fn callback_running(&self) -> bool {
+        let self_ptr = self.inner.as_ref().get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_callback_running(timer_ptr) }
+    }
+
+    fn remaining(&self) -> Ktime {
+        let self_ptr = self.inner.as_ref().get_ref() as *const U;
+        let timer_ptr = unsafe { <U as HasTimer<U, Tag>>::raw_get_timer(self_ptr) };
+
+        unsafe { Timer::<U, Tag>::raw_remaining(timer_ptr) }
     }
 }
 
-impl<'a, U> Drop for PinMutTimerHandle<'a, U>
+impl<'a, U, Tag> Drop for PinMutTimerHandle<'a, U, Tag>
 where
-    U: HasTimer<U>,
+    U: HasTimer<U, Tag>,
 {
     fn drop(&mut self) {
         self.cancel();
@@ -40,15 +83,15 @@ where
 
 // SAFETY: We capture the lifetime of `Self` when we create a
 // `PinMutTimerHandle`, so `Self` will outlive the handle.
-unsafe impl<'a, U> TimerPointer<U> for Pin<&'a mut U>
+unsafe impl<'a, U, Tag> TimerPointer<U, Tag> for Pin<&'a mut U>
 where
     U: Send + Sync,
-    U: HasTimer<U>,
-    U: TimerCallback,
+    U: HasTimer<U, Tag>,
+    U: TimerCallback<Tag>,
 {
-    type TimerHandle = PinMutTimerHandle<'a, U>;
+    type TimerHandle = PinMutTimerHandle<'a, U, Tag>;
 
-    fn schedule(self, expires: u64) -> Self::TimerHandle {
+    fn schedule(self, expires: Ktime, mode: TimerMode) -> Self::TimerHandle {
         use core::ops::Deref;
 
         // Cast to pointer
@@ -58,23 +101,42 @@ where
         unsafe {
             bindings::hrtimer_start_range_ns(
                 c_timer_ptr(self_ptr).cast_mut(),
-                expires as i64,
+                expires.to_ns(),
                 0,
-                bindings::hrtimer_mode_HRTIMER_MODE_REL,
+                mode.into_raw(),
             );
         }
 
-        PinMutTimerHandle { inner: self }
+        PinMutTimerHandle {
+            inner: self,
+            _tag: core::marker::PhantomData,
+        }
+    }
+
+    fn schedule_range(self, expires: Ktime, slack: Ktime, mode: TimerMode) -> Self::TimerHandle {
+        use core::ops::Deref;
+
+        // Cast to pointer
+        let self_ptr = self.deref() as *const U;
+
+        // SAFETY: c_timer_ptr points to a valid hrtimer instance that was
+        // initialized by `hrtimer_init`.
+        unsafe { raw_schedule_range(c_timer_ptr(self_ptr).cast_mut(), expires, slack, mode) };
+
+        PinMutTimerHandle {
+            inner: self,
+            _tag: core::marker::PhantomData,
+        }
     }
 
     unsafe extern "C" fn run(ptr: *mut bindings::hrtimer) -> bindings::hrtimer_restart {
         // `Timer` is `repr(transparent)`
-        let timer_ptr = ptr as *mut Timer<U>;
+        let timer_ptr = ptr as *mut Timer<U, Tag>;
         let receiver_ptr = unsafe { U::timer_container_of(timer_ptr) };
         let receiver_ref = unsafe { &mut *receiver_ptr };
         let receiver_pin = unsafe { Pin::new_unchecked(receiver_ref) };
         U::run(&receiver_pin, unsafe {
-            TimerCallbackContext::<U>::from_raw(timer_ptr.cast())
+            TimerCallbackContext::<U, Tag>::from_raw(timer_ptr.cast())
         })
         .into()
     }
@@ -146,19 +146,163 @@ macro_rules! kunit_assert {
     };
 }
 
+/// Prints the rich "Expected LEFT == RIGHT, but ..." failure message for
+/// [`kunit_assert_eq!`], rendering both operands' [`core::fmt::Debug`] output.
+///
+/// Public but hidden since it should only be used from the `kunit_assert_eq!` macro.
+///
+/// KUnit's own `kunit_binary_assert` only understands a handful of integer types, so instead of
+/// trying to populate it generically, this renders both sides with a plain Rust formatter and
+/// writes the result straight to KUnit's log through [`err`], which works for any
+/// `Debug`-implementing type.
+#[doc(hidden)]
+pub fn kunit_binary_assert_failed(
+    name: &str,
+    left_expr: &str,
+    right_expr: &str,
+    left_val: fmt::Arguments<'_>,
+    right_val: fmt::Arguments<'_>,
+) {
+    err(format_args!(
+        "    # {name}: Expected {left_expr} == {right_expr}, but\n"
+    ));
+    err(format_args!("      {left_expr} == {left_val}\n"));
+    err(format_args!("      {right_expr} == {right_val}\n"));
+}
+
 /// Asserts that two expressions are equal to each other (using [`PartialEq`]).
 ///
 /// Public but hidden since it should only be used from generated tests.
 ///
 /// Unlike the one in `core`, this one does not panic; instead, it is mapped to the KUnit
 /// facilities. See [`assert!`] for more details.
+///
+/// Unlike a plain `$left == $right` forwarded to [`kunit_assert!`], a failure here also renders
+/// both operands' actual values (via [`kunit_binary_assert_failed`]) before aborting, so the
+/// test log shows what was actually compared instead of just the source expression.
 #[doc(hidden)]
 #[macro_export]
 macro_rules! kunit_assert_eq {
     ($name:literal, $file:literal, $diff:expr, $left:expr, $right:expr $(,)?) => {{
-        // For the moment, we just forward to the expression assert because, for binary asserts,
-        // KUnit supports only a few types (e.g. integers).
-        $crate::kunit_assert!($name, $file, $diff, $left == $right);
+        match (&$left, &$right) {
+            (left_val, right_val) => {
+                if left_val != right_val {
+                    $crate::kunit::kunit_binary_assert_failed(
+                        $name,
+                        core::stringify!($left),
+                        core::stringify!($right),
+                        format_args!("{left_val:?}"),
+                        format_args!("{right_val:?}"),
+                    );
+                }
+
+                $crate::kunit_assert!($name, $file, $diff, left_val == right_val);
+            }
+        }
+    }};
+}
+
+/// Computes the next parameter for a [`kunit_case_param!`]-generated `generate_params` callback.
+///
+/// `prev` is either NULL (meaning "start from the beginning") or a pointer previously returned
+/// by this function for the same `params` slice; the result is a pointer to the following
+/// element, or NULL once `params` is exhausted.
+///
+/// Public but hidden since it should only be used from generated tests.
+///
+/// # Safety
+///
+/// `prev` must be NULL, or a pointer this function previously returned for `params`.
+#[doc(hidden)]
+pub unsafe fn kunit_next_param<T>(prev: *const c_void, params: &'static [T]) -> *const c_void {
+    let prev = prev as *const T;
+
+    let next = if prev.is_null() {
+        params.as_ptr()
+    } else {
+        // SAFETY: By the caller's contract, `prev` is a pointer previously returned by
+        // this function for `params`, so it is either `params.as_ptr()` or the result of
+        // a previous in-bounds `add(1)`, and therefore safe to advance by one more
+        // element as long as it does not run past the end of `params` (checked below).
+        unsafe { prev.add(1) }
+    };
+
+    if next >= params.as_ptr().wrapping_add(params.len()) {
+        core::ptr::null()
+    } else {
+        next as *const c_void
+    }
+}
+
+/// Renders `args` into `desc`, KUnit's fixed 256-byte parameter description buffer, truncating
+/// rather than overflowing it if the rendered description does not fit.
+///
+/// Public but hidden since it should only be used from generated tests.
+#[doc(hidden)]
+pub fn kunit_describe_param(desc: &mut [core::ffi::c_char; 256], args: fmt::Arguments<'_>) {
+    // SAFETY: `c_char` and `u8` have the same size and alignment, and any bit pattern is
+    // valid for both.
+    let buf = unsafe { &mut *(desc as *mut [core::ffi::c_char; 256] as *mut [u8; 256]) };
+
+    if let Ok(mut writer) = crate::str::BufferWriter::new(buf) {
+        let _ = fmt::write(&mut writer, args);
+    }
+}
+
+/// Like [`kunit_case!`], but builds a parameterized test case that runs `$test_fn` once for each
+/// element of the `'static` slice `$params`, reporting each element's [`core::fmt::Debug`]
+/// rendering as KUnit's per-invocation parameter description.
+///
+/// `$test_fn` is called as `$test_fn(test, param)`, where `test: *mut crate::bindings::kunit` is
+/// the pointer KUnit passed in and `param: &$elem_ty` is the element being tested this
+/// invocation; the C-side `generate_params`/`param_value` plumbing that makes this possible is
+/// generated by this macro, unlike the plain `run_case` in [`kunit_case!`], which the caller
+/// writes by hand.
+#[macro_export]
+macro_rules! kunit_case_param {
+    ($name:ident, $params:expr, $elem_ty:ty, $test_fn:expr) => {{
+        static PARAMS: &[$elem_ty] = $params;
+
+        unsafe extern "C" fn generate_params(
+            prev: *const core::ffi::c_void,
+            desc: *mut core::ffi::c_char,
+        ) -> *const core::ffi::c_void {
+            // SAFETY: KUnit only ever passes `prev` as NULL or as this function's own
+            // previous return value for `PARAMS`.
+            let next = unsafe { $crate::kunit::kunit_next_param(prev, PARAMS) };
+
+            if !next.is_null() && !desc.is_null() {
+                // SAFETY: `next` was just computed by `kunit_next_param` to point to a
+                // live element of `PARAMS`.
+                let param = unsafe { &*(next as *const $elem_ty) };
+                // SAFETY: `desc` points to a live 256-byte buffer, per KUnit's
+                // `generate_params` calling convention.
+                let desc = unsafe { &mut *(desc as *mut [core::ffi::c_char; 256]) };
+                $crate::kunit::kunit_describe_param(desc, format_args!("{:?}", param));
+            }
+
+            next
+        }
+
+        unsafe extern "C" fn run_case(test: *mut $crate::bindings::kunit) {
+            // SAFETY: KUnit only invokes a parameterized case's `run_case` with
+            // `param_value` set to a pointer `generate_params` above returned, which is
+            // always a live element of `PARAMS`.
+            let param = unsafe { &*((*test).param_value as *const $elem_ty) };
+            ($test_fn)(test, param);
+        }
+
+        $crate::bindings::kunit_case {
+            run_case: Some(run_case),
+            name: $crate::c_str!(core::stringify!($name)).as_char_ptr(),
+            generate_params: Some(generate_params),
+            attr: $crate::bindings::kunit_attributes {
+                speed: $crate::bindings::kunit_speed_KUNIT_SPEED_UNSET,
+            },
+            status: $crate::bindings::kunit_status_KUNIT_SUCCESS,
+            module_name: core::ptr::null_mut(),
+            log: core::ptr::null_mut(),
+        }
     }};
 }
 
@@ -169,6 +313,12 @@ macro_rules! kunit_assert_eq {
 ///
 /// The `kunit_unsafe_test_suite!` macro expects a NULL-terminated list of test cases. This macro
 /// can be invoked without parameters to generate the delimiter.
+///
+/// An optional third argument sets the case's KUnit speed attribute (one of the
+/// `bindings::kunit_speed_*` constants, e.g. `bindings::kunit_speed_KUNIT_SPEED_SLOW` or
+/// `..._VERY_SLOW`), which lets `kunit.py run --filter "speed>normal"`-style invocations skip
+/// slow Rust cases out of a fast CI pass, the same as it already does for C ones. Omitting it
+/// keeps the previous `KUNIT_SPEED_UNSET` default.
 #[macro_export]
 macro_rules! kunit_case {
     () => {
@@ -183,11 +333,14 @@ macro_rules! kunit_case {
         }
     };
     ($name:ident, $run_case:ident) => {
+        $crate::kunit_case!($name, $run_case, $crate::bindings::kunit_speed_KUNIT_SPEED_UNSET)
+    };
+    ($name:ident, $run_case:ident, $speed:expr) => {
         $crate::bindings::kunit_case {
             run_case: Some($run_case),
             name: $crate::c_str!(core::stringify!($name)).as_char_ptr(),
             generate_params: None,
-            attr: bindings::kunit_attributes { speed: bindings::kunit_speed_KUNIT_SPEED_UNSET },
+            attr: bindings::kunit_attributes { speed: $speed },
             status: $crate::bindings::kunit_status_KUNIT_SUCCESS,
             module_name: core::ptr::null_mut(),
             log: core::ptr::null_mut(),
@@ -217,9 +370,21 @@ macro_rules! kunit_case {
 /// };
 /// crate::kunit_unsafe_test_suite!(suite_name, KUNIT_TEST_CASES);
 /// ```
+///
+/// An optional third argument sets the suite's own KUnit speed attribute, threaded through the
+/// same way as [`kunit_case!`]'s; most suites should leave this at the default
+/// `KUNIT_SPEED_UNSET` and tag individual slow cases instead, since filtering is normally done
+/// per case.
 #[macro_export]
 macro_rules! kunit_unsafe_test_suite {
     ($name:ident, $test_cases:ident) => {
+        $crate::kunit_unsafe_test_suite!(
+            $name,
+            $test_cases,
+            $crate::bindings::kunit_speed_KUNIT_SPEED_UNSET
+        );
+    };
+    ($name:ident, $test_cases:ident, $speed:expr) => {
         const _: () = {
             static KUNIT_TEST_SUITE_NAME: [i8; 256] = {
                 let name_u8 = core::stringify!($name).as_bytes();
@@ -241,7 +406,7 @@ macro_rules! kunit_unsafe_test_suite {
                     test_cases: unsafe { $test_cases.as_mut_ptr() },
                     suite_init: None,
                     suite_exit: None,
-                    attr: bindings::kunit_attributes { speed: bindings::kunit_speed_KUNIT_SPEED_UNSET },
+                    attr: bindings::kunit_attributes { speed: $speed },
                     init: None,
                     exit: None,
                     status_comment: [0; 256usize],
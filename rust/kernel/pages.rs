@@ -2,10 +2,16 @@
 
 //! Kernel page allocation and management.
 //!
-//! This module currently provides limited support. It supports pages of order 0
-//! for most operations. Page allocation flags are fixed.
-
-use crate::{bindings, error::code::*, error::Result, PAGE_SIZE};
+//! This module provides support for allocating and mapping physical pages, including
+//! higher-order (multi-page) allocations.
+
+use crate::{
+    bindings,
+    error::code::*,
+    error::Result,
+    io_buffer::{IoBufferReader, IoBufferWriter},
+    PAGE_SIZE,
+};
 use core::{marker::PhantomData, ptr};
 
 /// A set of physical pages.
@@ -23,12 +29,16 @@ pub struct Pages<const ORDER: u32> {
 impl<const ORDER: u32> Pages<ORDER> {
     /// Allocates a new set of contiguous pages.
     pub fn new() -> Result<Self> {
-        let pages = unsafe {
-            bindings::alloc_pages(
-                bindings::GFP_KERNEL | bindings::__GFP_ZERO | bindings::___GFP_HIGHMEM,
-                ORDER,
-            )
-        };
+        Self::alloc(bindings::GFP_KERNEL | bindings::__GFP_ZERO | bindings::___GFP_HIGHMEM)
+    }
+
+    /// Allocates a new set of contiguous pages using the given GFP flags.
+    ///
+    /// This allows callers to allocate from contexts that cannot sleep (`GFP_ATOMIC`)
+    /// or to opt out of zeroing the returned memory, unlike [`Pages::new`], which
+    /// always allocates with a fixed set of flags.
+    pub fn alloc(flags: bindings::gfp_t) -> Result<Self> {
+        let pages = unsafe { bindings::alloc_pages(flags, ORDER) };
         if pages.is_null() {
             return Err(ENOMEM);
         }
@@ -46,26 +56,46 @@ impl<const ORDER: u32> Pages<ORDER> {
     pub unsafe fn from_raw(ptr: *mut bindings::page) -> Self {
         Self { pages: ptr }
     }
-}
 
-impl Pages<0> {
+    /// Bounds-checks `[offset, offset + len)` against the size of this allocation and invokes
+    /// `f` once per page crossed by the range, passing it a pointer into the mapping of that
+    /// page positioned at the sub-range's starting offset, along with the sub-range's length.
+    ///
+    /// `kmap`/`kmap_local` only map a single page at a time, so for `ORDER > 0` a range that
+    /// spans more than one page is split at page boundaries and each sub-range is mapped and
+    /// unmapped in turn.
     #[inline(always)]
-    fn check_offset_and_map<I: MappingInfo>(
+    fn with_pointer_into_page<I: MappingInfo>(
         &self,
         offset: usize,
         len: usize,
-    ) -> Result<PageMapping<'_, I>>
+        mut f: impl FnMut(*mut u8, usize) -> Result,
+    ) -> Result
     where
-        Pages<0>: MappingActions<I>,
+        Self: MappingActions<I>,
     {
         let end = offset.checked_add(len).ok_or(EINVAL)?;
-        if end as u32 > PAGE_SIZE {
+        if end as u64 > (PAGE_SIZE as u64) << ORDER {
             return Err(EINVAL);
         }
 
-        let mapping = <Self as MappingActions<I>>::map(self);
+        let mut done = 0;
+        while done < len {
+            let cur = offset + done;
+            let page_index = cur / PAGE_SIZE as usize;
+            let offset_in_page = cur % PAGE_SIZE as usize;
+            let chunk = core::cmp::min(len - done, PAGE_SIZE as usize - offset_in_page);
+
+            let mapping = <Self as MappingActions<I>>::map(self, page_index);
+            f(
+                unsafe { (mapping.ptr as *mut u8).add(offset_in_page) },
+                chunk,
+            )?;
 
-        Ok(mapping)
+            done += chunk;
+        }
+
+        Ok(())
     }
 
     #[inline(always)]
@@ -76,12 +106,14 @@ impl Pages<0> {
         len: usize,
     ) -> Result
     where
-        Pages<0>: MappingActions<I>,
+        Self: MappingActions<I>,
     {
-        let mapping = self.check_offset_and_map::<I>(offset, len)?;
-
-        unsafe { ptr::copy_nonoverlapping((mapping.ptr as *mut u8).add(offset), dest, len) };
-        Ok(())
+        let mut copied = 0;
+        self.with_pointer_into_page::<I>(offset, len, |src, chunk| {
+            unsafe { ptr::copy_nonoverlapping(src, dest.add(copied), chunk) };
+            copied += chunk;
+            Ok(())
+        })
     }
 
     /// Maps the pages and reads from them into the given buffer.
@@ -121,12 +153,14 @@ impl Pages<0> {
         len: usize,
     ) -> Result
     where
-        Pages<0>: MappingActions<I>,
+        Self: MappingActions<I>,
     {
-        let mapping = self.check_offset_and_map::<I>(offset, len)?;
-
-        unsafe { ptr::copy_nonoverlapping(src, (mapping.ptr as *mut u8).add(offset), len) };
-        Ok(())
+        let mut written = 0;
+        self.with_pointer_into_page::<I>(offset, len, |dst, chunk| {
+            unsafe { ptr::copy_nonoverlapping(src.add(written), dst, chunk) };
+            written += chunk;
+            Ok(())
+        })
     }
 
     /// Maps the pages and writes into them from the given buffer.
@@ -174,9 +208,36 @@ impl Pages<0> {
         unsafe { self.write_internal::<LocalMappingInfo>(src, offset, len) }
     }
 
+    /// Copies `len` bytes starting at `offset` directly from a user-space buffer into this
+    /// page, without bouncing through an intermediate kernel buffer.
+    pub fn copy_from_user_slice(
+        &self,
+        reader: &mut impl IoBufferReader,
+        offset: usize,
+        len: usize,
+    ) -> Result {
+        self.with_pointer_into_page::<LocalMappingInfo>(offset, len, |dst, chunk| {
+            // SAFETY: `dst` is valid for writes of `chunk` bytes by `with_pointer_into_page`.
+            unsafe { reader.read_raw(dst, chunk) }
+        })
+    }
+
+    /// Copies `len` bytes starting at `offset` directly from this page into a user-space
+    /// buffer, without bouncing through an intermediate kernel buffer.
+    pub fn copy_to_user_slice(
+        &self,
+        writer: &mut impl IoBufferWriter,
+        offset: usize,
+        len: usize,
+    ) -> Result {
+        self.with_pointer_into_page::<LocalMappingInfo>(offset, len, |src, chunk| {
+            // SAFETY: `src` is valid for reads of `chunk` bytes by `with_pointer_into_page`.
+            unsafe { writer.write_raw(src, chunk) }
+        })
+    }
+
     /// Copy src into `self`.
     pub fn copy_from_slice(&mut self, src: &[u8]) -> Result {
-
         if src.len() as u32 > PAGE_SIZE {
             return Err(EINVAL);
         }
@@ -189,36 +250,63 @@ impl Pages<0> {
     /// Maps the page at index 0.
     #[inline(always)]
     pub fn kmap(&self) -> PageMapping<'_, NormalMappingInfo> {
-        let ptr = unsafe { bindings::kmap(self.pages) };
+        self.kmap_index(0)
+    }
+
+    /// Atomically maps the page at index 0.
+    #[inline(always)]
+    pub fn kmap_atomic(&self) -> PageMapping<'_, AtomicMappingInfo> {
+        self.kmap_atomic_index(0)
+    }
+
+    /// Locally maps the page at index 0
+    #[inline(always)]
+    pub fn kmap_local(&self) -> PageMapping<'_, LocalMappingInfo> {
+        self.kmap_local_index(0)
+    }
+
+    /// Maps the page at the given `index` within this `2^ORDER`-page allocation.
+    #[inline(always)]
+    fn kmap_index(&self, index: usize) -> PageMapping<'_, NormalMappingInfo> {
+        // SAFETY: By the type invariants, `self.pages` points to `2^ORDER` pages, and `index`
+        // is bounds-checked against that range by `Pages::with_pointer_into_page`.
+        let page = unsafe { self.pages.add(index) };
+        let ptr = unsafe { bindings::kmap(page) };
 
         PageMapping {
-            page: self.pages,
+            page,
             ptr,
             _phantom: PhantomData,
             _phantom2: PhantomData,
         }
     }
 
-    /// Atomically maps the page at index 0.
+    /// Atomically maps the page at the given `index` within this `2^ORDER`-page allocation.
     #[inline(always)]
-    pub fn kmap_atomic(&self) -> PageMapping<'_, AtomicMappingInfo> {
-        let ptr = unsafe { bindings::kmap_atomic(self.pages) };
+    fn kmap_atomic_index(&self, index: usize) -> PageMapping<'_, AtomicMappingInfo> {
+        // SAFETY: By the type invariants, `self.pages` points to `2^ORDER` pages, and `index`
+        // is bounds-checked against that range by `Pages::with_pointer_into_page`.
+        let page = unsafe { self.pages.add(index) };
+        let ptr = unsafe { bindings::kmap_atomic(page) };
 
         PageMapping {
-            page: self.pages,
+            page,
             ptr,
             _phantom: PhantomData,
             _phantom2: PhantomData,
         }
     }
 
-    /// Locally maps the page at index 0
+    /// Locally maps the page at the given `index` within this `2^ORDER`-page allocation.
     #[inline(always)]
-    pub fn kmap_local(&self) -> PageMapping<'_, LocalMappingInfo> {
-        let ptr = unsafe { bindings::kmap_local_page(self.pages) };
+    fn kmap_local_index(&self, index: usize) -> PageMapping<'_, LocalMappingInfo> {
+        // SAFETY: By the type invariants, `self.pages` points to `2^ORDER` pages, and `index`
+        // is bounds-checked against that range by `Pages::with_pointer_into_page`.
+        let page = unsafe { self.pages.add(index) };
+        let ptr = unsafe { bindings::kmap_local_page(page) };
 
         PageMapping {
-            page: self.pages,
+            page,
             ptr,
             _phantom: PhantomData,
             _phantom2: PhantomData,
@@ -234,109 +322,89 @@ impl<const ORDER: u32> Drop for Pages<ORDER> {
 }
 
 /// Specifies the type of page mapping
-pub trait MappingInfo {}
-
-/// Encapsulates methods to map and unmap pages
-pub trait MappingActions<I: MappingInfo>
-where
-    Pages<0>: MappingActions<I>,
-{
-    /// Map a page into the kernel address scpace
-    fn map(pages: &Pages<0>) -> PageMapping<'_, I>;
-
+pub trait MappingInfo {
     /// Unmap a page specified by `mapping`
     ///
     /// # Safety
     ///
     /// Must only be called by `PageMapping::drop()`.
-    unsafe fn unmap(mapping: &PageMapping<'_, I>);
+    unsafe fn unmap(mapping: &PageMapping<'_, Self>)
+    where
+        Self: Sized;
+}
+
+/// Encapsulates methods to map pages of a `Pages<ORDER>` allocation
+pub trait MappingActions<I: MappingInfo> {
+    /// Map the page at `index` within this allocation into the kernel address space
+    fn map(pages: &Self, index: usize) -> PageMapping<'_, I>;
 }
 
 /// A type state indicating that pages were mapped with `kmap_atomic`
 pub struct AtomicMappingInfo;
-impl MappingInfo for AtomicMappingInfo {}
-
-/// A type state indicating that pages were mapped with `kmap`
-pub struct NormalMappingInfo;
-impl MappingInfo for NormalMappingInfo {}
-
-/// A type state indicating that pages were mapped using `kmap_local_page`
-pub struct LocalMappingInfo;
-impl MappingInfo for LocalMappingInfo {}
-
-/// Mapping actions to map and unmap pages with the `kmap_atomic` interface
-impl MappingActions<AtomicMappingInfo> for Pages<0> {
+impl MappingInfo for AtomicMappingInfo {
     #[inline(always)]
-    fn map(pages: &Pages<0>) -> PageMapping<'_, AtomicMappingInfo> {
-        pages.kmap_atomic()
+    unsafe fn unmap(mapping: &PageMapping<'_, Self>) {
+        // SAFETY: An instance of `PageMapping` is created only when `kmap_atomic`
+        // succeeded for the given page, so it is safe to unmap it here.
+        unsafe { bindings::kunmap_atomic(mapping.ptr) };
     }
+}
 
-    /// Unmap a page specified by `mapping`
-    ///
-    /// # Safety
-    ///
-    /// Must only be called by `PageMapping::drop()`.
+/// A type state indicating that pages were mapped with `kmap`
+pub struct NormalMappingInfo;
+impl MappingInfo for NormalMappingInfo {
     #[inline(always)]
-    unsafe fn unmap(mapping: &PageMapping<'_, AtomicMappingInfo>) {
+    unsafe fn unmap(mapping: &PageMapping<'_, Self>) {
         // SAFETY: An instance of `PageMapping` is created only when `kmap`
         // succeeded for the given page, so it is safe to unmap it here.
-        unsafe { bindings::kunmap_atomic(mapping.ptr) };
+        unsafe { bindings::kunmap(mapping.page) };
     }
 }
 
-/// Mapping actions to map and unmap pages with the regular `kmap` interface
-impl MappingActions<NormalMappingInfo> for Pages<0> {
+/// A type state indicating that pages were mapped using `kmap_local_page`
+pub struct LocalMappingInfo;
+impl MappingInfo for LocalMappingInfo {
     #[inline(always)]
-    fn map(pages: &Pages<0>) -> PageMapping<'_, NormalMappingInfo> {
-        pages.kmap()
+    unsafe fn unmap(mapping: &PageMapping<'_, Self>) {
+        // SAFETY: An instance of `PageMapping` is created only when `kmap_local_page`
+        // succeeded for the given page, so it is safe to unmap it here.
+        unsafe { bindings::kunmap_local(mapping.ptr) };
     }
+}
 
-    /// Unmap a page specified by `mapping`
-    ///
-    /// # Safety
-    ///
-    /// Must only be called by `PageMapping::drop()`.
+/// Mapping actions to map pages with the `kmap_atomic` interface
+impl<const ORDER: u32> MappingActions<AtomicMappingInfo> for Pages<ORDER> {
     #[inline(always)]
-    unsafe fn unmap(mapping: &PageMapping<'_, NormalMappingInfo>) {
-        // SAFETY: An instance of `PageMapping` is created only when `kmap`
-        // succeeded for the given page, so it is safe to unmap it here.
-        unsafe { bindings::kunmap(mapping.page) };
+    fn map(pages: &Self, index: usize) -> PageMapping<'_, AtomicMappingInfo> {
+        pages.kmap_atomic_index(index)
     }
 }
 
-/// Mapping actions to map and unmap pages with the `kmap_local_page` interface
-impl MappingActions<LocalMappingInfo> for Pages<0> {
+/// Mapping actions to map pages with the regular `kmap` interface
+impl<const ORDER: u32> MappingActions<NormalMappingInfo> for Pages<ORDER> {
     #[inline(always)]
-    fn map(pages: &Pages<0>) -> PageMapping<'_, LocalMappingInfo> {
-        pages.kmap_local()
+    fn map(pages: &Self, index: usize) -> PageMapping<'_, NormalMappingInfo> {
+        pages.kmap_index(index)
     }
+}
 
-    /// Unmap a page specified by `mapping`
-    ///
-    /// # Safety
-    ///
-    /// Must only be called by `PageMapping::drop()`.
+/// Mapping actions to map pages with the `kmap_local_page` interface
+impl<const ORDER: u32> MappingActions<LocalMappingInfo> for Pages<ORDER> {
     #[inline(always)]
-    unsafe fn unmap(mapping: &PageMapping<'_, LocalMappingInfo>) {
-        unsafe { bindings::kunmap_local(mapping.ptr) };
+    fn map(pages: &Self, index: usize) -> PageMapping<'_, LocalMappingInfo> {
+        pages.kmap_local_index(index)
     }
 }
 
 /// An owned page mapping. When this struct is dropped, the page is unmapped.
-pub struct PageMapping<'a, I: MappingInfo>
-where
-    Pages<0>: MappingActions<I>,
-{
+pub struct PageMapping<'a, I: MappingInfo> {
     page: *mut bindings::page,
     ptr: *mut core::ffi::c_void,
     _phantom: PhantomData<&'a i32>,
     _phantom2: PhantomData<I>,
 }
 
-impl<'a, I: MappingInfo> PageMapping<'a, I>
-where
-    Pages<0>: MappingActions<I>,
-{
+impl<'a, I: MappingInfo> PageMapping<'a, I> {
     /// Return a pointer to the wrapped `struct page`
     #[inline(always)]
     pub fn get_ptr(&self) -> *mut core::ffi::c_void {
@@ -344,10 +412,7 @@ where
     }
 }
 
-impl<'a, I: MappingInfo> core::ops::Deref for PageMapping<'a, I>
-where
-    Pages<0>: MappingActions<I>,
-{
+impl<'a, I: MappingInfo> core::ops::Deref for PageMapping<'a, I> {
     type Target = [u8];
 
     fn deref(&self) -> &Self::Target {
@@ -358,13 +423,10 @@ where
 // Because we do not have Drop specialization, we have to do this dance. Life
 // would be much more simple if we could have `impl Drop for PageMapping<'_,
 // Atomic>` and `impl Drop for PageMapping<'_, NotAtomic>`
-impl<I: MappingInfo> Drop for PageMapping<'_, I>
-where
-    Pages<0>: MappingActions<I>,
-{
+impl<I: MappingInfo> Drop for PageMapping<'_, I> {
     #[inline(always)]
     fn drop(&mut self) {
         // SAFETY: We are OK to call this because we are `PageMapping::drop()`
-        unsafe { <Pages<0> as MappingActions<I>>::unmap(self) }
+        unsafe { I::unmap(self) }
     }
 }
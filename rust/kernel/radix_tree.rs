@@ -27,15 +27,13 @@ pub struct RadixTree<V: ForeignOwnable> {
 }
 
 impl<V: ForeignOwnable> RadixTree<V> {
-    /// Create a new radix tree
-    ///
-    /// Note: This function allocates memory with `GFP_ATOMIC`.
-    pub fn new() -> Result<Self> {
+    /// Create a new radix tree whose internal nodes are allocated with `gfp`.
+    pub fn new(gfp: bindings::gfp_t) -> Result<Self> {
         let tree = Pin::from(Box::try_new(Opaque::uninit())?);
 
         // SAFETY: `tree` points to allocated but not initialized memory. This
         // call will initialize the memory.
-        unsafe { bindings::init_radix_tree(tree.get(), bindings::GFP_ATOMIC) };
+        unsafe { bindings::init_radix_tree(tree.get(), gfp) };
 
         Ok(Self {
             tree,
@@ -43,14 +41,72 @@ impl<V: ForeignOwnable> RadixTree<V> {
         })
     }
 
-    /// Try to insert a value into the tree
-    pub fn try_insert(&mut self, key: Key, value: V) -> Result<()> {
-        // SAFETY: `self.tree` points to a valid and initialized `struct radix_tree`
+    /// Try to insert a value into the tree.
+    ///
+    /// Node allocation for the insert itself never blocks: `gfp` is used to preload the current
+    /// CPU's pool of nodes via `radix_tree_preload` beforehand, so this may be called from
+    /// GFP_KERNEL context even though the tree was created with a GFP that cannot sleep, or vice
+    /// versa.
+    pub fn try_insert(&mut self, key: Key, value: V, gfp: bindings::gfp_t) -> Result<()> {
+        // SAFETY: FFI call with no special requirements, other than the matching
+        // `radix_tree_preload_end()` below.
+        to_result(unsafe { bindings::radix_tree_preload(gfp) })?;
+
+        // SAFETY: `radix_tree_preload` above succeeded, so it must be paired with a call to
+        // `radix_tree_preload_end()`, which this guard provides.
+        let _preload = ScopeGuard::new(|| unsafe { bindings::radix_tree_preload_end() });
+
+        // SAFETY: `self.tree` points to a valid and initialized `struct radix_tree`, and the
+        // preload above guarantees that the node allocation this may need to perform will not
+        // block.
         let ret =
             unsafe { bindings::radix_tree_insert(self.tree.get(), key, value.into_foreign() as _) };
         to_result(ret)
     }
 
+    /// Sets `tag` on the entry at `key`. Does nothing if there is no entry at `key`.
+    pub fn set_tag(&mut self, key: Key, tag: Tag) {
+        // SAFETY: `self.tree` points to a valid and initialized `struct radix_tree`
+        unsafe { bindings::radix_tree_tag_set(self.tree.get(), key, tag.0) };
+    }
+
+    /// Clears `tag` on the entry at `key`. Does nothing if there is no entry at `key`.
+    pub fn clear_tag(&mut self, key: Key, tag: Tag) {
+        // SAFETY: `self.tree` points to a valid and initialized `struct radix_tree`
+        unsafe { bindings::radix_tree_tag_clear(self.tree.get(), key, tag.0) };
+    }
+
+    /// Returns whether `tag` is set on the entry at `key`.
+    pub fn get_tag(&self, key: Key, tag: Tag) -> bool {
+        // SAFETY: `self.tree` points to a valid and initialized `struct radix_tree`
+        unsafe { bindings::radix_tree_tag_get(self.tree.get(), key, tag.0) != 0 }
+    }
+
+    /// Returns an iterator over the entries tagged with `tag`, in key order.
+    ///
+    /// This walks the tree the same way [`Drop`] does, via `radix_tree_iter_init` and
+    /// `radix_tree_next_chunk`/`radix_tree_next_slot`, but restricted to `tag` and yielding
+    /// borrows instead of removing what it visits. This is what makes the tree useful for
+    /// page-cache-style "find all dirty entries" scans.
+    pub fn iter_tagged(&self, tag: Tag) -> TaggedIter<'_, V> {
+        let mut iter = bindings::radix_tree_iter {
+            index: 0,
+            next_index: 0,
+            tags: 0,
+            node: core::ptr::null_mut(),
+        };
+
+        // SAFETY: `iter` is valid, as it was just initialized on the stack above.
+        let slot = unsafe { bindings::radix_tree_iter_init(&mut iter, 0) };
+
+        TaggedIter {
+            tree: self,
+            iter,
+            slot,
+            tag,
+        }
+    }
+
     /// Search for `key` in the map. Returns a reference to the associated
     /// value if found.
     pub fn get(&self, key: Key) -> Option<V::Borrowed<'_>> {
@@ -135,6 +191,67 @@ impl<V: ForeignOwnable> Drop for RadixTree<V> {
     }
 }
 
+/// One of the small number of tags (dirty, writeback, ...) that can be set on a [`RadixTree`]
+/// entry and later used to restrict iteration with [`RadixTree::iter_tagged`].
+///
+/// This wraps the raw C tag index (e.g. `PAGECACHE_TAG_DIRTY`).
+#[derive(Clone, Copy)]
+pub struct Tag(core::ffi::c_uint);
+
+impl Tag {
+    /// Creates a `Tag` from a raw C tag index.
+    pub const fn from_raw(tag: core::ffi::c_uint) -> Self {
+        Self(tag)
+    }
+}
+
+/// An iterator over the entries of a [`RadixTree`] tagged with a given [`Tag`].
+///
+/// Created by [`RadixTree::iter_tagged`].
+pub struct TaggedIter<'a, V: ForeignOwnable> {
+    tree: &'a RadixTree<V>,
+    iter: bindings::radix_tree_iter,
+    slot: *mut *mut core::ffi::c_void,
+    tag: Tag,
+}
+
+impl<'a, V: ForeignOwnable> Iterator for TaggedIter<'a, V> {
+    type Item = (Key, V::Borrowed<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.slot.is_null() {
+            // SAFETY: `self.tree.tree` and `self.iter` are valid.
+            self.slot = unsafe {
+                bindings::radix_tree_next_chunk(
+                    self.tree.tree.get(),
+                    &mut self.iter,
+                    bindings::RADIX_TREE_ITER_TAGGED | self.tag.0,
+                )
+            };
+        }
+
+        if self.slot.is_null() {
+            return None;
+        }
+
+        // SAFETY: `self.slot` was just returned by `radix_tree_next_chunk`/`radix_tree_next_slot`
+        // and shown to be non-null, so it points to an entry created by a call to
+        // `ForeignOwnable::into_foreign()`.
+        let item = unsafe { *self.slot };
+        let index = self.iter.index;
+
+        // SAFETY: `self.tree.tree` is valid and `self.iter` is managed by
+        // `radix_tree_next_chunk()` and `radix_tree_next_slot()`. `self.slot` is not null.
+        self.slot = unsafe {
+            bindings::radix_tree_next_slot(self.slot, &mut self.iter, bindings::RADIX_TREE_ITER_TAGGED)
+        };
+
+        // SAFETY: `item` was created by a call to `ForeignOwnable::into_foreign()`. The borrow's
+        // lifetime is tied to `self.tree`, which outlives it.
+        Some((index, unsafe { V::borrow(item) }))
+    }
+}
+
 /// A mutable borrow of an object owned by a `RadixTree`
 pub struct MutBorrow<'a, V: ForeignOwnable> {
     guard: ScopeGuard<V, fn(V)>,
@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Memory shrinkers.
+//!
+//! Allows a driver to register a reclaim callback with the kernel's shrinker
+//! infrastructure, so that a pool of cached objects (for example, idle [`Pages`](crate::pages::Pages))
+//! can be handed back to the system when memory is scarce.
+//!
+//! C header: [`include/linux/shrinker.h`](srctree/include/linux/shrinker.h)
+
+use crate::{bindings, error::code::*, error::Result, str::CStr, types::ForeignOwnable};
+use core::marker::PhantomData;
+
+type ForeignBorrowed<'a, T> = <T as ForeignOwnable>::Borrowed<'a>;
+
+/// Implemented by drivers that keep a reclaimable pool of objects.
+///
+/// `Self::Data` is the driver state needed to walk the pool; it is stored in the registered
+/// [`Shrinker`] and handed to both callbacks.
+pub trait ShrinkerOperations: Sized {
+    /// Data associated with the registered shrinker, e.g. a handle to the driver's free list.
+    type Data: ForeignOwnable;
+
+    /// Returns the number of objects that could currently be freed.
+    fn count_objects(data: ForeignBorrowed<'_, Self::Data>) -> u64;
+
+    /// Frees up to `nr_to_scan` objects, returning how many were actually freed.
+    fn scan_objects(data: ForeignBorrowed<'_, Self::Data>, nr_to_scan: u64) -> u64;
+}
+
+/// A registered memory shrinker.
+///
+/// Dropping a `Shrinker` unregisters it and drops the `T::Data` it was registered with.
+///
+/// # Invariants
+///
+/// `self.0` is a non-null pointer returned by `bindings::shrinker_alloc` and registered with
+/// `bindings::shrinker_register`, whose `private_data` holds the `T::Data` passed to
+/// [`Shrinker::register`] via [`ForeignOwnable::into_foreign`].
+pub struct Shrinker<T: ShrinkerOperations>(*mut bindings::shrinker, PhantomData<T>);
+
+// SAFETY: A `Shrinker` just owns a registered `struct shrinker`; the C side does its own
+// locking, so it is safe to send and share the handle across threads.
+unsafe impl<T: ShrinkerOperations> Send for Shrinker<T> {}
+// SAFETY: see above.
+unsafe impl<T: ShrinkerOperations> Sync for Shrinker<T> {}
+
+impl<T: ShrinkerOperations> Shrinker<T> {
+    /// Registers a new shrinker named `name`, with the given `seeks` cost and `batch` size,
+    /// whose callbacks operate on `data`.
+    ///
+    /// `seeks` is typically `bindings::DEFAULT_SEEKS as i32`, and `batch` of `0` lets the
+    /// core pick a sane default.
+    pub fn register(name: &CStr, seeks: i32, batch: i32, data: T::Data) -> Result<Self> {
+        // SAFETY: `name` is only read for the duration of this call.
+        let shrinker = unsafe { bindings::shrinker_alloc(0, name.as_char_ptr()) };
+        if shrinker.is_null() {
+            return Err(ENOMEM);
+        }
+
+        let private_data = data.into_foreign();
+
+        // SAFETY: `shrinker` was just allocated by us above and is not yet registered, so we
+        // have exclusive access to it.
+        unsafe {
+            (*shrinker).count_objects = Some(Self::count_objects_callback);
+            (*shrinker).scan_objects = Some(Self::scan_objects_callback);
+            (*shrinker).seeks = seeks;
+            (*shrinker).batch = batch;
+            (*shrinker).private_data = private_data as *mut _;
+        }
+
+        // SAFETY: `shrinker` was fully initialized above, and is not registered elsewhere.
+        unsafe { bindings::shrinker_register(shrinker) };
+
+        // INVARIANTS: `shrinker` is registered and its `private_data` holds the `T::Data` we
+        // just converted with `into_foreign`.
+        Ok(Self(shrinker, PhantomData))
+    }
+
+    /// # Safety
+    ///
+    /// `shrink` must point to a valid `struct shrinker` registered by [`Shrinker::register`]
+    /// for this `T`.
+    unsafe extern "C" fn count_objects_callback(
+        shrink: *mut bindings::shrinker,
+        _sc: *mut bindings::shrink_control,
+    ) -> core::ffi::c_ulong {
+        // SAFETY: By the safety requirements of this function and the type invariants of
+        // `Shrinker`, `private_data` was produced by `T::Data::into_foreign()`.
+        let data = unsafe { T::Data::borrow((*shrink).private_data) };
+        T::count_objects(data) as core::ffi::c_ulong
+    }
+
+    /// # Safety
+    ///
+    /// `shrink` must point to a valid `struct shrinker` registered by [`Shrinker::register`]
+    /// for this `T`, and `sc` must be valid for the duration of the call.
+    unsafe extern "C" fn scan_objects_callback(
+        shrink: *mut bindings::shrinker,
+        sc: *mut bindings::shrink_control,
+    ) -> core::ffi::c_ulong {
+        // SAFETY: By the safety requirements of this function and the type invariants of
+        // `Shrinker`, `private_data` was produced by `T::Data::into_foreign()`.
+        let data = unsafe { T::Data::borrow((*shrink).private_data) };
+        // SAFETY: `sc` is valid for the duration of this call.
+        let nr_to_scan = unsafe { (*sc).nr_to_scan };
+        T::scan_objects(data, nr_to_scan) as core::ffi::c_ulong
+    }
+}
+
+impl<T: ShrinkerOperations> Drop for Shrinker<T> {
+    fn drop(&mut self) {
+        // SAFETY: `self.0` is valid by the type invariants of `Shrinker`, and its
+        // `private_data` was set from `T::Data::into_foreign()` in `Self::register`.
+        let private_data = unsafe { (*self.0).private_data };
+
+        // SAFETY: `self.0` was registered by `Self::register` and is not used after this
+        // call, by the type invariants of `Shrinker`.
+        unsafe { bindings::shrinker_free(self.0) };
+
+        // SAFETY: `private_data` was produced by `T::Data::into_foreign()` in
+        // `Self::register` and has not been reclaimed before, since `Shrinker` only ever
+        // borrows it in the callbacks above.
+        drop(unsafe { T::Data::from_foreign(private_data) });
+    }
+}
@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Time related primitives.
+//!
+//! This module contains the kernel APIs related to time that have been ported or wrapped for
+//! usage by Rust code in the kernel.
+
+use crate::bindings;
+
+/// A monotonic, nanosecond-precision timestamp or duration, backed by the kernel's `ktime_t`.
+///
+/// C header: [`include/linux/ktime.h`](srctree/include/linux/ktime.h)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ktime {
+    inner: bindings::ktime_t,
+}
+
+impl Ktime {
+    /// Create a `Ktime` from a raw `ktime_t`.
+    #[inline]
+    pub fn from_raw(inner: bindings::ktime_t) -> Self {
+        Self { inner }
+    }
+
+    /// Create a `Ktime` from a number of nanoseconds.
+    #[inline]
+    pub fn from_ns(ns: i64) -> Self {
+        Self { inner: ns }
+    }
+
+    /// Create a `Ktime` from a number of milliseconds.
+    #[inline]
+    pub fn from_ms(ms: i64) -> Self {
+        Self::from_ns(ms.saturating_mul(bindings::NSEC_PER_MSEC as i64))
+    }
+
+    /// Returns the number of nanoseconds.
+    #[inline]
+    pub fn to_ns(self) -> i64 {
+        self.inner
+    }
+
+    /// Returns the number of milliseconds.
+    #[inline]
+    pub fn to_ms(self) -> i64 {
+        self.inner / bindings::NSEC_PER_MSEC as i64
+    }
+}
+
+impl From<core::time::Duration> for Ktime {
+    /// Converts a [`Duration`](core::time::Duration) to a `Ktime`, saturating instead of
+    /// overflowing if the duration is too large to represent in nanoseconds as an `i64`.
+    fn from(delta: core::time::Duration) -> Self {
+        Self::from_ns(i64::try_from(delta.as_nanos()).unwrap_or(i64::MAX))
+    }
+}
+
+/// Converts a duration in milliseconds to the equivalent number of jiffies, mirroring the
+/// kernel's `msecs_to_jiffies()`.
+///
+/// Kernel code that schedules through jiffies-based APIs (e.g. `schedule_timeout()`) rather
+/// than an [`hrtimer`](crate::hrtimer), can use this instead of hand-rolling the
+/// `HZ`-dependent arithmetic.
+#[inline]
+pub fn msecs_to_jiffies(msecs: u64) -> core::ffi::c_ulong {
+    // `__msecs_to_jiffies()` is used instead of `msecs_to_jiffies()` because the latter is an
+    // inline function and cannot be bound to directly.
+    //
+    // `msecs` is clamped to `u32::MAX` rather than truncated, so an overlong duration maps to
+    // the largest jiffies offset instead of wrapping around to a short one.
+    //
+    // SAFETY: FFI call with no special requirements, well-defined for any `msecs` value.
+    unsafe { bindings::__msecs_to_jiffies(msecs.min(u32::MAX as u64) as core::ffi::c_uint) }
+}
@@ -8,6 +8,7 @@ use crate::{
     bindings,
     error::{to_result, Error, Result},
     types::{ForeignOwnable, Opaque, ScopeGuard}, init::PinInit,
+    prelude::*,
 };
 use core::{
     marker::{PhantomData, PhantomPinned},
@@ -37,6 +38,30 @@ pub mod flags {
     pub const ALLOC1: super::Flags = bindings::BINDINGS_XA_FLAGS_ALLOC1;
 }
 
+/// One of the three per-entry marks the C XArray supports (`XA_MARK_0..2`).
+///
+/// Marks let callers partition entries into subsets (e.g. "dirty", "pending")
+/// without needing a second data structure alongside the `XArray`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mark {
+    /// `XA_MARK_0`.
+    Mark0,
+    /// `XA_MARK_1`.
+    Mark1,
+    /// `XA_MARK_2`.
+    Mark2,
+}
+
+impl Mark {
+    fn as_raw(self) -> u32 {
+        match self {
+            Mark::Mark0 => bindings::BINDINGS_XA_MARK_0,
+            Mark::Mark1 => bindings::BINDINGS_XA_MARK_1,
+            Mark::Mark2 => bindings::BINDINGS_XA_MARK_2,
+        }
+    }
+}
+
 /// Wrapper for a value owned by the `XArray` which holds the `XArray` lock until dropped.
 pub struct Guard<'a, T: ForeignOwnable>(NonNull<T>, Pin<&'a XArray<T>>);
 
@@ -66,17 +91,41 @@ impl<'a, T: ForeignOwnable> Guard<'a, T> {
     }
 }
 
+/// Crate-local replacement for `Into<&'a T>`, used to let [`Guard`] implement `Deref` for
+/// borrowed forms that cannot implement `Into` directly due to the orphan rules -- notably
+/// `ArcBorrow<'a, T>`, which cannot implement `Into<&'a T>` since neither the trait nor the
+/// reference type is local to `sync::arc`.
+pub(crate) trait IntoRef<'a, T: ?Sized> {
+    /// Converts a borrowed `ForeignOwnable` form into a plain reference.
+    fn into_ref(self) -> &'a T;
+}
+
+impl<'a, T: ?Sized> IntoRef<'a, T> for &'a T {
+    fn into_ref(self) -> &'a T {
+        self
+    }
+}
+
+impl<'a, T> IntoRef<'a, T> for crate::sync::ArcBorrow<'a, T> {
+    fn into_ref(self) -> &'a T {
+        // SAFETY: `ArcBorrow<'a, T>`'s invariant is that the backing `Arc`'s allocation
+        // outlives `'a`, so reborrowing the pointer behind its `Deref` impl for `'a`
+        // instead of the shorter lifetime of the local `&self` below is sound.
+        unsafe { &*(&*self as *const T) }
+    }
+}
+
 // Convenience impl for `ForeignOwnable` types whose `Borrowed`
 // form implements Deref.
 impl<'a, T: ForeignOwnable> Deref for Guard<'a, T>
 where
     T::Borrowed<'a>: Deref,
-    for<'b> T::Borrowed<'b>: Into<&'b <T::Borrowed<'a> as Deref>::Target>,
+    for<'b> T::Borrowed<'b>: IntoRef<'b, <T::Borrowed<'a> as Deref>::Target>,
 {
     type Target = <T::Borrowed<'a> as Deref>::Target;
 
     fn deref(&self) -> &Self::Target {
-        self.borrow().into()
+        self.borrow().into_ref()
     }
 }
 
@@ -84,7 +133,7 @@ impl<'a, T: ForeignOwnable> DerefMut for Guard<'a, T>
 where
     T::Borrowed<'a>: Deref,
     T::BorrowedMut<'a>: DerefMut,
-    for<'b> T::Borrowed<'b>: Into<&'b <T::Borrowed<'a> as Deref>::Target>,
+    for<'b> T::Borrowed<'b>: IntoRef<'b, <T::Borrowed<'a> as Deref>::Target>,
     for<'b> T::BorrowedMut<'b>: Into<&'b mut <T::Borrowed<'a> as Deref>::Target>,
 {
     fn deref_mut(&mut self) -> &mut Self::Target {
@@ -99,6 +148,52 @@ impl<'a, T: ForeignOwnable> Drop for Guard<'a, T> {
     }
 }
 
+/// Asserts that a [`ForeignOwnable`]'s backing storage is only reclaimed
+/// after an RCU grace period has elapsed, e.g. because `from_foreign` defers
+/// the actual drop via `call_rcu` rather than freeing synchronously.
+///
+/// # Safety
+///
+/// Implementers must guarantee that a value handed to
+/// [`ForeignOwnable::from_foreign`] remains valid for readers who observed
+/// its pointer inside an RCU read-side critical section, for the entire
+/// duration of that critical section. In practice this means the type's
+/// `from_foreign` must not free the value immediately but must schedule its
+/// destruction with `call_rcu` (or equivalent), as `Arc<T>` can be made to
+/// do.
+pub unsafe trait RcuForeignOwnable: ForeignOwnable {}
+
+/// Wrapper for a value borrowed from an [`XArray`] during an RCU read-side
+/// critical section, in place of the `xa_lock`-holding [`Guard`].
+///
+/// Concurrent lookups and concurrent mutations of the `XArray` may proceed
+/// while a `RcuGuard` is held; only the borrowed value itself is kept alive,
+/// by the RCU grace period guarantee [`RcuForeignOwnable`] asserts. The
+/// borrow must not escape the RCU read-side critical section, so it is tied
+/// to the lifetime of this guard, and `Drop` ends the critical section with
+/// `rcu_read_unlock`.
+pub struct RcuGuard<'a, T: RcuForeignOwnable>(NonNull<T>, PhantomData<&'a ()>);
+
+impl<'a, T: RcuForeignOwnable> RcuGuard<'a, T> {
+    /// Borrow the underlying value wrapped by the `RcuGuard`.
+    ///
+    /// Returns a `T::Borrowed` type for the owned `ForeignOwnable` type.
+    pub fn borrow(&self) -> T::Borrowed<'_> {
+        // SAFETY: The value is owned by the `XArray`, and remains valid for
+        // the duration of the RCU read-side critical section that this
+        // guard holds open, per the `RcuForeignOwnable` invariant.
+        unsafe { T::borrow(self.0.as_ptr() as _) }
+    }
+}
+
+impl<'a, T: RcuForeignOwnable> Drop for RcuGuard<'a, T> {
+    fn drop(&mut self) {
+        // SAFETY: `get_rcu` entered the RCU read-side critical section that
+        // this guard is ending.
+        unsafe { bindings::rcu_read_unlock() };
+    }
+}
+
 /// Represents a reserved slot in an `XArray`, which does not yet have a value but has an assigned
 /// index and may not be allocated by any other user. If the Reservation is dropped without
 /// being filled, the entry is marked as available again.
@@ -134,6 +229,130 @@ impl<'a, T: ForeignOwnable> Drop for Reservation<'a, T> {
     }
 }
 
+/// Iterator over the indices of an [`XArray`]'s entries carrying a given [`Mark`], returned by
+/// [`XArray::iter_marked`].
+///
+/// Holds the `xa_lock` until dropped.
+pub struct MarkedIndices<'a, T: ForeignOwnable> {
+    xa: Pin<&'a XArray<T>>,
+    mark: Mark,
+    index: core::ffi::c_ulong,
+    started: bool,
+}
+
+impl<'a, T: ForeignOwnable> Iterator for MarkedIndices<'a, T> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        // SAFETY: `self.xa` is always valid by the type invariant, and we hold the `xa_lock`
+        // for the duration of this iterator's lifetime.
+        let entry = unsafe {
+            if self.started {
+                bindings::xa_find_after(
+                    self.xa.xa.get(),
+                    &mut self.index,
+                    core::ffi::c_ulong::MAX,
+                    self.mark.as_raw(),
+                )
+            } else {
+                self.started = true;
+                bindings::xa_find(
+                    self.xa.xa.get(),
+                    &mut self.index,
+                    core::ffi::c_ulong::MAX,
+                    self.mark.as_raw(),
+                )
+            }
+        };
+
+        if entry.is_null() {
+            None
+        } else {
+            let found_index = self.index;
+            // SAFETY: `self.xa` is valid and we hold its `xa_lock`.
+            unsafe { skip_multi_index_range(self.xa.xa.get(), &mut self.index) };
+            Some(found_index as usize)
+        }
+    }
+}
+
+impl<'a, T: ForeignOwnable> Drop for MarkedIndices<'a, T> {
+    fn drop(&mut self) {
+        // SAFETY: `iter_marked` took the `xa_lock` that this releases.
+        unsafe { bindings::xa_unlock(self.xa.xa.get()) };
+    }
+}
+
+/// Borrowing iterator over `(index, value)` pairs of an [`XArray`]'s present entries, returned by
+/// [`XArray::iter`] and [`XArray::find_from`].
+///
+/// Holds the `xa_lock` until dropped.
+pub struct Iter<'a, T: ForeignOwnable> {
+    xa: Pin<&'a XArray<T>>,
+    index: core::ffi::c_ulong,
+    started: bool,
+}
+
+impl<'a, T: ForeignOwnable> Iterator for Iter<'a, T> {
+    type Item = (usize, T::Borrowed<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: `self.xa` is always valid by the type invariant, and we hold the `xa_lock`
+        // for the duration of this iterator's lifetime.
+        let entry = unsafe {
+            if self.started {
+                bindings::xa_find_after(
+                    self.xa.xa.get(),
+                    &mut self.index,
+                    core::ffi::c_ulong::MAX,
+                    bindings::BINDINGS_XA_PRESENT,
+                )
+            } else {
+                self.started = true;
+                bindings::xa_find(
+                    self.xa.xa.get(),
+                    &mut self.index,
+                    core::ffi::c_ulong::MAX,
+                    bindings::BINDINGS_XA_PRESENT,
+                )
+            }
+        };
+
+        if entry.is_null() {
+            None
+        } else {
+            let found_index = self.index;
+            // SAFETY: `self.xa` is valid and we hold its `xa_lock`.
+            unsafe { skip_multi_index_range(self.xa.xa.get(), &mut self.index) };
+            // SAFETY: `entry` was just returned by `xa_find`/`xa_find_after` on an `XArray`
+            // whose `xa_lock` we hold for as long as this borrow (tied to `'a`) can live.
+            Some((found_index as usize, unsafe { T::borrow(entry as _) }))
+        }
+    }
+}
+
+impl<'a, T: ForeignOwnable> Drop for Iter<'a, T> {
+    fn drop(&mut self) {
+        // SAFETY: `iter`/`find_from` took the `xa_lock` that this releases.
+        unsafe { bindings::xa_unlock(self.xa.xa.get()) };
+    }
+}
+
+/// Advances `*index` to the last index covered by the multi-index entry found there (a no-op if
+/// the entry at `*index` has order 0), so that a following `xa_find_after` resumes the walk past
+/// it instead of landing on one of its sibling indices and re-visiting the same logical entry.
+///
+/// # Safety
+///
+/// `xa` must be a valid, locked (or otherwise exclusively owned) `xarray`.
+unsafe fn skip_multi_index_range(xa: *mut bindings::xarray, index: &mut core::ffi::c_ulong) {
+    // SAFETY: The caller guarantees `xa` is valid and locked/exclusively owned.
+    let order = unsafe { bindings::xa_get_order(xa, *index) };
+    if order > 0 {
+        *index |= (1 << order) - 1;
+    }
+}
+
 /// An array which efficiently maps sparse integer indices to owned objects.
 ///
 /// This is similar to a `Vec<Option<T>>`, but more efficient when there are holes in the
@@ -141,7 +360,9 @@ impl<'a, T: ForeignOwnable> Drop for Reservation<'a, T> {
 ///
 /// This structure is expected to often be used with an inner type that
 /// can be efficiently cloned, such as an `Arc<T>`.
+#[pin_data]
 pub struct XArray<T: ForeignOwnable> {
+    #[pin]
     xa: Opaque<bindings::xarray>,
     _p: PhantomData<T>,
     _q: PhantomPinned,
@@ -165,7 +386,33 @@ impl<T: ForeignOwnable> XArray<T> {
         }
     }
 
+    /// Returns an initializer for a new `XArray` with the given flags, for embedding as a
+    /// `#[pin]` field in a `#[pin_data]` struct initialized with `pin_init!`/`try_pin_init!`,
+    /// alongside primitives like [`Mutex`](crate::sync::Mutex) or
+    /// [`CondVar`](crate::sync::CondVar).
+    ///
+    /// Unlike [`Self::new`], this runs `xa_init_flags` in place on the final pinned address,
+    /// rather than constructing a by-value `XArray` that the caller must then pin separately.
+    pub fn new_pinned(flags: Flags) -> impl PinInit<Self> {
+        pin_init!(Self {
+            // INVARIANTS: We initialize `xa` in place with `xa_init_flags` below.
+            xa <- Opaque::ffi_init(move |place: *mut bindings::xarray| {
+                // SAFETY: By design of `pin_init!`, `place` points to a live allocation and
+                // does not need to be initialized prior to this call.
+                unsafe { bindings::xa_init_flags(place, flags) };
+            }),
+            _p: PhantomData,
+            _q: PhantomPinned,
+        })
+    }
+
     /// Replaces an entry with a new value, returning the old value (if any).
+    ///
+    /// If `T` is [`RcuForeignOwnable`], concurrent [`get_rcu`](Self::get_rcu)
+    /// callers may still be reading the old value; its actual deallocation
+    /// is not this call's concern; it happens when the returned `T` is
+    /// eventually dropped, which `T::from_foreign`'s RCU-delayed drop makes
+    /// safe to do immediately.
     pub fn replace(self: Pin<&Self>, index: usize, value: T) -> Result<Option<T>> {
         let new = value.into_foreign();
         // SAFETY: `new` just came from into_foreign(), and we dismiss this guard if
@@ -206,11 +453,92 @@ impl<T: ForeignOwnable> XArray<T> {
         Ok(())
     }
 
+    /// Stores `value` across the aligned `2^order` indices starting at `index`, returning the
+    /// old value (if any) that occupied any part of that range.
+    ///
+    /// `index` must itself be aligned to `2^order`. The whole range is one logical entry:
+    /// [`Self::get`] and [`Self::remove`] at any index within the range operate on `value` as a
+    /// whole, and iteration (see [`Self::iter`], [`Self::iter_marked`]) visits it once, not once
+    /// per covered index -- `T::from_foreign` is correspondingly called exactly once per
+    /// logical entry, never once per index, which would double-drop it.
+    pub fn store_order(self: Pin<&Self>, index: usize, value: T, order: u32) -> Result<Option<T>> {
+        let span: usize = 1usize
+            .checked_shl(order)
+            .ok_or(crate::error::code::EINVAL)?;
+        let last = index
+            .checked_add(span - 1)
+            .ok_or(crate::error::code::EINVAL)?;
+
+        let new = value.into_foreign();
+        // SAFETY: `new` just came from into_foreign(), and we dismiss this guard if
+        // the xa_store_range operation succeeds and takes ownership of the pointer.
+        let guard = ScopeGuard::new(|| unsafe {
+            T::from_foreign(new);
+        });
+
+        // SAFETY: `self.xa` is always valid by the type invariant, and we are storing
+        // a `T::into_foreign()` result which upholds the later invariants.
+        let old = unsafe {
+            bindings::xa_store_range(
+                self.xa.get(),
+                index.try_into()?,
+                last.try_into()?,
+                new as *mut _,
+                bindings::GFP_KERNEL,
+            )
+        };
+
+        // SAFETY: `xa_store_range` returns the old entry previously occupying any part
+        // of this range on success, or a XArray result, which can be turned into an
+        // errno through `xa_err`.
+        to_result(unsafe { bindings::xa_err(old) })?;
+        guard.dismiss();
+
+        Ok(if old.is_null() {
+            None
+        } else {
+            // SAFETY: The old value must have been stored by `store_order`, `replace`,
+            // or `alloc_limits_opt`, all of which ensure non-NULL entries are valid
+            // `ForeignOwnable` pointers.
+            Some(unsafe { T::from_foreign(old) })
+        })
+    }
+
+    /// Allocates an aligned, `2^order`-sized free range within `[min, max]` and stores `value`
+    /// across it, returning the first index of the range.
+    ///
+    /// Unlike [`Self::alloc_limits`], this has no single C API to lean on for the alignment
+    /// search, so it probes successive `2^order`-aligned candidates with [`Self::get`] until it
+    /// finds one that is entirely free, then commits it with [`Self::store_order`].
+    pub fn alloc_order(self: Pin<&Self>, value: T, order: u32, min: u32, max: u32) -> Result<usize> {
+        let span: u32 = 1u32.checked_shl(order).ok_or(crate::error::code::EINVAL)?;
+        let mut first = min.div_ceil(span).saturating_mul(span);
+
+        loop {
+            let last = first
+                .checked_add(span - 1)
+                .ok_or(crate::error::code::ENOSPC)?;
+            if last > max {
+                return Err(crate::error::code::ENOSPC);
+            }
+
+            if (first..=last).all(|i| self.get(i as usize).is_none()) {
+                self.store_order(first as usize, value, order)?;
+                return Ok(first as usize);
+            }
+
+            first += span;
+        }
+    }
+
     /// Looks up and returns a reference to an entry in the array, returning a `Guard` if it
     /// exists.
     ///
     /// This guard blocks all other actions on the `XArray`. Callers are expected to drop the
     /// `Guard` eagerly to avoid blocking other users, such as by taking a clone of the value.
+    ///
+    /// If `index` falls inside a multi-index entry stored by [`Self::store_order`], this returns
+    /// that entry's single value, regardless of which covered index is passed.
     pub fn get(self: Pin<&Self>, index: usize) -> Option<Guard<'_, T>> {
         // SAFETY: `self.xa` is always valid by the type invariant.
         unsafe { bindings::xa_lock(self.xa.get()) };
@@ -227,7 +555,103 @@ impl<T: ForeignOwnable> XArray<T> {
         })
     }
 
+    /// Looks up and returns a reference to an entry in the array without
+    /// taking the `xa_lock`, returning a [`RcuGuard`] if it exists.
+    ///
+    /// This enters an RCU read-side critical section instead of locking the
+    /// `XArray`, so concurrent lookups and concurrent mutations (including
+    /// another thread's `replace`/`remove`) may proceed while the returned
+    /// guard is held. This requires `T` to be [`RcuForeignOwnable`], so that
+    /// the value cannot be freed out from under a concurrent reader: it is
+    /// only available for `T` whose `from_foreign` defers the actual drop
+    /// past the current grace period.
+    pub fn get_rcu(self: Pin<&Self>, index: usize) -> Option<RcuGuard<'_, T>>
+    where
+        T: RcuForeignOwnable,
+    {
+        // SAFETY: We have just entered the RCU read-side critical section
+        // that the returned `RcuGuard` (or the `ScopeGuard` below, if we
+        // don't find an entry) will end.
+        unsafe { bindings::rcu_read_lock() };
+
+        // SAFETY: We have just taken the RCU read lock above.
+        let guard = ScopeGuard::new(|| unsafe { bindings::rcu_read_unlock() });
+
+        // SAFETY: `self.xa` is always valid by the type invariant. Reading
+        // it under the RCU read-side critical section we are holding is the
+        // C API's documented lock-free lookup path.
+        let p = unsafe { bindings::xa_load(self.xa.get(), index.try_into().ok()?) };
+
+        NonNull::new(p as *mut T).map(|p| {
+            guard.dismiss();
+            RcuGuard(p, PhantomData)
+        })
+    }
+
+    /// Sets `mark` on the entry at `index`.
+    pub fn set_mark(self: Pin<&Self>, index: usize, mark: Mark) -> Result {
+        // SAFETY: `self.xa` is always valid by the type invariant.
+        unsafe { bindings::xa_set_mark(self.xa.get(), index.try_into()?, mark.as_raw()) };
+        Ok(())
+    }
+
+    /// Clears `mark` on the entry at `index`.
+    pub fn clear_mark(self: Pin<&Self>, index: usize, mark: Mark) -> Result {
+        // SAFETY: `self.xa` is always valid by the type invariant.
+        unsafe { bindings::xa_clear_mark(self.xa.get(), index.try_into()?, mark.as_raw()) };
+        Ok(())
+    }
+
+    /// Returns whether `mark` is set on the entry at `index`.
+    pub fn get_mark(self: Pin<&Self>, index: usize, mark: Mark) -> Result<bool> {
+        // SAFETY: `self.xa` is always valid by the type invariant.
+        Ok(unsafe { bindings::xa_get_mark(self.xa.get(), index.try_into()?, mark.as_raw()) })
+    }
+
+    /// Iterates over the indices of entries carrying `mark`, in ascending order.
+    ///
+    /// This mirrors the `xa_find`/`xa_find_after` walk [`XArray`]'s `Drop` impl uses to visit
+    /// every present entry, but filtered to `mark` instead of `XA_PRESENT`. The returned
+    /// iterator holds the `xa_lock` for its entire lifetime, so callers should collect or act on
+    /// the indices promptly rather than holding onto it, the same guidance as [`Self::get`].
+    pub fn iter_marked(self: Pin<&Self>, mark: Mark) -> MarkedIndices<'_, T> {
+        // SAFETY: `self.xa` is always valid by the type invariant.
+        unsafe { bindings::xa_lock(self.xa.get()) };
+
+        MarkedIndices {
+            xa: self,
+            mark,
+            index: 0,
+            started: false,
+        }
+    }
+
+    /// Iterates over `(index, value)` pairs for every present entry, in ascending order.
+    ///
+    /// This mirrors the `xa_find`/`xa_find_after` walk [`XArray`]'s `Drop` impl uses, but borrows
+    /// each entry instead of consuming it. The returned iterator holds the `xa_lock` for its
+    /// entire lifetime, so callers should clone out what they need and drop it eagerly rather
+    /// than holding onto it, the same guidance as [`Self::get`].
+    pub fn iter(self: Pin<&Self>) -> Iter<'_, T> {
+        self.find_from(0)
+    }
+
+    /// Like [`Self::iter`], but resumes the scan at or after `index` instead of from the start.
+    pub fn find_from(self: Pin<&Self>, index: usize) -> Iter<'_, T> {
+        // SAFETY: `self.xa` is always valid by the type invariant.
+        unsafe { bindings::xa_lock(self.xa.get()) };
+
+        Iter {
+            xa: self,
+            index: index as _,
+            started: false,
+        }
+    }
+
     /// Removes and returns an entry, returning it if it existed.
+    ///
+    /// If `index` falls inside a multi-index entry stored by [`Self::store_order`], this removes
+    /// and returns that entry's single value, regardless of which covered index is passed.
     pub fn remove(self: Pin<&Self>, index: usize) -> Option<T> {
         // SAFETY: `self.xa` is always valid by the type invariant.
         let p = unsafe { bindings::xa_erase(self.xa.get(), index.try_into().ok()?) };
@@ -331,6 +755,11 @@ impl<T: ForeignOwnable> Drop for XArray<T> {
 
             while !entry.is_null() {
                 T::from_foreign(entry);
+                // A multi-index entry stored by `store_order` is one logical `T`, so
+                // skip past the rest of its range before resuming the walk -- otherwise
+                // `xa_find_after` would step onto a sibling index still covered by the
+                // entry we just dropped, and we would double-`from_foreign` it.
+                skip_multi_index_range(self.xa.get(), &mut index);
                 entry = bindings::xa_find_after(
                     self.xa.get(),
                     &mut index,
@@ -2,13 +2,16 @@
 
 //! Rust hrtimer sample.
 
-use core::sync::atomic::AtomicBool;
-use core::sync::atomic::Ordering;
 use kernel::{
-    hrtimer::{Timer, TimerCallback, TimerCallbackContext, TimerPointer, TimerRestart},
+    completion::Completion,
+    hrtimer::{
+        ClockSource, Timer, TimerCallback, TimerCallbackContext, TimerMode, TimerPointer,
+        TimerRestart,
+    },
     impl_has_timer,
     prelude::*,
     sync::Arc,
+    time::Ktime,
 };
 
 module! {
@@ -25,28 +28,24 @@ struct RustMinimal {}
 struct PinMutIntrusiveTimer {
     #[pin]
     timer: Timer<Self>,
-    // TODO: Change to CondVar
-    flag: Arc<AtomicBool>,
+    completion: Arc<Completion>,
 }
 
-impl PinMutIntrusiveTimer
-{
-    fn new() -> impl PinInit<Self, kernel::error::Error>
-    {
+impl PinMutIntrusiveTimer {
+    fn new() -> impl PinInit<Self, kernel::error::Error> {
         try_pin_init!(Self {
-            timer <- Timer::new::<Pin<&mut _>>(),
-            flag: Arc::new(AtomicBool::new(false), kernel::alloc::flags::GFP_KERNEL)?,
+            timer <- Timer::new::<Pin<&mut _>>(ClockSource::Monotonic, TimerMode::Rel),
+            completion: Arc::pin_init(Completion::new(), kernel::alloc::flags::GFP_KERNEL)?,
         })
     }
 }
 
-impl TimerCallback for PinMutIntrusiveTimer
-{
-    type CallbackTarget<'a> =  Pin<&'a mut Self>;
+impl TimerCallback for PinMutIntrusiveTimer {
+    type CallbackTarget<'a> = Pin<&'a mut Self>;
 
     fn run(this: Self::CallbackTarget<'_>, _ctx: TimerCallbackContext<'_, Self>) -> TimerRestart {
         pr_info!("Timer called\n");
-        this.flag.store(true, Ordering::Relaxed);
+        this.completion.complete();
         TimerRestart::NoRestart
     }
 }
@@ -61,44 +60,38 @@ fn stack_timer() -> Result<()> {
     pr_info!("Timer on the stack\n");
 
     stack_try_pin_init!( let has_timer =? PinMutIntrusiveTimer::new() );
-    let flag_handle = has_timer.flag.clone();
-    let _handle = has_timer.as_mut().schedule(200_000_000);
+    let completion = has_timer.completion.clone();
+    let _handle = has_timer.as_mut().schedule_after(Ktime::from_ns(200_000_000));
 
-    while !flag_handle.load(Ordering::Relaxed) {
-        core::hint::spin_loop()
-    }
+    completion.wait();
 
     pr_info!("Flag raised\n");
     Ok(())
 }
 
-
 #[pin_data]
 struct ArcIntrusiveTimer {
     #[pin]
     timer: Timer<Self>,
-    // TODO: Change to CondVar
-    flag: AtomicBool,
+    #[pin]
+    completion: Completion,
 }
 
-impl ArcIntrusiveTimer
-{
-    fn new() -> impl PinInit<Self, kernel::error::Error>
-    {
+impl ArcIntrusiveTimer {
+    fn new() -> impl PinInit<Self, kernel::error::Error> {
         try_pin_init!(Self {
-            timer <- Timer::new::<Arc<_>>(),
-            flag: AtomicBool::new(false),
+            timer <- Timer::new::<Arc<_>>(ClockSource::Monotonic, TimerMode::Rel),
+            completion <- Completion::new(),
         })
     }
 }
 
-impl TimerCallback for ArcIntrusiveTimer
-{
-    type CallbackTarget<'a> =  Arc<Self>;
+impl TimerCallback for ArcIntrusiveTimer {
+    type CallbackTarget<'a> = Arc<Self>;
 
     fn run(this: Self::CallbackTarget<'_>, _ctx: TimerCallbackContext<'_, Self>) -> TimerRestart {
         pr_info!("Timer called\n");
-        this.flag.store(true, Ordering::Relaxed);
+        this.completion.complete();
         TimerRestart::NoRestart
     }
 }
@@ -111,10 +104,8 @@ fn arc_timer() -> Result<()> {
     pr_info!("Timer on the heap in Arc\n");
 
     let has_timer = Arc::pin_init(ArcIntrusiveTimer::new(), GFP_KERNEL)?;
-    let _handle = has_timer.clone().schedule(200_000_000);
-    while !has_timer.flag.load(Ordering::Relaxed) {
-        core::hint::spin_loop()
-    }
+    let _handle = has_timer.clone().schedule_after(Ktime::from_ns(200_000_000));
+    has_timer.completion.wait();
 
     pr_info!("Flag raised\n");
     Ok(())
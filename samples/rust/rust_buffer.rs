@@ -6,9 +6,11 @@
 //! file device interface.
 
 use kernel::{
-    file::{self, File},
+    file::{self, File, SeekFrom},
     io_buffer::{IoBufferReader, IoBufferWriter},
+    new_mutex,
     prelude::*,
+    sync::{Arc, Mutex},
 };
 
 module_misc_device! {
@@ -17,58 +19,87 @@ module_misc_device! {
     author: "Andrea Righi <andrea.righi@canonical.com>",
     description: "Memory-backed file implemented in Rust",
     license: "GPL v2",
+    params: {
+        buffer_size: usize {
+            default: 4096,
+            permissions: 0,
+            description: "Size in bytes of the buffer allocated for each open file",
+        },
+    },
 }
 
-// Size of the shared memory buffer (4K by default)
-const BUFSIZE : usize = 4096usize;
-
-// Shared memory buffer
-static mut BUFFER: [u8; BUFSIZE] = [0u8; BUFSIZE];
-
+#[pin_data]
 struct RustBuffer {
+    #[pin]
+    buffer: Mutex<Vec<u8>>,
 }
 
 #[vtable]
 impl file::Operations for RustBuffer {
-    type Data = Box<Self>;
+    type Data = Arc<Self>;
 
     fn open(_context: &Self::OpenData, _file: &File) -> Result<Self::Data> {
-        Ok(Box::try_new(Self { })?)
+        Arc::pin_init(
+            try_pin_init!(Self {
+                buffer <- new_mutex!(
+                    Vec::try_with_capacity(*buffer_size.read())?,
+                    "RustBuffer::buffer"
+                ),
+            }),
+            GFP_KERNEL,
+        )
     }
 
-    fn read(_this: &Self, _: &File, buf: &mut impl IoBufferWriter, offset: u64) -> Result<usize> {
-        let mut total_len = 0;
-        let off : usize = offset.try_into().unwrap();
-
-        while !buf.is_empty() {
-            let start : usize = off + total_len;
-            let len = buf.len().min(BUFSIZE - start);
-            if len <= 0 {
-                break;
-            }
-            unsafe {
-                buf.write_slice(&BUFFER[start .. start + len])?;
-            }
-            total_len += len;
+    fn read(
+        this: &Self,
+        _file: &File,
+        buf: &mut impl IoBufferWriter,
+        offset: u64,
+    ) -> Result<usize> {
+        let buffer = this.buffer.lock();
+        let off: usize = offset.try_into().unwrap_or(usize::MAX);
+        if off >= buffer.len() {
+            return Ok(0);
         }
-        Ok(total_len)
+
+        let len = buf.len().min(buffer.len() - off);
+        buf.write_slice(&buffer[off..off + len])?;
+        Ok(len)
     }
 
-    fn write(_this: &Self, _: &File, buf: &mut impl IoBufferReader, offset: u64) -> Result<usize> {
-        let mut total_len = 0;
-        let off : usize = offset.try_into().unwrap();
+    fn write(
+        this: &Self,
+        _file: &File,
+        buf: &mut impl IoBufferReader,
+        offset: u64,
+    ) -> Result<usize> {
+        let mut buffer = this.buffer.lock();
+        let off: usize = offset.try_into().map_err(|_| kernel::error::code::EINVAL)?;
+        let len = buf.len();
+        let end = off.checked_add(len).ok_or(kernel::error::code::EINVAL)?;
 
-        while !buf.is_empty() {
-            let start : usize = off + total_len;
-            let len = buf.len().min(BUFSIZE - start);
-            if len <= 0 {
-                break;
-            }
-            unsafe {
-                buf.read_slice(&mut BUFFER[start .. start + len])?;
-            }
-            total_len += len;
+        if end > buffer.len() {
+            buffer.try_resize(end, 0)?;
         }
-        Ok(total_len)
+
+        buf.read_slice(&mut buffer[off..end])?;
+        Ok(len)
+    }
+
+    fn seek(this: &Self, file: &File, offset: SeekFrom) -> Result<u64> {
+        let len: i64 = this
+            .buffer
+            .lock()
+            .len()
+            .try_into()
+            .map_err(|_| kernel::error::code::EINVAL)?;
+
+        let new_pos = match offset {
+            SeekFrom::Start(off) => off as i64,
+            SeekFrom::Current(off) => file.pos() as i64 + off,
+            SeekFrom::End(off) => len + off,
+        };
+
+        new_pos.try_into().map_err(|_| kernel::error::code::EINVAL)
     }
 }
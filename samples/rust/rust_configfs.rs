@@ -2,7 +2,6 @@
 
 //! Rust configfs sample.
 
-use kernel::alloc::flags;
 use kernel::c_str;
 use kernel::configfs;
 use kernel::configfs_attrs;
@@ -29,14 +28,14 @@ struct RustConfigfs {
 struct Configuration {
     foo: &'static CStr,
     #[pin]
-    bar: Mutex<(KBox<[u8; 4096]>, usize)>,
+    bar: Mutex<Vec<u8>>,
 }
 
 impl Configuration {
     fn new() -> impl PinInit<Self, Error> {
         try_pin_init!(Self {
             foo: c_str!("Hello World\n"),
-            bar <- new_mutex!((KBox::new([0;4096], flags::GFP_KERNEL)?,0)),
+            bar <- new_mutex!(Vec::new()),
         })
     }
 }
@@ -52,6 +51,8 @@ impl kernel::InPlaceModule for RustConfigfs {
             pinned: Arc<configfs::Group<Child>>,
             attributes: [
                 foo: FooOps,
+            ],
+            bin_attributes: [
                 bar: BarOps,
             ],
         };
@@ -103,22 +104,27 @@ impl configfs::AttributeOperations<Configuration> for FooOps {
 
 struct BarOps;
 
+// Unlike `FooOps`, `bar` is exposed as a binary attribute: its backing store grows with what is
+// written to it, so writes larger than a single page are not silently truncated the way they
+// would be through `AttributeOperations::store`, which is handed at most a single page.
 #[vtable]
-impl configfs::AttributeOperations<Configuration> for BarOps {
-    fn show(container: &Configuration, page: &mut [u8; 4096]) -> isize {
-        pr_info!("Show bar\n");
+impl configfs::BinAttributeOperations<Configuration> for BarOps {
+    const MAX_SIZE: usize = 1024 * 1024;
+
+    fn read(container: &Configuration, page: &mut [u8]) -> Result<usize> {
+        pr_info!("Read bar\n");
         let guard = container.bar.lock();
-        let data = guard.0.as_slice();
-        let len = guard.1;
-        page[0..len].copy_from_slice(&data[0..len]);
-        len as _
+        let len = core::cmp::min(page.len(), guard.len());
+        page[0..len].copy_from_slice(&guard[0..len]);
+        Ok(len)
     }
 
-    fn store(container: &Configuration, page: &[u8]) {
-        pr_info!("Store bar\n");
+    fn write(container: &Configuration, page: &[u8]) -> Result {
+        pr_info!("Write bar\n");
         let mut guard = container.bar.lock();
-        guard.0[0..page.len()].copy_from_slice(page);
-        guard.1 = page.len();
+        guard.clear();
+        guard.try_extend_from_slice(page)?;
+        Ok(())
     }
 }
 
@@ -31,11 +31,15 @@ impl kernel::Module for RustMinimal {
             use core::sync::atomic::Ordering;
             use kernel::{
                 alloc::flags,
-                hrtimer::{Timer, TimerCallback, TimerCallbackContext, TimerPointer},
+                hrtimer::{
+                    ClockSource, Timer, TimerCallback, TimerCallbackContext, TimerMode,
+                    TimerPointer,
+                },
                 impl_has_timer,
                 prelude::*,
                 stack_pin_init,
                 sync::Arc,
+                time::Ktime,
             };
 
             #[pin_data]
@@ -49,14 +53,13 @@ impl kernel::Module for RustMinimal {
             impl IntrusiveTimer {
                 fn new() -> impl PinInit<Self> {
                     pin_init!(Self {
-                        timer <- Timer::new(),
+                        timer <- Timer::new(ClockSource::Monotonic, TimerMode::Rel),
                         flag: AtomicBool::new(false),
                     })
                 }
             }
 
             impl TimerCallback for IntrusiveTimer {
-
                 fn run(&self, _ctx: TimerCallbackContext<'_, Self>) {
                     pr_info!("Timer called\n");
                     self.flag.store(true, Ordering::Relaxed);
@@ -68,7 +71,7 @@ impl kernel::Module for RustMinimal {
             }
 
             let has_timer = Arc::pin_init(IntrusiveTimer::new(), GFP_KERNEL)?;
-            let _handle = has_timer.clone().schedule(200_000_000);
+            let _handle = has_timer.clone().schedule_after(Ktime::from_ns(200_000_000));
             while !has_timer.flag.load(Ordering::Relaxed) {
                 core::hint::spin_loop()
             }